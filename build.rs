@@ -0,0 +1,65 @@
+//! Captures build metadata for `--version --format json`: the git commit the
+//! binary was built from, the compiler version, and the target triple. Each
+//! falls back to "unknown" (crates.io tarballs have no `.git`), so the build
+//! never fails over it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn main() {
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap_or_default());
+    let git_dir = manifest_dir.join(".git");
+    // Only this crate's own checkout: unpacked inside some other repository,
+    // `git` would report that one's commit.
+    let commit = if git_dir.exists() {
+        output("git", &["rev-parse", "--short=12", "HEAD"], &manifest_dir)
+    } else {
+        None
+    };
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = output(&rustc, &["--version"], &manifest_dir);
+    let target = std::env::var("TARGET").ok();
+
+    println!(
+        "cargo:rustc-env=FLASH_GIT_COMMIT={}",
+        commit.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "cargo:rustc-env=FLASH_RUSTC_VERSION={}",
+        rustc_version.as_deref().unwrap_or("unknown")
+    );
+    println!(
+        "cargo:rustc-env=FLASH_TARGET={}",
+        target.as_deref().unwrap_or("unknown")
+    );
+
+    println!("cargo:rerun-if-changed=build.rs");
+    // HEAD moves on checkout; the ref it names moves on commit. Only point
+    // cargo at files that exist, or it reruns this script on every build.
+    let head = git_dir.join("HEAD");
+    if head.exists() {
+        println!("cargo:rerun-if-changed={}", head.display());
+        if let Some(reference) = std::fs::read_to_string(&head)
+            .ok()
+            .and_then(|h| h.strip_prefix("ref: ").map(|r| r.trim().to_string()))
+        {
+            let path = git_dir.join(&reference);
+            if path.exists() {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+    }
+}
+
+fn output(program: &str, args: &[&str], dir: &Path) -> Option<String> {
+    let out = Command::new(program)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(out.stdout).ok()?.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
@@ -0,0 +1,51 @@
+//! Surfacing command failures outside the terminal.
+//!
+//! The [`Runner`](crate::Runner) reports failed one-shot runs to an optional
+//! [`FailureHook`]. The only shipped hook is the desktop notifier behind
+//! `--notify`, which needs the `desktop-notify` cargo feature; without it the
+//! flag degrades to a warning.
+
+use std::process::ExitStatus;
+
+/// Called whenever a one-shot run exits unsuccessfully.
+pub trait FailureHook: Send {
+    fn command_failed(&mut self, command: &[String], status: ExitStatus);
+}
+
+/// Build the hook behind `--notify`, or explain why there isn't one.
+pub fn desktop_notifier() -> Option<Box<dyn FailureHook>> {
+    #[cfg(feature = "desktop-notify")]
+    {
+        Some(Box::new(DesktopNotifier { warned: false }))
+    }
+    #[cfg(not(feature = "desktop-notify"))]
+    {
+        eprintln!(
+            "flash-watcher: --notify needs a build with the `desktop-notify` feature; ignoring"
+        );
+        None
+    }
+}
+
+/// Posts a desktop notification per failure. If the platform refuses (no
+/// notification daemon, headless session) it warns once and carries on.
+#[cfg(feature = "desktop-notify")]
+struct DesktopNotifier {
+    warned: bool,
+}
+
+#[cfg(feature = "desktop-notify")]
+impl FailureHook for DesktopNotifier {
+    fn command_failed(&mut self, command: &[String], status: ExitStatus) {
+        let shown = notify_rust::Notification::new()
+            .summary("flash-watcher: command failed")
+            .body(&format!("{} exited with {status}", command.join(" ")))
+            .show();
+        if let Err(err) = shown {
+            if !self.warned {
+                eprintln!("flash-watcher: desktop notification failed: {err}");
+                self.warned = true;
+            }
+        }
+    }
+}
@@ -8,20 +8,36 @@
 //! The bench is intentionally simple: 5 samples per measurement, report the
 //! median, no statistical claims beyond that. Users who want rigorous numbers
 //! reach for `hyperfine`; this is for a quick, honest sanity-check.
+//!
+//! Watchers settle at different speeds, so detection and memory samples start
+//! once each one is ready rather than after a fixed sleep, which flattered the
+//! quick starters and caught slow ones mid-scan. Flash says when it is ready
+//! through `--ready-fd`; the others are taken to be ready once their CPU use
+//! has stayed near zero for a few polls. A watcher that is not ready within
+//! [`READY_TIMEOUT`] is reported as `n/a`.
 
 use std::fs;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 use anyhow::{bail, Context, Result};
-use colored::Colorize;
 use sysinfo::{Pid, System};
 use tempfile::TempDir;
 
+use crate::style::{Colorize, DASH, RULE, SEP};
+
 const SAMPLES: usize = 5;
-const READY_WAIT: Duration = Duration::from_millis(1500);
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often a watcher without a ready signal is checked for settling, and
+/// the CPU use under which a poll counts as quiet.
+const SETTLE_POLL: Duration = Duration::from_millis(250);
+const SETTLE_CPU_PERCENT: f32 = 1.0;
+/// Consecutive quiet polls that mean the initial scan is over.
+const SETTLE_POLLS: u32 = 3;
 const DETECT_TIMEOUT: Duration = Duration::from_secs(5);
 const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
 
@@ -62,6 +78,16 @@ struct Competitor {
     /// Build the watcher command. Args: watch root, marker path the watcher's
     /// command should `touch` on every file change.
     invoke: fn(&Path, &Path) -> Command,
+    ready: Readiness,
+}
+
+/// How the harness tells that a freshly spawned watcher is watching.
+#[derive(Debug, Clone, Copy)]
+enum Readiness {
+    /// It writes a line to stdout once every watch is registered.
+    Signal,
+    /// No signal; wait for its CPU use to settle.
+    Settle,
 }
 
 fn discover_competitors(flash: &Path) -> Vec<Competitor> {
@@ -72,6 +98,11 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
         binary: flash.to_path_buf(),
         version: query_version(flash, &["--version"]).unwrap_or_else(|| "local".to_string()),
         invoke: invoke_flash,
+        ready: if cfg!(unix) {
+            Readiness::Signal
+        } else {
+            Readiness::Settle
+        },
     });
 
     if let Some(p) = which("watchexec") {
@@ -81,6 +112,7 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
             binary: p,
             version,
             invoke: invoke_watchexec,
+            ready: Readiness::Settle,
         });
     }
     if let Some(p) = which("nodemon") {
@@ -90,6 +122,7 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
             binary: p,
             version,
             invoke: invoke_nodemon,
+            ready: Readiness::Settle,
         });
     }
     if let Some(p) = which("cargo-watch") {
@@ -99,6 +132,7 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
             binary: p,
             version,
             invoke: invoke_cargo_watch,
+            ready: Readiness::Settle,
         });
     }
     if let Some(p) = which("entr") {
@@ -108,6 +142,7 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
             binary: p,
             version,
             invoke: invoke_entr,
+            ready: Readiness::Settle,
         });
     }
     found
@@ -116,13 +151,11 @@ fn discover_competitors(flash: &Path) -> Vec<Competitor> {
 fn invoke_flash(watch: &Path, marker: &Path) -> Command {
     // Pass the marker command as separate args so flash's shell-skip kicks in.
     let mut c = Command::new(crate_invocation_self());
-    c.arg("--fast")
-        .arg("--debounce")
-        .arg("10")
-        .arg("-w")
-        .arg(watch)
-        .arg("touch")
-        .arg(marker);
+    c.arg("--fast").arg("--debounce").arg("10");
+    if cfg!(unix) {
+        c.arg("--ready-fd").arg("1");
+    }
+    c.arg("-w").arg(watch).arg("touch").arg(marker);
     c
 }
 
@@ -266,14 +299,9 @@ fn detection_once(c: &Competitor) -> Result<Option<Duration>> {
     // Pre-create a file so the initial scan has something to settle on.
     fs::write(watch_dir.join("seed.txt"), "seed")?;
 
-    let mut child = (c.invoke)(&watch_dir, &marker)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .context("spawning competitor")?;
-
-    // Let the watcher reach steady state and absorb any initial-scan events.
-    thread::sleep(READY_WAIT);
+    let Some(mut child) = spawn_ready(c, &watch_dir, &marker)? else {
+        return Ok(None);
+    };
     let _ = fs::remove_file(&marker);
 
     let trigger = watch_dir.join("trigger.txt");
@@ -302,13 +330,9 @@ fn measure_memory(c: &Competitor) -> Result<Option<u64>> {
         // command, but the invoke API still wants somewhere to point.
         let marker = workspace.path().join("unused");
 
-        let mut child = (c.invoke)(&watch_dir, &marker)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .context("spawning competitor for memory probe")?;
-
-        thread::sleep(READY_WAIT);
+        let Some(mut child) = spawn_ready(c, &watch_dir, &marker)? else {
+            return Ok(None);
+        };
         let pid = Pid::from_u32(child.id());
         let mut sys = System::new();
         sys.refresh_process(pid);
@@ -328,6 +352,83 @@ fn measure_memory(c: &Competitor) -> Result<Option<u64>> {
 
 // ─── helpers ────────────────────────────────────────────────────────────────
 
+/// Start `c` watching `watch` and block until it is ready. `None` if it never
+/// got there within [`READY_TIMEOUT`].
+fn spawn_ready(c: &Competitor, watch: &Path, marker: &Path) -> Result<Option<Child>> {
+    let mut command = (c.invoke)(watch, marker);
+    command.stderr(Stdio::null());
+    command.stdout(match c.ready {
+        Readiness::Signal => Stdio::piped(),
+        Readiness::Settle => Stdio::null(),
+    });
+    let mut child = command.spawn().context("spawning competitor")?;
+    let ready = match c.ready {
+        Readiness::Signal => wait_for_signal(&mut child),
+        Readiness::Settle => wait_for_settle(&child),
+    };
+    if !ready {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Ok(None);
+    }
+    Ok(Some(child))
+}
+
+/// Wait for the first byte on the child's stdout. The rest is drained in the
+/// background so the watcher never blocks on, or dies of, a full pipe.
+fn wait_for_signal(child: &mut Child) -> bool {
+    let Some(mut stdout) = child.stdout.take() else {
+        return false;
+    };
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut byte = [0u8; 1];
+        if stdout.read_exact(&mut byte).is_ok() {
+            let _ = tx.send(());
+            let _ = io::copy(&mut stdout, &mut io::sink());
+        }
+    });
+    rx.recv_timeout(READY_TIMEOUT).is_ok()
+}
+
+fn wait_for_settle(child: &Child) -> bool {
+    let pid = Pid::from_u32(child.id());
+    let mut sys = System::new();
+    // The first refresh is the baseline CPU use is measured against.
+    sys.refresh_process(pid);
+    let deadline = Instant::now() + READY_TIMEOUT;
+    let mut settling = Settling::default();
+    while Instant::now() < deadline {
+        thread::sleep(SETTLE_POLL);
+        if !sys.refresh_process(pid) {
+            return false;
+        }
+        let cpu = sys.process(pid).map_or(0.0, |p| p.cpu_usage());
+        if settling.observe(cpu) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Counts consecutive quiet CPU readings for [`wait_for_settle`].
+#[derive(Debug, Default)]
+struct Settling {
+    quiet: u32,
+}
+
+impl Settling {
+    /// Record one reading; true once [`SETTLE_POLLS`] in a row were quiet.
+    fn observe(&mut self, cpu_percent: f32) -> bool {
+        if cpu_percent < SETTLE_CPU_PERCENT {
+            self.quiet += 1;
+        } else {
+            self.quiet = 0;
+        }
+        self.quiet >= SETTLE_POLLS
+    }
+}
+
 fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Option<std::process::ExitStatus> {
     let deadline = Instant::now() + timeout;
     loop {
@@ -370,8 +471,8 @@ fn shell_quote(s: &str) -> String {
 // ─── output ──────────────────────────────────────────────────────────────────
 
 fn print_header(competitors: &[Competitor]) {
-    println!("{}", "flash · benchmark".bold().bright_cyan());
-    println!("{}", "─".repeat(58).bright_black());
+    println!("{}", format!("flash {SEP} benchmark").bold().bright_cyan());
+    println!("{}", RULE.repeat(58).bright_black());
     println!(
         "{} {} {}",
         "host".bright_black(),
@@ -389,8 +490,10 @@ fn print_header(competitors: &[Competitor]) {
     {
         println!(
             "{}",
-            "note: running from a debug build — rebuild with --release for representative numbers"
-                .yellow()
+            format!(
+                "note: running from a debug build {DASH} rebuild with --release for representative numbers"
+            )
+            .yellow()
         );
     }
 
@@ -484,7 +587,12 @@ fn print_methodology() {
     );
     println!(
         "  {}",
-        "memory     resident set size after 1.5 s of steady state".bright_black()
+        "memory     resident set size once the watcher is ready".bright_black()
+    );
+    println!(
+        "  {}",
+        "ready      Flash: its --ready-fd signal; others: CPU under 1% for 3 polls 250 ms apart"
+            .bright_black()
     );
     println!(
         "  {}",
@@ -523,6 +631,17 @@ mod tests {
         assert_eq!(median(samples), Duration::from_millis(30));
     }
 
+    #[test]
+    fn settling_needs_consecutive_quiet_polls() {
+        let mut s = Settling::default();
+        assert!(!s.observe(0.2));
+        assert!(!s.observe(0.0));
+        assert!(!s.observe(35.0), "a busy poll starts the count over");
+        assert!(!s.observe(0.5));
+        assert!(!s.observe(0.1));
+        assert!(s.observe(0.0));
+    }
+
     #[test]
     fn shell_quote_escapes_quotes() {
         assert_eq!(shell_quote("ab'cd"), "'ab'\\''cd'");
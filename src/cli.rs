@@ -1,7 +1,13 @@
+use std::ffi::OsString;
 use std::path::PathBuf;
 
 use clap::Parser;
 
+use crate::config::parse_millis;
+use crate::runner::{ClearMode, RestartSignal};
+use crate::sink::PathDisplay;
+use crate::version::VersionFormat;
+
 // Optional fields use `Option<T>` rather than clap's `default_value` so the
 // merge logic in `Settings::build` can tell "the user set it to the default"
 // apart from "the user did not provide a value." That distinction matters when
@@ -9,21 +15,34 @@ use clap::Parser;
 #[derive(Parser, Debug, Clone)]
 #[command(
     name = "flash-watcher",
-    version,
     about = "A fast, predictable file watcher that runs commands when files change.",
     after_long_help = EXAMPLES,
     disable_help_subcommand = true,
+    disable_version_flag = true,
 )]
 pub struct Cli {
     /// Command (and arguments) to execute when matching files change.
     #[arg(trailing_var_arg = true)]
     pub command: Vec<String>,
 
+    /// The command followed `--`, so it runs as given and never through the
+    /// shell. Set by [`Cli::parse_args`].
+    #[arg(skip)]
+    pub literal_command: bool,
+
     /// Path or glob to watch. Pass repeatedly to watch several locations.
     #[arg(short, long, value_name = "PATH")]
     pub watch: Vec<String>,
 
-    /// File extensions to keep, comma-separated. Example: "rs,toml".
+    /// Resolve relative watch paths, pattern and ignore files, and output dirs against DIR.
+    #[arg(long, value_name = "DIR")]
+    pub root: Option<PathBuf>,
+
+    /// Also watch the paths read from stdin, one per line, e.g. piped from `git ls-files`.
+    #[arg(long)]
+    pub watch_stdin: bool,
+
+    /// File extensions to keep, comma-separated. Example: "rs,toml". A leading '!' excludes one: "rs,!generated.rs".
     #[arg(short, long, value_name = "LIST")]
     pub ext: Option<String>,
 
@@ -35,41 +54,315 @@ pub struct Cli {
     #[arg(short, long, value_name = "GLOB")]
     pub ignore: Vec<String>,
 
-    /// Debounce window in milliseconds. Defaults to 50.
-    #[arg(short, long, value_name = "MS")]
+    /// Read more ignore globs from a file, one per line ('#' starts a comment).
+    #[arg(long, value_name = "FILE")]
+    pub ignore_file: Option<PathBuf>,
+
+    /// Read more include globs from a file, one per line ('#' starts a comment). Also
+    /// accepted as --pattern-from.
+    #[arg(long, value_name = "FILE", visible_alias = "pattern-from")]
+    pub pattern_file: Option<PathBuf>,
+
+    /// Ignore everything under DIR, where the command writes its output (repeatable).
+    #[arg(long, value_name = "DIR")]
+    pub exclude_output: Vec<PathBuf>,
+
+    /// Ignore the scratch files Vim, Emacs, and JetBrains IDEs write around a save.
+    #[arg(long)]
+    pub ignore_editor_temp: bool,
+
+    /// Consider files and directories whose names start with '.' (the default).
+    #[arg(long, overrides_with = "exclude_hidden")]
+    pub include_hidden: bool,
+
+    /// Skip files and directories whose names start with '.', below each watch root.
+    #[arg(long, overrides_with = "include_hidden")]
+    pub exclude_hidden: bool,
+
+    /// Match globs and extensions regardless of case, e.g. on a case-insensitive file system.
+    #[arg(long)]
+    pub ignore_case_paths: bool,
+
+    /// Only let files trigger a run, never directories.
+    #[arg(long, overrides_with = "dirs_only")]
+    pub files_only: bool,
+
+    /// Only let directories trigger a run, e.g. to react to scaffolded modules.
+    #[arg(long, overrides_with = "files_only")]
+    pub dirs_only: bool,
+
+    /// Watch only the direct children of each watch root, not whole subtrees.
+    #[arg(long)]
+    pub no_recursive: bool,
+
+    /// If a watch root is deleted, watch it again once it is recreated.
+    #[arg(long)]
+    pub rewatch: bool,
+
+    /// Resolve symlinks in changed paths, so one file reached two ways runs once (the default).
+    #[arg(long, overrides_with = "no_canonicalize")]
+    pub canonicalize: bool,
+
+    /// Use changed paths as notify reports them, without resolving symlinks.
+    #[arg(long, overrides_with = "canonicalize")]
+    pub no_canonicalize: bool,
+
+    /// Ignore changes that only touch metadata (permissions, ownership, access time).
+    #[arg(long)]
+    pub content_only: bool,
+
+    /// Only react to files git tracks under each watch root.
+    #[arg(long)]
+    pub git_tracked_only: bool,
+
+    /// Skip watching directories that hold no file the filters accept.
+    #[arg(long)]
+    pub watch_extensions_only: bool,
+
+    /// Debounce window, e.g. 250ms or 1.5s; bare numbers are milliseconds. Defaults
+    /// to 50ms; 0 runs on every raw event.
+    #[arg(short, long, value_name = "TIME", value_parser = parse_millis)]
     pub debounce: Option<u64>,
 
+    /// Run the command at once for changes to paths matching this glob, skipping the debounce.
+    #[arg(long, value_name = "GLOB")]
+    pub no_debounce_pattern: Vec<String>,
+
+    /// Start the command at most once per this long (e.g. 5s), however often files change.
+    #[arg(long, value_name = "TIME", value_parser = parse_millis)]
+    pub throttle: Option<u64>,
+
+    /// Before running, wait until each changed file's size and mtime have held still this
+    /// long (e.g. 1s), so large files aren't read mid-write.
+    #[arg(long, value_name = "TIME", value_parser = parse_millis)]
+    pub await_stable: Option<u64>,
+
+    /// Skip a run when PATH, the command's output, is newer than every file that changed.
+    #[arg(long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
     /// Run the command once before watching.
     #[arg(short = 'n', long)]
     pub initial: bool,
 
+    /// Run the command once before watching, and exit with its status instead of watching if it fails.
+    #[arg(long)]
+    pub require_initial: bool,
+
+    /// At startup, run the command if matching files changed in the last N seconds.
+    #[arg(long, value_name = "SECONDS")]
+    pub changed_since: Option<u64>,
+
+    /// Exit after the command has run this many times, with its last exit status.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_runs: Option<u64>,
+
+    /// Post a desktop notification when the command fails (needs the `desktop-notify` feature).
+    #[arg(long)]
+    pub notify: bool,
+
+    /// Count the --initial run toward --max-runs.
+    #[arg(long)]
+    pub count_initial: bool,
+
+    /// Stop watching after the first failed run and exit with its status (for CI).
+    #[arg(long)]
+    pub fail_fast: bool,
+
+    /// Exit with status 0 once no matching file has changed for this many seconds.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub idle_timeout: Option<u64>,
+
+    /// How to show changed paths: file name only, relative to the watch root, or in full.
+    #[arg(long, value_name = "MODE")]
+    pub path_display: Option<PathDisplay>,
+
     /// Clear the terminal before each run.
     #[arg(short, long)]
     pub clear: bool,
 
+    /// When to clear the terminal before a run; overrides --clear. "on-success" keeps a
+    /// failed run's output on screen.
+    #[arg(long, value_name = "MODE")]
+    pub clear_mode: Option<ClearMode>,
+
     /// Restart the previous process on change instead of spawning a new one.
     #[arg(short, long)]
     pub restart: bool,
 
-    /// Load defaults from a YAML configuration file.
+    /// Stop a restarted command with this signal (e.g. SIGTERM) instead of SIGKILL (Unix).
+    #[arg(long, value_name = "SIGNAL", ignore_case = true)]
+    pub restart_signal: Option<RestartSignal>,
+
+    /// How long --restart-signal waits for the command to exit before killing it. Defaults to 5s.
+    #[arg(long, value_name = "TIME", value_parser = parse_millis)]
+    pub restart_grace: Option<u64>,
+
+    /// Run the command in its own process group and kill the whole group (Unix).
+    #[arg(long)]
+    pub process_group: bool,
+
+    /// Run up to N commands at once, each change batch as its own job, instead of one at a time.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub parallel: Option<u64>,
+
+    /// Buffer each run's output and print it between delimiters once the command exits.
+    #[arg(long)]
+    pub capture: bool,
+
+    /// Join the command's arguments with spaces and run the result through the shell.
+    #[arg(long)]
+    pub raw_command: bool,
+
+    /// On a change, run the script NAME from the changed file's directory, or the nearest parent up to the watch root, instead of the command.
+    #[arg(long, value_name = "NAME")]
+    pub command_from_matched_file: Option<String>,
+
+    /// Run this shell command once at startup and abort if it fails.
+    #[arg(long, value_name = "CMD")]
+    pub before: Option<String>,
+
+    /// Run this shell command once when Flash shuts down, even after a failed run.
+    #[arg(long, value_name = "CMD")]
+    pub after: Option<String>,
+
+    /// Refuse to start if the watch roots contain more than this many directories.
+    #[arg(long, value_name = "N")]
+    pub max_dirs: Option<usize>,
+
+    /// Exit with an error if Flash itself grows past this many megabytes of memory (RSS).
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+
+    /// How many pending change notifications to queue before dropping extras.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub buffer_size: Option<u64>,
+
+    /// Retry a watch that fails because the OS is out of file descriptors or watches up to N
+    /// times (at most 10). Defaults to 3.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(0..=10))]
+    pub watch_retries: Option<u32>,
+
+    /// Load defaults from a YAML configuration file. Repeat to layer files; later ones win.
     #[arg(short = 'f', long, value_name = "FILE")]
-    pub config: Option<PathBuf>,
+    pub config: Vec<PathBuf>,
+
+    /// Re-read the config file when it changes and apply its filters. By default edits to it are ignored.
+    #[arg(long)]
+    pub reload_config: bool,
+
+    /// Write a commented starter config to flash.yaml (or the --config path), then exit.
+    #[arg(long)]
+    pub init: bool,
+
+    /// Let --init overwrite an existing config file.
+    #[arg(long, requires = "init")]
+    pub force: bool,
+
+    /// Say whether a change to PATH would run the command, and which rule decided, then exit.
+    #[arg(long, value_name = "PATH")]
+    pub explain: Option<PathBuf>,
+
+    /// Print the effective configuration (CLI merged over the config file) as YAML, then exit.
+    #[arg(long)]
+    pub print_config: bool,
+
+    /// Check the configuration for anything that would stop Flash from starting, report it, then exit.
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// Print a JSON Schema for the config file, for editor completion and validation, then exit.
+    #[arg(long)]
+    pub config_schema: bool,
+
+    /// Write one byte to this file descriptor once every watch is registered (Unix only).
+    #[arg(long, value_name = "FD")]
+    pub ready_fd: Option<u32>,
+
+    /// Prefix every line Flash prints with "[NAME]", to tell several instances apart.
+    #[arg(long, value_name = "NAME")]
+    pub label: Option<String>,
+
+    /// Prefix every line Flash prints with the local time (HH:MM:SS).
+    #[arg(long)]
+    pub timestamps: bool,
+
+    /// Append a copy of everything Flash prints to this file, without colors.
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// List every watch root in the startup banner instead of a summary, and time each startup phase.
+    #[arg(short, long)]
+    pub verbose: bool,
 
     /// Quieter output and a leaner startup path. Useful for tight feedback loops.
     #[arg(long)]
     pub fast: bool,
 
+    /// Count accepted and rejected changes instead of running a command, with a
+    /// running tally and a summary on exit. Takes no command.
+    #[arg(long)]
+    pub count_only: bool,
+
     /// Print periodic performance statistics.
     #[arg(long)]
     pub stats: bool,
 
     /// How often to refresh statistics, in seconds. Defaults to 10.
-    #[arg(long, value_name = "SECONDS")]
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
     pub stats_interval: Option<u64>,
 
+    /// Print an aggregate summary every N seconds instead of a line per change.
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub summary_interval: Option<u64>,
+
+    /// When Flash exits, print one line with the changes seen, runs, failures, and uptime.
+    #[arg(long)]
+    pub print_events_summary_on_exit: bool,
+
     /// Benchmark Flash against other watchers installed on this machine, then exit.
     #[arg(long)]
     pub bench: bool,
+
+    /// Print which notify backend watches files on this platform, then exit.
+    #[arg(long)]
+    pub list_watchers: bool,
+
+    /// Print the version, then exit.
+    #[arg(short = 'V', long)]
+    pub version: bool,
+
+    /// Output format for --version: "text", or "json" with build metadata for bug reports.
+    #[arg(long, value_name = "FORMAT", requires = "version")]
+    pub format: Option<VersionFormat>,
+}
+
+impl Cli {
+    /// Parse `args` (program name first), noting whether the command came
+    /// after `--`. clap drops the separator, so that is read off `args`.
+    pub fn parse_args<I, T>(args: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let mut cli = Self::parse_from(&args);
+        cli.literal_command = follows_separator(&args, &cli.command);
+        cli
+    }
+}
+
+/// Whether `command` makes up the end of `args`, right after a `--`.
+fn follows_separator(args: &[OsString], command: &[String]) -> bool {
+    let Some(start) = args.len().checked_sub(command.len() + 1) else {
+        return false;
+    };
+    !command.is_empty()
+        && args[start] == "--"
+        && args[start + 1..]
+            .iter()
+            .zip(command)
+            .all(|(a, c)| a == c.as_str())
 }
 
 const EXAMPLES: &str = "\
@@ -78,4 +371,5 @@ EXAMPLES:
     flash-watcher -r -c -n npm run dev
     flash-watcher -e rs --debounce 250 cargo check
     flash-watcher -f flash.yaml
+    flash-watcher --init
 ";
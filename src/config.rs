@@ -1,111 +1,1019 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use anyhow::{bail, Context, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::cli::Cli;
+use crate::error::FlashError;
+use crate::filter::EntryKind;
+use crate::runner::{ClearMode, RestartSignal};
+use crate::sink::PathDisplay;
 
-const DEFAULT_DEBOUNCE_MS: u64 = 50;
+/// Where `--init` writes the starter config unless `--config` names a path.
+pub const DEFAULT_CONFIG_FILE: &str = "flash.yaml";
+
+pub(crate) const DEFAULT_DEBOUNCE_MS: u64 = 50;
 const DEFAULT_STATS_INTERVAL_SECS: u64 = 10;
+const DEFAULT_BUFFER_SIZE: u64 = 4096;
+const DEFAULT_WATCH_RETRIES: u32 = 3;
+/// Most watch retries allowed, which bounds the wait to about ten seconds.
+const MAX_WATCH_RETRIES: u32 = 10;
+const DEFAULT_RESTART_GRACE_MS: u64 = 5_000;
+/// Most runs one change may fan out into through `matrix`.
+pub(crate) const MAX_MATRIX_RUNS: usize = 64;
 
 /// On-disk YAML representation. Every field is optional so users can write the
 /// shortest config that does the job.
-#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
+    /// Another config file to start from, relative to this one. Keys set here
+    /// override it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub extends: Option<PathBuf>,
     #[serde(default)]
     pub command: Vec<String>,
-    pub watch: Option<Vec<String>>,
+    /// Values for `{key}` placeholders in `command`; it runs once per
+    /// combination.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matrix: Option<BTreeMap<String, Vec<String>>>,
+    /// Run instead of `command` when files are created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_create: Option<Vec<String>>,
+    /// Run instead of `command` when files are modified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_modify: Option<Vec<String>>,
+    /// Run instead of `command` when files are removed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_remove: Option<Vec<String>>,
+    /// Projects to watch side by side, each with its own watcher and runner.
+    /// An entry takes the keys it leaves unset from the rest of this file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projects: Option<Vec<Config>>,
+    /// Directory relative watch paths and filter files are resolved against,
+    /// relative to this config file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch: Option<Vec<WatchEntry>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub pattern: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ignore: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_output: Option<Vec<PathBuf>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_editor_temp: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore_case_paths: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entries: Option<EntryKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recursive: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rewatch: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonicalize: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git_tracked_only: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_extensions_only: Option<bool>,
+    /// Milliseconds. YAML may also spell it with a unit: `250ms`, `1.5s`.
+    #[serde(
+        default,
+        deserialize_with = "millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "millis_schema")]
     pub debounce: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub no_debounce_pattern: Option<Vec<String>>,
+    #[serde(
+        default,
+        deserialize_with = "millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "millis_schema")]
+    pub throttle: Option<u64>,
+    #[serde(
+        default,
+        deserialize_with = "millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "millis_schema")]
+    pub await_stable: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub initial: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_initial: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed_since: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_runs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count_initial: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fail_fast: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle_timeout: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path_display: Option<PathDisplay>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbose: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub clear: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clear_mode: Option<ClearMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub restart: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restart_signal: Option<RestartSignal>,
+    /// Milliseconds, like `debounce`.
+    #[serde(
+        default,
+        deserialize_with = "millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(schema_with = "millis_schema")]
+    pub restart_grace: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process_group: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parallel: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capture: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_command: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command_from_matched_file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_dirs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_memory: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stats: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub stats_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub print_events_summary_on_exit: Option<bool>,
+}
+
+/// One item of the YAML `watch:` list: either a bare path, or a path with its
+/// own filters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum WatchEntry {
+    Path(String),
+    Scoped(ScopedWatch),
+}
+
+impl WatchEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            Self::Path(path) => path,
+            Self::Scoped(scoped) => &scoped.path,
+        }
+    }
+}
+
+impl From<&str> for WatchEntry {
+    fn from(path: &str) -> Self {
+        Self::Path(path.to_string())
+    }
+}
+
+/// A watch root with filters that apply only to events under it. Each filter
+/// left unset falls back to the global `ext` / `pattern` / `ignore`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ScopedWatch {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ignore: Option<Vec<String>>,
 }
 
 impl Config {
-    pub fn load(path: &Path) -> Result<Self> {
-        let contents = std::fs::read_to_string(path)
-            .with_context(|| format!("reading config file '{}'", path.display()))?;
-        serde_yaml::from_str(&contents)
-            .with_context(|| format!("parsing config file '{}'", path.display()))
+    pub fn to_yaml(&self) -> Result<String> {
+        serde_yaml::to_string(self).context("serializing configuration")
+    }
+
+    /// JSON Schema for the config file, for `--config-schema`.
+    pub fn schema() -> Result<String> {
+        serde_json::to_string_pretty(&schemars::schema_for!(Config))
+            .context("serializing the config schema")
+    }
+
+    /// Read `path`, following its `extends` chain. The result has `extends`
+    /// resolved away.
+    pub fn load(path: &Path) -> Result<Self, FlashError> {
+        Self::load_chain(path, &mut Vec::new())
+    }
+
+    /// The files loading `path` reads: `path` and each config its `extends`
+    /// chain names, canonicalized, up to the first that fails to load.
+    pub(crate) fn sources(path: &Path) -> Vec<PathBuf> {
+        let mut seen = Vec::new();
+        let _ = Self::load_chain(path, &mut seen);
+        seen
+    }
+
+    fn load_chain(path: &Path, seen: &mut Vec<PathBuf>) -> Result<Self, FlashError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if seen.contains(&key) {
+            return Err(FlashError::ConfigCycle {
+                path: path.to_path_buf(),
+            });
+        }
+        seen.push(key);
+
+        let contents = std::fs::read_to_string(path).map_err(|source| FlashError::ConfigRead {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let mut config: Self =
+            serde_yaml::from_str(&contents).map_err(|source| FlashError::ConfigParse {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        config.rebase(dir, dir);
+        // A project without a root of its own takes the top-level one.
+        let base = config.root.clone().unwrap_or_else(|| dir.to_path_buf());
+        for project in config.projects.iter_mut().flatten() {
+            project.rebase(dir, &base);
+        }
+        if let Some(base) = config.extends.take() {
+            let base = dir.join(base);
+            config = config.inherit(Self::load_chain(&base, seen)?);
+        }
+        Ok(config)
+    }
+
+    /// Resolve `root` against `dir`, the directory of the file it was read
+    /// from, and the filter files and `output` against that root, or `base`
+    /// without one.
+    fn rebase(&mut self, dir: &Path, base: &Path) {
+        if let Some(root) = self.root.as_mut() {
+            *root = dir.join(&root);
+        }
+        let base = self.root.clone().unwrap_or_else(|| base.to_path_buf());
+        for file in [
+            &mut self.ignore_file,
+            &mut self.pattern_file,
+            &mut self.output,
+        ] {
+            if let Some(file) = file.as_mut() {
+                *file = base.join(&file);
+            }
+        }
+    }
+
+    /// Layer `configs` in order: each key takes its value from the last
+    /// config that sets it. Keys none of them set stay unset.
+    pub fn merge_all(configs: Vec<Self>) -> Self {
+        configs
+            .into_iter()
+            .fold(Self::default(), |merged, next| next.inherit(merged))
+    }
+
+    /// This `projects` entry, with the keys it leaves unset taken from
+    /// `base` and labelled after its first watch path unless it has a label.
+    fn into_project(self, index: usize, base: &Self) -> Self {
+        let label = self.label.clone().unwrap_or_else(|| {
+            match self.watch.as_ref().and_then(|watch| watch.first()) {
+                Some(entry) => entry.path().to_string(),
+                None => format!("project {}", index + 1),
+            }
+        });
+        Self {
+            label: Some(label),
+            ..self.inherit(base.clone())
+        }
+    }
+
+    /// Fill every key this config leaves unset from `base`.
+    fn inherit(self, base: Self) -> Self {
+        Self {
+            extends: None,
+            command: if self.command.is_empty() {
+                base.command
+            } else {
+                self.command
+            },
+            matrix: self.matrix.or(base.matrix),
+            on_create: self.on_create.or(base.on_create),
+            on_modify: self.on_modify.or(base.on_modify),
+            on_remove: self.on_remove.or(base.on_remove),
+            projects: self.projects.or(base.projects),
+            root: self.root.or(base.root),
+            watch: self.watch.or(base.watch),
+            ext: self.ext.or(base.ext),
+            pattern: self.pattern.or(base.pattern),
+            ignore: self.ignore.or(base.ignore),
+            ignore_file: self.ignore_file.or(base.ignore_file),
+            pattern_file: self.pattern_file.or(base.pattern_file),
+            exclude_output: self.exclude_output.or(base.exclude_output),
+            ignore_editor_temp: self.ignore_editor_temp.or(base.ignore_editor_temp),
+            include_hidden: self.include_hidden.or(base.include_hidden),
+            ignore_case_paths: self.ignore_case_paths.or(base.ignore_case_paths),
+            entries: self.entries.or(base.entries),
+            recursive: self.recursive.or(base.recursive),
+            rewatch: self.rewatch.or(base.rewatch),
+            canonicalize: self.canonicalize.or(base.canonicalize),
+            content_only: self.content_only.or(base.content_only),
+            git_tracked_only: self.git_tracked_only.or(base.git_tracked_only),
+            watch_extensions_only: self.watch_extensions_only.or(base.watch_extensions_only),
+            debounce: self.debounce.or(base.debounce),
+            no_debounce_pattern: self.no_debounce_pattern.or(base.no_debounce_pattern),
+            throttle: self.throttle.or(base.throttle),
+            await_stable: self.await_stable.or(base.await_stable),
+            output: self.output.or(base.output),
+            initial: self.initial.or(base.initial),
+            require_initial: self.require_initial.or(base.require_initial),
+            changed_since: self.changed_since.or(base.changed_since),
+            max_runs: self.max_runs.or(base.max_runs),
+            count_initial: self.count_initial.or(base.count_initial),
+            fail_fast: self.fail_fast.or(base.fail_fast),
+            idle_timeout: self.idle_timeout.or(base.idle_timeout),
+            notify: self.notify.or(base.notify),
+            path_display: self.path_display.or(base.path_display),
+            label: self.label.or(base.label),
+            timestamps: self.timestamps.or(base.timestamps),
+            log_file: self.log_file.or(base.log_file),
+            verbose: self.verbose.or(base.verbose),
+            clear: self.clear.or(base.clear),
+            clear_mode: self.clear_mode.or(base.clear_mode),
+            restart: self.restart.or(base.restart),
+            restart_signal: self.restart_signal.or(base.restart_signal),
+            restart_grace: self.restart_grace.or(base.restart_grace),
+            process_group: self.process_group.or(base.process_group),
+            parallel: self.parallel.or(base.parallel),
+            capture: self.capture.or(base.capture),
+            raw_command: self.raw_command.or(base.raw_command),
+            command_from_matched_file: self
+                .command_from_matched_file
+                .or(base.command_from_matched_file),
+            before: self.before.or(base.before),
+            after: self.after.or(base.after),
+            max_dirs: self.max_dirs.or(base.max_dirs),
+            max_memory: self.max_memory.or(base.max_memory),
+            buffer_size: self.buffer_size.or(base.buffer_size),
+            watch_retries: self.watch_retries.or(base.watch_retries),
+            stats: self.stats.or(base.stats),
+            stats_interval: self.stats_interval.or(base.stats_interval),
+            summary_interval: self.summary_interval.or(base.summary_interval),
+            print_events_summary_on_exit: self
+                .print_events_summary_on_exit
+                .or(base.print_events_summary_on_exit),
+        }
+    }
+}
+
+impl From<&Settings> for Config {
+    /// The inverse of [`Settings::merge`]: every value is filled in, so the
+    /// result round-trips to the same settings and doubles as a starter file.
+    fn from(settings: &Settings) -> Self {
+        Self {
+            extends: None,
+            command: settings.command.clone(),
+            matrix: (!settings.matrix.is_empty()).then(|| settings.matrix.clone()),
+            on_create: settings.on_create.clone(),
+            on_modify: settings.on_modify.clone(),
+            on_remove: settings.on_remove.clone(),
+            projects: (!settings.projects.is_empty()).then(|| settings.projects.clone()),
+            // Already applied to the paths below; repeating it would apply
+            // it twice when the output is loaded again.
+            root: None,
+            watch: Some(
+                settings
+                    .watch
+                    .iter()
+                    .map(
+                        |path| match settings.scoped.iter().find(|scoped| &scoped.path == path) {
+                            Some(scoped) => WatchEntry::Scoped(scoped.clone()),
+                            None => WatchEntry::Path(path.clone()),
+                        },
+                    )
+                    .collect(),
+            ),
+            ext: Some(settings.extensions.join(",")),
+            pattern: Some(settings.include.clone()),
+            ignore: Some(settings.ignore.clone()),
+            ignore_file: settings.ignore_file.clone(),
+            pattern_file: settings.pattern_file.clone(),
+            exclude_output: Some(settings.exclude_output.clone()),
+            ignore_editor_temp: Some(settings.ignore_editor_temp),
+            include_hidden: Some(settings.include_hidden),
+            ignore_case_paths: Some(settings.ignore_case_paths),
+            entries: Some(settings.entries),
+            recursive: Some(settings.recursive),
+            rewatch: Some(settings.rewatch),
+            canonicalize: Some(settings.canonicalize),
+            content_only: Some(settings.content_only),
+            git_tracked_only: Some(settings.git_tracked_only),
+            watch_extensions_only: Some(settings.watch_extensions_only),
+            debounce: Some(settings.debounce.as_millis() as u64),
+            no_debounce_pattern: Some(settings.no_debounce_pattern.clone()),
+            throttle: settings.throttle.map(|d| d.as_millis() as u64),
+            await_stable: settings.await_stable.map(|d| d.as_millis() as u64),
+            output: settings.output.clone(),
+            initial: Some(settings.initial),
+            require_initial: Some(settings.require_initial),
+            changed_since: settings.changed_since.map(|d| d.as_secs()),
+            max_runs: settings.max_runs,
+            count_initial: Some(settings.count_initial),
+            fail_fast: Some(settings.fail_fast),
+            idle_timeout: settings.idle_timeout.map(|d| d.as_secs()),
+            notify: Some(settings.notify),
+            path_display: Some(settings.path_display),
+            label: settings.label.clone(),
+            timestamps: Some(settings.timestamps),
+            log_file: settings.log_file.clone(),
+            verbose: Some(settings.verbose),
+            clear: Some(settings.clear),
+            clear_mode: settings.clear_mode,
+            restart: Some(settings.restart),
+            restart_signal: settings.restart_signal,
+            restart_grace: Some(settings.restart_grace.as_millis() as u64),
+            process_group: Some(settings.process_group),
+            parallel: settings.parallel,
+            capture: Some(settings.capture),
+            raw_command: Some(settings.raw_command),
+            command_from_matched_file: settings.command_from_matched_file.clone(),
+            before: settings.before.clone(),
+            after: settings.after.clone(),
+            max_dirs: settings.max_dirs,
+            max_memory: settings.max_memory,
+            buffer_size: Some(settings.buffer_size),
+            watch_retries: Some(settings.watch_retries),
+            stats: Some(settings.stats),
+            stats_interval: Some(settings.stats_interval.as_secs()),
+            summary_interval: settings.summary_interval.map(|d| d.as_secs()),
+            print_events_summary_on_exit: Some(settings.print_events_summary_on_exit),
+        }
     }
 }
 
+/// Commented template written by `--init`. Every key is present, set to the
+/// same default [`Settings`] would use, so the file documents the schema.
+const STARTER_CONFIG: &str = r#"# flash-watcher configuration.
+# CLI flags always win; values here fill in whatever the CLI leaves out.
+
+# Start from another config file (relative to this one) and override only
+# the keys set here.
+# extends: ../shared/flash.yaml
+
+# Command (and arguments) to run when matching files change.
+command: ["echo", "files changed"]
+
+# Run the command once per combination of these values, one after another,
+# with each {key} in it replaced: `command: [cargo, build, --target,
+# "{target}"]` builds for both triples below. At most 64 combinations.
+# matrix:
+#   target: [x86_64-unknown-linux-gnu, aarch64-unknown-linux-gnu]
+
+# Run these instead of `command` for changes of one kind; kinds left unset
+# run `command`. A run covering several kinds runs each one's command.
+# on_create: [./upload.sh]
+# on_remove: [./delete-remote.sh]
+
+# Watch several projects from one Flash, each with its own watcher and
+# command. An entry takes any key it leaves unset from the rest of this file,
+# and its lines are prefixed with its `label`, by default its first watch
+# path. `root` in an entry is relative to this file, like the one above.
+# projects:
+#   - watch: [services/api]
+#     command: [cargo, test]
+#   - watch: [services/web]
+#     command: [npm, test]
+#     label: web
+
+# Resolve relative watch paths, `ignore_file`, `pattern_file`, `output`, and
+# `exclude_output` against this directory instead of wherever Flash is
+# started; relative to this file. With `root: .` in a config at the top of a
+# repository, patterns written from there match wherever you launch (--root).
+# root: .
+
+# Paths or globs to watch. An entry can also carry its own filters, which
+# replace the global ones below for events under that path:
+#   - path: assets
+#     ext: "png,svg"
+watch:
+  - .
+
+# Comma-separated extensions to keep, e.g. "rs,toml". Empty keeps everything.
+# A leading "!" excludes one instead and wins over the rest: "rs,!generated.rs".
+# Keep the value quoted; YAML reads an unquoted "!" as a tag.
+ext: ""
+
+# Only react to paths matching at least one of these globs (empty = all).
+pattern: []
+
+# Drop paths matching any of these globs. Checked before `pattern`.
+ignore:
+  - "**/.git/**"
+  - "**/target/**"
+  - "**/node_modules/**"
+
+# More ignore globs, one per line, read from this file (blank lines and lines
+# starting with `#` are skipped). They apply on top of `ignore` everywhere.
+# The path is relative to this config file, or to `root` when set.
+# ignore_file: .flashignore
+
+# More include globs, one per line, read from this file in the same way.
+# They add to `pattern`.
+# pattern_file: .flashinclude
+
+# Directories the command writes into, such as `dist` or `build`. Nothing
+# inside them triggers a run, so a build can't set off the next one
+# (--exclude-output).
+exclude_output: []
+
+# Also ignore editor scratch files: Vim swap/backup/probe files, Emacs
+# auto-saves and locks, JetBrains safe-write temporaries.
+ignore_editor_temp: false
+
+# Consider paths with a component starting with "." below the watch root,
+# such as `.env` or `.config/app.toml`. `false` skips them all
+# (--exclude-hidden).
+include_hidden: true
+
+# Match globs and extensions regardless of case, so "**/*.jpg" also takes
+# "Photo.JPG" (--ignore-case-paths).
+ignore_case_paths: false
+
+# Which changed entries can trigger a run: all, files (--files-only), or dirs
+# (--dirs-only). A deleted path counts as a file.
+entries: all
+
+# Watch whole subtrees. `false` watches only the direct children of each
+# watch root (--no-recursive).
+recursive: true
+
+# When a watch root is deleted, watch it again once it is recreated. By
+# default Flash only warns that changes under it are no longer seen.
+rewatch: false
+
+# Resolve symlinked directories in changed paths before filtering, so a file
+# reachable through a symlink and directly triggers one run, and globs see
+# one spelling of it. `false` uses paths as reported (--no-canonicalize).
+canonicalize: true
+
+# Ignore changes that only touch metadata, such as a chmod or an access-time
+# update, and run only when a file's contents or name change (--content-only).
+content_only: false
+
+# Only react to files git tracks under each watch root, so untracked build
+# output never triggers a run. The list is refreshed every few seconds, so a
+# newly added file counts shortly after `git add` (--git-tracked-only).
+git_tracked_only: false
+
+# Skip watching directories with no file the filters accept, such as
+# fixtures next to the `.rs` files you care about, to save OS watches on
+# mixed trees. Directories created later are watched as they appear, and a
+# skipped directory is picked up within a second of a matching file landing in
+# it (--watch-extensions-only).
+watch_extensions_only: false
+
+# Debounce window in milliseconds; 0 runs on every raw event. Durations with
+# a unit work too: "250ms", "1.5s".
+debounce: 50
+
+# Globs for paths that skip the debounce window and run the command at once,
+# such as a file a tool touches to ask for a reload (--no-debounce-pattern).
+no_debounce_pattern: []
+
+# Minimum milliseconds between the starts of two runs, however often files
+# change. Changes inside the window are batched into one run at its end.
+# throttle: 5000
+
+# Before running, wait until each changed file's size and modification time
+# have held still this many milliseconds, so a large file still being written
+# isn't picked up half done. Deleted files don't wait (--await-stable).
+# await_stable: 1000
+
+# The file the command builds. A run is skipped when it is newer than every
+# file that changed, as make would; if it doesn't exist, the command runs.
+# Relative to `root`, like `ignore_file`. Startup runs always go ahead.
+# output: target/app
+
+# Run the command once on startup, before watching.
+initial: false
+
+# Run the command once on startup like `initial`, but if it fails, exit with
+# its status instead of watching. Has no effect with `restart`.
+require_initial: false
+
+# On startup, run the command if any matching file was modified within the
+# last N seconds, e.g. edits made while Flash was not running.
+# changed_since: 300
+
+# Exit after the command has run this many times. `count_initial` decides
+# whether the `initial` run counts toward the limit.
+# max_runs: 5
+count_initial: false
+
+# Stop watching as soon as a run fails and exit with the command's status,
+# e.g. in CI. Applies to every run, the initial one included; has no effect
+# with `restart`.
+fail_fast: false
+
+# Exit with status 0 once no matching file has changed for this many seconds,
+# e.g. to end a throwaway dev session on its own.
+# idle_timeout: 1800
+
+# Desktop notification when the command fails (needs the `desktop-notify`
+# cargo feature).
+notify: false
+
+# How changed paths are shown: basename, relative (to the watch root), or full.
+path_display: relative
+
+# Prefix every line Flash prints with "[label]" and/or the local time, to tell
+# several instances apart in one terminal.
+# label: api
+timestamps: false
+
+# Append a copy of everything Flash prints to this file, colors stripped. With
+# `capture`, the command's output goes in too. Changes to the file itself
+# never trigger a run.
+# log_file: flash.log
+
+# List every watch root in the startup banner. Past a handful, it otherwise
+# shows the first few and how many more there are (--verbose).
+verbose: false
+
+# Clear the terminal before each run.
+clear: false
+
+# Or choose when to clear, overriding `clear`: "always", "on-success" to
+# keep a failed run's output on screen until the next run after a success,
+# or "never" (--clear-mode).
+# clear_mode: on-success
+
+# Kill and relaunch a still-running command instead of waiting for it.
+restart: false
+
+# Stop a running command on restart with this signal, e.g. SIGTERM or SIGINT,
+# and give it `restart_grace` milliseconds to exit before killing it. By
+# default it is killed with SIGKILL right away. Unix only.
+# restart_signal: SIGTERM
+restart_grace: 5000
+
+# Start the command in its own process group and kill the whole group on
+# restart and exit, so servers launched by a shell script don't linger.
+# Unix only.
+process_group: false
+
+# Run up to this many commands at once instead of one at a time, for
+# independent work such as transcoding. Changes that arrive while a run is
+# going start another; each job's output lines are tagged "[job N]". Has no
+# effect with `restart`.
+# parallel: 4
+
+# Collect the output of each run and print it between two rules once the
+# command exits, set apart from Flash's own lines. By default the command
+# writes straight to the terminal. Has no effect with `restart`.
+capture: false
+
+# Join `command` with spaces and hand it to the shell, so list items like
+# "&&" or "|" act as shell syntax. By default each item is passed through
+# as one argument, exactly as written.
+raw_command: false
+
+# On a change, look for a script with this name in the changed file's
+# directory, then in each parent up to the watch root, and run the nearest
+# one found instead of `command`. Changes with no script nearby run
+# `command` as usual.
+# command_from_matched_file: build.sh
+
+# A shell command run once at startup, before anything is watched. If it
+# exits non-zero, Flash exits with the same status.
+# before: make generated.rs
+
+# A shell command run once on shutdown (Ctrl-C or `max_runs` reached), even
+# if the last run failed.
+# after: docker compose down
+
+# Refuse to start if the watch roots hold more directories than this.
+# max_dirs: 10000
+
+# Exit with an error if Flash's own memory use (RSS) grows past this many
+# megabytes, checked once a second. A guard against leaks in long sessions.
+# max_memory: 512
+
+# How many change notifications may queue up while the command runs. When
+# the queue is full, extras are dropped (and counted); the pending run
+# covers them anyway.
+buffer_size: 4096
+
+# How many times to retry a watch that fails because the OS is out of file
+# descriptors or watches, with a short backoff (at most 10). Directories that
+# still fail are reported and skipped, as long as at least one is watched.
+watch_retries: 3
+
+# Print periodic statistics, refreshed every `stats_interval` seconds.
+stats: false
+stats_interval: 10
+
+# Print an aggregate summary every N seconds instead of a line per change.
+# summary_interval: 10
+
+# When Flash exits, print one line with the changes it saw, how often the
+# command ran and failed, and how long the session lasted.
+print_events_summary_on_exit: false
+"#;
+
+/// Write [`STARTER_CONFIG`] to `path`. An existing file is left alone unless
+/// `force` is set.
+pub fn write_starter(path: &Path, force: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+    let mut file = match options.open(path) {
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            bail!(
+                "'{}' already exists; pass --force to overwrite it",
+                path.display()
+            )
+        }
+        other => other.with_context(|| format!("creating config file '{}'", path.display()))?,
+    };
+    file.write_all(STARTER_CONFIG.as_bytes())
+        .with_context(|| format!("writing config file '{}'", path.display()))
+}
+
 /// Fully-merged runtime configuration. CLI flags win; the config file fills any
 /// values the user did not supply on the command line.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Settings {
     pub command: Vec<String>,
+    /// Placeholder values to run `command` with, once per combination. Only
+    /// read from config files.
+    pub matrix: BTreeMap<String, Vec<String>>,
+    /// Commands that replace `command` for created, modified, and removed
+    /// files. Only read from config files.
+    pub on_create: Option<Vec<String>>,
+    pub on_modify: Option<Vec<String>>,
+    pub on_remove: Option<Vec<String>>,
+    /// Projects to watch side by side, each already filled in from the
+    /// top-level keys and labelled. Only read from config files.
+    pub projects: Vec<Config>,
+    /// What relative paths below are resolved against, if not the working
+    /// directory. Already applied to them by [`Settings::merge`].
+    pub root: Option<PathBuf>,
     pub watch: Vec<String>,
+    /// Config-file watch entries that carry their own filters.
+    pub scoped: Vec<ScopedWatch>,
     pub extensions: Vec<String>,
     pub include: Vec<String>,
     pub ignore: Vec<String>,
+    pub ignore_file: Option<PathBuf>,
+    pub pattern_file: Option<PathBuf>,
+    pub exclude_output: Vec<PathBuf>,
+    pub ignore_editor_temp: bool,
+    pub include_hidden: bool,
+    pub ignore_case_paths: bool,
+    pub entries: EntryKind,
+    pub recursive: bool,
+    pub rewatch: bool,
+    pub canonicalize: bool,
+    pub content_only: bool,
+    pub git_tracked_only: bool,
+    pub watch_extensions_only: bool,
     pub debounce: Duration,
+    pub no_debounce_pattern: Vec<String>,
+    pub throttle: Option<Duration>,
+    pub await_stable: Option<Duration>,
+    /// What the command produces, for skipping runs it is already newer
+    /// than (`--output`).
+    pub output: Option<PathBuf>,
     pub initial: bool,
+    /// A failing initial run ends the session (`--require-initial`). Implies
+    /// [`Settings::initial`].
+    pub require_initial: bool,
+    pub changed_since: Option<Duration>,
+    pub max_runs: Option<u64>,
+    pub count_initial: bool,
+    pub fail_fast: bool,
+    pub idle_timeout: Option<Duration>,
+    pub notify: bool,
+    pub path_display: PathDisplay,
+    pub label: Option<String>,
+    pub timestamps: bool,
+    pub log_file: Option<PathBuf>,
+    pub verbose: bool,
     pub clear: bool,
+    /// Overrides `clear` when set.
+    pub clear_mode: Option<ClearMode>,
     pub restart: bool,
+    pub restart_signal: Option<RestartSignal>,
+    pub restart_grace: Duration,
+    pub process_group: bool,
+    pub parallel: Option<u64>,
+    pub capture: bool,
+    pub raw_command: bool,
+    /// The command came after `--` on the command line, so it never goes
+    /// through the shell.
+    pub literal_command: bool,
+    /// `--command-from-matched-file`: the name of the script a change runs
+    /// in place of the command, if one is found near it.
+    pub command_from_matched_file: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub max_dirs: Option<usize>,
+    /// `--max-memory`, in megabytes.
+    pub max_memory: Option<u64>,
+    pub buffer_size: u64,
+    pub watch_retries: u32,
     pub stats: bool,
     pub stats_interval: Duration,
+    pub summary_interval: Option<Duration>,
+    pub print_events_summary_on_exit: bool,
     pub fast: bool,
+    /// Tally changes instead of running a command (`--count-only`). Not
+    /// read from config files.
+    pub count_only: bool,
 }
 
 impl Settings {
-    /// Combine CLI arguments with any referenced config file. The CLI is always
-    /// authoritative; the config file only fills gaps.
-    pub fn build(cli: Cli) -> Result<Self> {
-        let config = match cli.config.as_deref() {
-            Some(path) => Some(Config::load(path)?),
-            None => None,
+    /// Combine CLI arguments with any referenced config files, layered in the
+    /// order given. The CLI is always authoritative; the config files only
+    /// fill gaps.
+    pub fn build(cli: Cli) -> Result<Self, FlashError> {
+        let config = if cli.config.is_empty() {
+            None
+        } else {
+            let configs = cli
+                .config
+                .iter()
+                .map(|path| Config::load(path))
+                .collect::<Result<Vec<_>, _>>()?;
+            Some(Config::merge_all(configs))
         };
         Ok(Self::merge(cli, config))
     }
 
+    /// Reject settings that can't drive a watch session.
+    pub fn validate(&self) -> Result<(), FlashError> {
+        if self.command.is_empty() && !self.count_only {
+            return Err(FlashError::EmptyCommand);
+        }
+        if self.max_runs == Some(0) {
+            return Err(FlashError::ZeroMaxRuns);
+        }
+        if let Some((key, _)) = self.matrix.iter().find(|(_, values)| values.is_empty()) {
+            return Err(FlashError::EmptyMatrixKey { key: key.clone() });
+        }
+        let runs = self
+            .matrix
+            .values()
+            .fold(1usize, |runs, values| runs.saturating_mul(values.len()));
+        if runs > MAX_MATRIX_RUNS {
+            return Err(FlashError::MatrixTooLarge {
+                runs,
+                limit: MAX_MATRIX_RUNS,
+            });
+        }
+        // A zero interval would have the printer thread spin on a zero sleep.
+        if self.stats_interval.is_zero() {
+            return Err(FlashError::ZeroInterval {
+                key: "stats_interval",
+            });
+        }
+        if self.summary_interval.is_some_and(|i| i.is_zero()) {
+            return Err(FlashError::ZeroInterval {
+                key: "summary_interval",
+            });
+        }
+        Ok(())
+    }
+
     pub(crate) fn merge(cli: Cli, config: Option<Config>) -> Self {
-        let cfg = config.unwrap_or_default();
+        let mut cfg = config.unwrap_or_default();
+        let projects = cfg
+            .projects
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(i, project)| project.into_project(i, &cfg))
+            .collect();
 
+        let literal_command = cli.literal_command && !cli.command.is_empty();
         let command = if cli.command.is_empty() {
             cfg.command
         } else {
             cli.command
         };
 
-        let watch = if !cli.watch.is_empty() {
-            cli.watch
+        let (watch, scoped) = if !cli.watch.is_empty() {
+            (cli.watch, Vec::new())
         } else {
-            cfg.watch.unwrap_or_else(|| vec![".".to_string()])
+            match cfg.watch {
+                Some(entries) => split_watch_entries(entries),
+                None => (vec![".".to_string()], Vec::new()),
+            }
+        };
+
+        // A leading `~` means the home directory, as it would in the shell.
+        let watch: Vec<String> = watch.iter().map(|spec| expand_home(spec)).collect();
+        let scoped: Vec<ScopedWatch> = scoped
+            .into_iter()
+            .map(|entry| ScopedWatch {
+                path: expand_home(&entry.path),
+                pattern: entry.pattern.map(expand_homes),
+                ignore: entry.ignore.map(expand_homes),
+                ..entry
+            })
+            .collect();
+
+        // Relative paths name places under --root; absolute ones stay put.
+        let root = cli.root.or(cfg.root);
+        let (watch, scoped) = match &root {
+            Some(root) => (
+                watch
+                    .iter()
+                    .map(|spec| root.join(spec).display().to_string())
+                    .collect(),
+                scoped
+                    .into_iter()
+                    .map(|entry| ScopedWatch {
+                        path: root.join(&entry.path).display().to_string(),
+                        ..entry
+                    })
+                    .collect(),
+            ),
+            None => (watch, scoped),
+        };
+        let under_root = |path: PathBuf| match &root {
+            Some(root) => root.join(path),
+            None => path,
         };
 
         let extensions = cli
             .ext
             .or(cfg.ext)
-            .map(|raw| {
-                raw.split(',')
-                    .map(str::trim)
-                    .filter(|s| !s.is_empty())
-                    .map(str::to_owned)
-                    .collect()
-            })
+            .map(|raw| parse_extensions(&raw))
             .unwrap_or_default();
 
-        let include = if !cli.pattern.is_empty() {
+        let include = expand_homes(if !cli.pattern.is_empty() {
             cli.pattern
         } else {
             cfg.pattern.unwrap_or_default()
-        };
+        });
 
-        let ignore = if !cli.ignore.is_empty() {
+        let ignore = expand_homes(if !cli.ignore.is_empty() {
             cli.ignore
         } else {
             cfg.ignore.unwrap_or_default()
-        };
+        });
 
         let debounce_ms = cli.debounce.or(cfg.debounce).unwrap_or(DEFAULT_DEBOUNCE_MS);
+        let require_initial = cli.require_initial || cfg.require_initial.unwrap_or(false);
 
         let stats_interval_secs = cli
             .stats_interval
@@ -114,36 +1022,331 @@ impl Settings {
 
         Self {
             command,
+            matrix: cfg.matrix.unwrap_or_default(),
+            on_create: cfg.on_create,
+            on_modify: cfg.on_modify,
+            on_remove: cfg.on_remove,
+            projects,
+            root: root.clone(),
             watch,
+            scoped,
             extensions,
             include,
             ignore,
+            ignore_file: cli.ignore_file.map(under_root).or(cfg.ignore_file),
+            pattern_file: cli.pattern_file.map(under_root).or(cfg.pattern_file),
+            exclude_output: if cli.exclude_output.is_empty() {
+                cfg.exclude_output.unwrap_or_default()
+            } else {
+                cli.exclude_output
+            }
+            .into_iter()
+            .map(under_root)
+            .collect(),
+            ignore_editor_temp: cli.ignore_editor_temp || cfg.ignore_editor_temp.unwrap_or(false),
+            include_hidden: if cli.exclude_hidden || cli.include_hidden {
+                cli.include_hidden
+            } else {
+                cfg.include_hidden.unwrap_or(true)
+            },
+            ignore_case_paths: cli.ignore_case_paths || cfg.ignore_case_paths.unwrap_or(false),
+            entries: if cli.files_only {
+                EntryKind::Files
+            } else if cli.dirs_only {
+                EntryKind::Dirs
+            } else {
+                cfg.entries.unwrap_or_default()
+            },
+            recursive: !cli.no_recursive && cfg.recursive.unwrap_or(true),
+            rewatch: cli.rewatch || cfg.rewatch.unwrap_or(false),
+            canonicalize: if cli.canonicalize || cli.no_canonicalize {
+                cli.canonicalize
+            } else {
+                cfg.canonicalize.unwrap_or(true)
+            },
+            content_only: cli.content_only || cfg.content_only.unwrap_or(false),
+            git_tracked_only: cli.git_tracked_only || cfg.git_tracked_only.unwrap_or(false),
+            watch_extensions_only: cli.watch_extensions_only
+                || cfg.watch_extensions_only.unwrap_or(false),
             debounce: Duration::from_millis(debounce_ms),
-            initial: cli.initial || cfg.initial.unwrap_or(false),
+            no_debounce_pattern: if !cli.no_debounce_pattern.is_empty() {
+                cli.no_debounce_pattern
+            } else {
+                cfg.no_debounce_pattern.unwrap_or_default()
+            },
+            throttle: cli.throttle.or(cfg.throttle).map(Duration::from_millis),
+            await_stable: cli
+                .await_stable
+                .or(cfg.await_stable)
+                .map(Duration::from_millis),
+            output: cli.output.map(under_root).or(cfg.output),
+            initial: cli.initial || cfg.initial.unwrap_or(false) || require_initial,
+            require_initial,
+            changed_since: cli
+                .changed_since
+                .or(cfg.changed_since)
+                .map(Duration::from_secs),
+            max_runs: cli.max_runs.or(cfg.max_runs),
+            count_initial: cli.count_initial || cfg.count_initial.unwrap_or(false),
+            fail_fast: cli.fail_fast || cfg.fail_fast.unwrap_or(false),
+            idle_timeout: cli
+                .idle_timeout
+                .or(cfg.idle_timeout)
+                .map(Duration::from_secs),
+            notify: cli.notify || cfg.notify.unwrap_or(false),
+            path_display: cli.path_display.or(cfg.path_display).unwrap_or_default(),
+            label: cli.label.or(cfg.label),
+            timestamps: cli.timestamps || cfg.timestamps.unwrap_or(false),
+            log_file: cli.log_file.or(cfg.log_file),
+            verbose: cli.verbose || cfg.verbose.unwrap_or(false),
             clear: cli.clear || cfg.clear.unwrap_or(false),
+            clear_mode: cli.clear_mode.or(cfg.clear_mode),
             restart: cli.restart || cfg.restart.unwrap_or(false),
+            restart_signal: cli.restart_signal.or(cfg.restart_signal),
+            restart_grace: Duration::from_millis(
+                cli.restart_grace
+                    .or(cfg.restart_grace)
+                    .unwrap_or(DEFAULT_RESTART_GRACE_MS),
+            ),
+            process_group: cli.process_group || cfg.process_group.unwrap_or(false),
+            parallel: cli.parallel.or(cfg.parallel).map(|n| n.max(1)),
+            capture: cli.capture || cfg.capture.unwrap_or(false),
+            raw_command: cli.raw_command || cfg.raw_command.unwrap_or(false),
+            literal_command,
+            command_from_matched_file: cli
+                .command_from_matched_file
+                .or(cfg.command_from_matched_file),
+            before: cli.before.or(cfg.before),
+            after: cli.after.or(cfg.after),
+            max_dirs: cli.max_dirs.or(cfg.max_dirs),
+            max_memory: cli.max_memory.or(cfg.max_memory),
+            buffer_size: cli
+                .buffer_size
+                .or(cfg.buffer_size)
+                .unwrap_or(DEFAULT_BUFFER_SIZE)
+                .max(1),
+            watch_retries: cli
+                .watch_retries
+                .or(cfg.watch_retries)
+                .unwrap_or(DEFAULT_WATCH_RETRIES)
+                .min(MAX_WATCH_RETRIES),
             stats: cli.stats || cfg.stats.unwrap_or(false),
-            stats_interval: Duration::from_secs(stats_interval_secs.max(1)),
+            stats_interval: Duration::from_secs(stats_interval_secs),
+            summary_interval: cli
+                .summary_interval
+                .or(cfg.summary_interval)
+                .map(Duration::from_secs),
+            print_events_summary_on_exit: cli.print_events_summary_on_exit
+                || cfg.print_events_summary_on_exit.unwrap_or(false),
             fast: cli.fast,
+            count_only: cli.count_only,
         }
     }
 }
 
+/// Split a comma-separated extension list, dropping blanks.
+pub(crate) fn parse_extensions(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parse a duration into milliseconds: a bare integer is milliseconds, as it
+/// always was; otherwise a number, possibly fractional, followed by `ms`, `s`,
+/// or `m`.
+pub(crate) fn parse_millis(raw: &str) -> Result<u64, String> {
+    let raw = raw.trim();
+    if let Ok(ms) = raw.parse::<u64>() {
+        return Ok(ms);
+    }
+    let invalid =
+        || format!("invalid duration '{raw}': use milliseconds or a unit, e.g. 250ms, 1.5s, 2m");
+    let (number, unit_ms) = if let Some(n) = raw.strip_suffix("ms") {
+        (n, 1.0)
+    } else if let Some(n) = raw.strip_suffix('s') {
+        (n, 1_000.0)
+    } else if let Some(n) = raw.strip_suffix('m') {
+        (n, 60_000.0)
+    } else {
+        return Err(invalid());
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok((value * unit_ms).round() as u64),
+        _ => Err(invalid()),
+    }
+}
+
+/// Deserialize a millisecond field given either as a number or as a string
+/// [`parse_millis`] understands.
+fn millis<'de, D: Deserializer<'de>>(de: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Millis(u64),
+        Text(String),
+    }
+    match Option::<Raw>::deserialize(de)? {
+        None => Ok(None),
+        Some(Raw::Millis(ms)) => Ok(Some(ms)),
+        Some(Raw::Text(text)) => parse_millis(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+/// Schema of a [`millis`] field: the same number-or-string it accepts.
+fn millis_schema(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+    schemars::json_schema!({
+        "description": "Milliseconds, or a duration with a unit such as \"250ms\", \"1.5s\" or \"2m\".",
+        "type": ["integer", "string", "null"],
+        "minimum": 0
+    })
+}
+
+/// Replace a leading `~` in a path or glob with the home directory, and
+/// `~user` with that user's (Unix). A `~` anywhere else, or one naming no
+/// known user, is left as written.
+fn expand_home(spec: &str) -> String {
+    let Some(rest) = spec.strip_prefix('~') else {
+        return spec.to_string();
+    };
+    let end = rest.find(std::path::is_separator).unwrap_or(rest.len());
+    let (user, tail) = rest.split_at(end);
+    let home = if user.is_empty() {
+        home::home_dir()
+    } else if user.contains(['*', '?', '[', '{']) {
+        None
+    } else {
+        user_home(user)
+    };
+    match home {
+        Some(home) => format!("{}{tail}", home.display()),
+        None => spec.to_string(),
+    }
+}
+
+fn expand_homes(specs: Vec<String>) -> Vec<String> {
+    specs.iter().map(|spec| expand_home(spec)).collect()
+}
+
+/// The home directory of the user called `name`, from the password database.
+#[cfg(unix)]
+fn user_home(name: &str) -> Option<PathBuf> {
+    use std::ffi::{CStr, CString, OsStr};
+    use std::os::unix::ffi::OsStrExt;
+
+    let name = CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 16 * 1024];
+    let mut entry = std::mem::MaybeUninit::<libc::passwd>::uninit();
+    let mut found = std::ptr::null_mut();
+    // SAFETY: every pointer is valid for the call, and `buf.len()` is the
+    // size of `buf`, where the strings `entry` points to are written.
+    let status = unsafe {
+        libc::getpwnam_r(
+            name.as_ptr(),
+            entry.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut found,
+        )
+    };
+    if status != 0 || found.is_null() {
+        return None;
+    }
+    // SAFETY: a non-null `found` means `entry` was filled in, with `pw_dir`
+    // a NUL-terminated string inside `buf`.
+    let dir = unsafe { CStr::from_ptr(entry.assume_init_ref().pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(not(unix))]
+fn user_home(_name: &str) -> Option<PathBuf> {
+    None
+}
+
+fn split_watch_entries(entries: Vec<WatchEntry>) -> (Vec<String>, Vec<ScopedWatch>) {
+    let mut watch = Vec::with_capacity(entries.len());
+    let mut scoped = Vec::new();
+    for entry in entries {
+        watch.push(entry.path().to_string());
+        if let WatchEntry::Scoped(s) = entry {
+            scoped.push(s);
+        }
+    }
+    (watch, scoped)
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
             command: Vec::new(),
+            matrix: BTreeMap::new(),
+            on_create: None,
+            on_modify: None,
+            on_remove: None,
+            projects: Vec::new(),
+            root: None,
             watch: vec![".".to_string()],
+            scoped: Vec::new(),
             extensions: Vec::new(),
             include: Vec::new(),
             ignore: Vec::new(),
+            ignore_file: None,
+            pattern_file: None,
+            exclude_output: Vec::new(),
+            ignore_editor_temp: false,
+            include_hidden: true,
+            ignore_case_paths: false,
+            entries: EntryKind::All,
+            recursive: true,
+            rewatch: false,
+            canonicalize: true,
+            content_only: false,
+            git_tracked_only: false,
+            watch_extensions_only: false,
             debounce: Duration::from_millis(DEFAULT_DEBOUNCE_MS),
+            no_debounce_pattern: Vec::new(),
+            throttle: None,
+            await_stable: None,
+            output: None,
             initial: false,
+            require_initial: false,
+            changed_since: None,
+            max_runs: None,
+            count_initial: false,
+            fail_fast: false,
+            idle_timeout: None,
+            notify: false,
+            path_display: PathDisplay::default(),
+            label: None,
+            timestamps: false,
+            log_file: None,
+            verbose: false,
             clear: false,
+            clear_mode: None,
             restart: false,
+            restart_signal: None,
+            restart_grace: Duration::from_millis(DEFAULT_RESTART_GRACE_MS),
+            process_group: false,
+            parallel: None,
+            capture: false,
+            raw_command: false,
+            literal_command: false,
+            command_from_matched_file: None,
+            before: None,
+            after: None,
+            max_dirs: None,
+            max_memory: None,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            watch_retries: DEFAULT_WATCH_RETRIES,
             stats: false,
             stats_interval: Duration::from_secs(DEFAULT_STATS_INTERVAL_SECS),
+            summary_interval: None,
+            print_events_summary_on_exit: false,
             fast: false,
+            count_only: false,
         }
     }
 }
@@ -151,25 +1354,87 @@ impl Default for Settings {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+    use tempfile::{NamedTempFile, TempDir};
 
     fn cli() -> Cli {
         Cli {
             command: Vec::new(),
+            root: None,
             watch: Vec::new(),
+            watch_stdin: false,
             ext: None,
             pattern: Vec::new(),
             ignore: Vec::new(),
+            ignore_file: None,
+            pattern_file: None,
+            exclude_output: Vec::new(),
+            ignore_editor_temp: false,
+            include_hidden: false,
+            exclude_hidden: false,
+            ignore_case_paths: false,
+            files_only: false,
+            dirs_only: false,
+            no_recursive: false,
+            rewatch: false,
+            canonicalize: false,
+            no_canonicalize: false,
+            content_only: false,
+            git_tracked_only: false,
+            watch_extensions_only: false,
             debounce: None,
+            no_debounce_pattern: Vec::new(),
+            throttle: None,
+            await_stable: None,
+            output: None,
             initial: false,
+            require_initial: false,
+            changed_since: None,
+            max_runs: None,
+            count_initial: false,
+            fail_fast: false,
+            idle_timeout: None,
+            notify: false,
+            path_display: None,
+            label: None,
+            timestamps: false,
+            log_file: None,
+            verbose: false,
             clear: false,
+            clear_mode: None,
             restart: false,
-            config: None,
+            restart_signal: None,
+            restart_grace: None,
+            process_group: false,
+            parallel: None,
+            capture: false,
+            raw_command: false,
+            literal_command: false,
+            command_from_matched_file: None,
+            before: None,
+            after: None,
+            max_dirs: None,
+            max_memory: None,
+            buffer_size: None,
+            watch_retries: None,
+            config: Vec::new(),
+            reload_config: false,
+            print_config: false,
+            check_config: false,
+            config_schema: false,
+            explain: None,
+            init: false,
+            force: false,
             fast: false,
+            count_only: false,
             stats: false,
             stats_interval: None,
+            summary_interval: None,
+            print_events_summary_on_exit: false,
+            ready_fd: None,
             bench: false,
+            list_watchers: false,
+            version: false,
+            format: None,
         }
     }
 
@@ -233,6 +1498,44 @@ mod tests {
         assert_eq!(s.debounce, Duration::from_millis(50));
     }
 
+    #[test]
+    fn hidden_flags_override_the_config_either_way() {
+        let excluding = Config {
+            include_hidden: Some(false),
+            ..Config::default()
+        };
+        assert!(
+            Settings::merge(cli(), None).include_hidden,
+            "default keeps them"
+        );
+        assert!(!Settings::merge(cli(), Some(excluding.clone())).include_hidden);
+
+        let mut c = cli();
+        c.include_hidden = true;
+        assert!(Settings::merge(c, Some(excluding)).include_hidden);
+
+        let mut c = cli();
+        c.exclude_hidden = true;
+        assert!(!Settings::merge(c, None).include_hidden);
+    }
+
+    #[test]
+    fn entry_flags_override_the_config() {
+        let dirs = Config {
+            entries: Some(EntryKind::Dirs),
+            ..Config::default()
+        };
+        assert_eq!(Settings::merge(cli(), None).entries, EntryKind::All);
+        assert_eq!(
+            Settings::merge(cli(), Some(dirs.clone())).entries,
+            EntryKind::Dirs
+        );
+
+        let mut c = cli();
+        c.files_only = true;
+        assert_eq!(Settings::merge(c, Some(dirs)).entries, EntryKind::Files);
+    }
+
     #[test]
     fn cli_boolean_true_sticks_even_when_config_says_false() {
         let mut c = cli();
@@ -260,17 +1563,263 @@ restart: true
         let file = write_config(yaml);
         let config = Config::load(file.path()).unwrap();
         assert_eq!(config.command, vec!["cargo", "test"]);
-        assert_eq!(config.watch, Some(vec!["src".to_string()]));
+        assert_eq!(config.watch, Some(vec![WatchEntry::from("src")]));
         assert_eq!(config.ext, Some("rs".to_string()));
         assert_eq!(config.debounce, Some(200));
         assert_eq!(config.restart, Some(true));
     }
 
+    #[test]
+    fn parse_millis_accepts_units_and_bare_milliseconds() {
+        assert_eq!(parse_millis("250"), Ok(250));
+        assert_eq!(parse_millis("0"), Ok(0));
+        assert_eq!(parse_millis("250ms"), Ok(250));
+        assert_eq!(parse_millis("1s"), Ok(1_000));
+        assert_eq!(parse_millis("1.5s"), Ok(1_500));
+        assert_eq!(parse_millis("0.25 s"), Ok(250));
+        assert_eq!(parse_millis("2m"), Ok(120_000));
+    }
+
+    #[test]
+    fn parse_millis_rejects_unitless_fractions_and_junk() {
+        for bad in ["1.5", "", "s", "fast", "-1s", "10h", "NaNs"] {
+            let err = parse_millis(bad).unwrap_err();
+            assert!(err.contains("invalid duration"), "{bad:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn config_durations_may_carry_a_unit() {
+        let file = write_config("command: [make]\ndebounce: 1.5s\nthrottle: \"250ms\"\n");
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.debounce, Some(1_500));
+        assert_eq!(config.throttle, Some(250));
+
+        let file = write_config("command: [make]\ndebounce: soon\n");
+        let Err(FlashError::ConfigParse { source, .. }) = Config::load(file.path()) else {
+            panic!("a bad duration should fail to parse");
+        };
+        assert!(
+            source.to_string().contains("invalid duration 'soon'"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn restart_signal_takes_its_name_with_or_without_the_sig_prefix() {
+        let file = write_config("command: [make]\nrestart_signal: INT\nrestart_grace: 1.5s\n");
+        let config = Config::load(file.path()).unwrap();
+        assert_eq!(config.restart_signal, Some(RestartSignal::Int));
+        assert_eq!(config.restart_grace, Some(1_500));
+
+        let mut c = cli();
+        c.restart_signal = Some(RestartSignal::Term);
+        let s = Settings::merge(c, Some(config));
+        assert_eq!(s.restart_signal, Some(RestartSignal::Term), "the CLI wins");
+        assert_eq!(s.restart_grace, Duration::from_millis(1_500));
+    }
+
+    #[test]
+    fn later_config_files_override_only_the_keys_they_set() {
+        let team = write_config(
+            "command: [cargo, check]\next: rs\ndebounce: 500\nrestart: true\nignore: [target/**]\n",
+        );
+        let personal = write_config("debounce: 100\n");
+        let mut c = cli();
+        c.config = vec![team.path().to_path_buf(), personal.path().to_path_buf()];
+
+        let s = Settings::build(c).unwrap();
+        assert_eq!(
+            s.debounce,
+            Duration::from_millis(100),
+            "the second file wins"
+        );
+        assert_eq!(s.command, vec!["cargo", "check"]);
+        assert_eq!(s.extensions, vec!["rs"]);
+        assert!(s.restart);
+        assert_eq!(s.ignore, vec!["target/**"]);
+    }
+
+    #[test]
+    fn merge_all_keeps_earlier_values_where_later_configs_are_silent() {
+        let merged = Config::merge_all(vec![
+            Config {
+                debounce: Some(500),
+                clear: Some(true),
+                ..Config::default()
+            },
+            Config {
+                debounce: Some(100),
+                ..Config::default()
+            },
+            Config::default(),
+        ]);
+        assert_eq!(merged.debounce, Some(100));
+        assert_eq!(merged.clear, Some(true));
+        assert_eq!(Config::merge_all(Vec::new()), Config::default());
+    }
+
+    #[test]
+    fn extends_chain_fills_gaps_with_the_child_winning() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("shared")).unwrap();
+        std::fs::write(
+            dir.path().join("shared").join("base.yaml"),
+            "command: [make]\next: \"c,h\"\ndebounce: 500\nclear: true\n\
+             ignore_file: .flashignore\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("team.yaml"),
+            "extends: shared/base.yaml\ndebounce: 200\nrestart: true\n",
+        )
+        .unwrap();
+        let project = dir.path().join("flash.yaml");
+        std::fs::write(&project, "extends: team.yaml\ncommand: [make, test]\n").unwrap();
+
+        let config = Config::load(&project).unwrap();
+        assert_eq!(config.extends, None, "the chain is resolved away");
+        assert_eq!(config.command, vec!["make", "test"]);
+        assert_eq!(config.debounce, Some(200), "middle file beats the base");
+        assert_eq!(config.restart, Some(true));
+        assert_eq!(config.ext, Some("c,h".to_string()), "base fills the rest");
+        assert_eq!(config.clear, Some(true));
+        assert_eq!(
+            config.ignore_file,
+            Some(dir.path().join("shared").join(".flashignore")),
+            "a base's paths stay relative to the base"
+        );
+        assert_eq!(
+            Config::sources(&project),
+            ["flash.yaml", "team.yaml", "shared/base.yaml"].map(|file| dir
+                .path()
+                .join(file)
+                .canonicalize()
+                .unwrap())
+        );
+    }
+
+    #[test]
+    fn extends_cycle_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let a = dir.path().join("a.yaml");
+        std::fs::write(&a, "extends: b.yaml\ncommand: [make]\n").unwrap();
+        std::fs::write(dir.path().join("b.yaml"), "extends: ./a.yaml\n").unwrap();
+
+        let err = Config::load(&a).unwrap_err();
+        assert!(matches!(err, FlashError::ConfigCycle { .. }), "{err:?}");
+        assert!(err.to_string().contains("`extends` cycle"), "{err}");
+
+        let selfish = dir.path().join("self.yaml");
+        std::fs::write(&selfish, "extends: self.yaml\n").unwrap();
+        assert!(matches!(
+            Config::load(&selfish),
+            Err(FlashError::ConfigCycle { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_extends_target_is_a_read_error() {
+        let file = write_config("extends: nowhere.yaml\ncommand: [make]\n");
+        assert!(matches!(
+            Config::load(file.path()),
+            Err(FlashError::ConfigRead { .. })
+        ));
+    }
+
+    #[test]
+    fn watch_entries_may_carry_their_own_filters() {
+        let file = write_config(
+            r#"
+command: [make]
+watch:
+  - docs
+  - path: src
+    ext: rs
+  - path: assets
+    pattern: ["**/*.png"]
+ext: md
+"#,
+        );
+        let s = Settings::merge(cli(), Some(Config::load(file.path()).unwrap()));
+        assert_eq!(s.watch, vec!["docs", "src", "assets"]);
+        assert_eq!(s.extensions, vec!["md"]);
+        assert_eq!(s.scoped.len(), 2);
+        assert_eq!(s.scoped[0].path, "src");
+        assert_eq!(s.scoped[0].ext.as_deref(), Some("rs"));
+        assert_eq!(s.scoped[1].pattern, Some(vec!["**/*.png".to_string()]));
+
+        let again = Settings::merge(cli(), Some(Config::from(&s)));
+        assert_eq!(again, s, "scoped entries survive --print-config");
+    }
+
+    #[test]
+    fn cli_watch_paths_drop_scoped_config_entries() {
+        let mut c = cli();
+        c.watch = vec!["lib".into()];
+        let cfg = Config {
+            watch: Some(vec![WatchEntry::Scoped(ScopedWatch {
+                path: "src".into(),
+                ext: Some("rs".into()),
+                pattern: None,
+                ignore: None,
+            })]),
+            ..Config::default()
+        };
+        let s = Settings::merge(c, Some(cfg));
+        assert_eq!(s.watch, vec!["lib"]);
+        assert!(s.scoped.is_empty());
+    }
+
     #[test]
     fn config_load_rejects_unknown_fields() {
         let file = write_config("command: [echo]\nmystery: 1\n");
         let err = Config::load(file.path()).expect_err("should fail");
         assert!(err.to_string().contains("parsing config file"));
+        assert!(matches!(err, FlashError::ConfigParse { .. }));
+    }
+
+    #[test]
+    fn config_load_reports_unreadable_files() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("nope.yaml");
+        match Config::load(&missing).expect_err("missing file") {
+            FlashError::ConfigRead { path, source } => {
+                assert_eq!(path, missing);
+                assert_eq!(source.kind(), io::ErrorKind::NotFound);
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn validate_rejects_an_empty_command() {
+        let err = Settings::default().validate().expect_err("no command");
+        assert!(matches!(err, FlashError::EmptyCommand));
+
+        let mut c = cli();
+        c.command = vec!["true".into()];
+        assert!(Settings::merge(c, None).validate().is_ok());
+
+        let mut c = cli();
+        c.count_only = true;
+        assert!(
+            Settings::merge(c, None).validate().is_ok(),
+            "--count-only needs no command"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_zero_max_runs() {
+        let cfg = Config {
+            command: vec!["true".into()],
+            max_runs: Some(0),
+            ..Config::default()
+        };
+        let err = Settings::merge(cli(), Some(cfg))
+            .validate()
+            .expect_err("zero runs");
+        assert!(matches!(err, FlashError::ZeroMaxRuns));
     }
 
     #[test]
@@ -280,10 +1829,300 @@ restart: true
     }
 
     #[test]
-    fn stats_interval_is_clamped_to_at_least_one_second() {
+    fn max_dirs_falls_back_to_config() {
+        let cfg = Config {
+            max_dirs: Some(500),
+            ..Config::default()
+        };
+        assert_eq!(
+            Settings::merge(cli(), Some(cfg.clone())).max_dirs,
+            Some(500)
+        );
+
+        let mut c = cli();
+        c.max_dirs = Some(10);
+        assert_eq!(Settings::merge(c, Some(cfg)).max_dirs, Some(10));
+    }
+
+    #[test]
+    fn effective_config_dump_reflects_cli_config_and_defaults() {
+        let mut c = cli();
+        c.debounce = Some(250);
+        let cfg = Config {
+            command: vec!["cargo".into(), "test".into()],
+            ext: Some("rs, toml".into()),
+            restart: Some(true),
+            ..Config::default()
+        };
+        let yaml = Config::from(&Settings::merge(c, Some(cfg)))
+            .to_yaml()
+            .unwrap();
+        assert_eq!(
+            yaml,
+            "\
+command:
+- cargo
+- test
+watch:
+- .
+ext: rs,toml
+pattern: []
+ignore: []
+exclude_output: []
+ignore_editor_temp: false
+include_hidden: true
+ignore_case_paths: false
+entries: all
+recursive: true
+rewatch: false
+canonicalize: true
+content_only: false
+git_tracked_only: false
+watch_extensions_only: false
+debounce: 250
+no_debounce_pattern: []
+initial: false
+require_initial: false
+count_initial: false
+fail_fast: false
+notify: false
+path_display: relative
+timestamps: false
+verbose: false
+clear: false
+restart: true
+restart_grace: 5000
+process_group: false
+capture: false
+raw_command: false
+buffer_size: 4096
+watch_retries: 3
+stats: false
+stats_interval: 10
+print_events_summary_on_exit: false
+"
+        );
+    }
+
+    #[test]
+    fn effective_config_round_trips_through_merge() {
+        let mut c = cli();
+        c.command = vec!["make".into()];
+        c.ext = Some("c,h".into());
+        c.max_dirs = Some(64);
+        c.clear = true;
+        c.summary_interval = Some(30);
+        let settings = Settings::merge(c, None);
+        let again = Settings::merge(cli(), Some(Config::from(&settings)));
+        assert_eq!(again, settings);
+    }
+
+    #[test]
+    fn starter_config_parses_back_to_the_defaults() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_FILE);
+        write_starter(&path, false).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.command, vec!["echo", "files changed"]);
+        let s = Settings::merge(cli(), Some(config));
+        let defaults = Settings::default();
+        assert_eq!(s.debounce, defaults.debounce);
+        assert_eq!(s.stats_interval, defaults.stats_interval);
+        assert_eq!(s.watch, defaults.watch);
+        assert!(s.extensions.is_empty());
+    }
+
+    #[test]
+    fn starter_config_does_not_clobber_without_force() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join(DEFAULT_CONFIG_FILE);
+        std::fs::write(&path, "command: [mine]\n").unwrap();
+
+        let err = write_starter(&path, false).expect_err("must not overwrite");
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "command: [mine]\n");
+
+        write_starter(&path, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), STARTER_CONFIG);
+    }
+
+    #[test]
+    fn validate_rejects_zero_intervals() {
+        for key in ["stats_interval", "summary_interval"] {
+            let file = write_config(&format!("command: [make]\nstats: true\n{key}: 0\n"));
+            let cfg = Config::load(file.path()).unwrap();
+            let err = Settings::merge(cli(), Some(cfg))
+                .validate()
+                .expect_err("zero interval");
+            assert!(
+                matches!(err, FlashError::ZeroInterval { key: k } if k == key),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn root_resolves_relative_paths_and_comes_relative_to_its_config() {
+        let file = write_config(
+            "command: [make]\nroot: ..\nwatch: [src, /abs]\nignore_file: .flashignore\n",
+        );
+        let dir = file.path().parent().unwrap();
+        let cfg = Config::load(file.path()).unwrap();
+        assert_eq!(cfg.root, Some(dir.join("..")));
+        let s = Settings::merge(cli(), Some(cfg));
+        let root = dir.join("..");
+        assert_eq!(
+            s.watch,
+            [root.join("src").display().to_string(), "/abs".to_string()]
+        );
+        assert_eq!(s.ignore_file, Some(root.join(".flashignore")));
+
+        let mut c = cli();
+        c.root = Some(PathBuf::from("/repo"));
+        c.exclude_output = vec![PathBuf::from("dist")];
+        let s = Settings::merge(c, None);
+        assert_eq!(s.watch, ["/repo/."]);
+        assert_eq!(s.exclude_output, [PathBuf::from("/repo/dist")]);
+    }
+
+    #[test]
+    fn filter_files_in_a_config_are_relative_to_it() {
+        let file = write_config(
+            "command: [make]\nignore_file: .flashignore\npattern_file: /etc/include\n\
+             output: target/app\n",
+        );
+        let dir = file.path().parent().unwrap();
+        let mut c = cli();
+        c.root = Some(PathBuf::from("/repo"));
+        let s = Settings::merge(c, Some(Config::load(file.path()).unwrap()));
+        assert_eq!(s.ignore_file, Some(dir.join(".flashignore")));
+        assert_eq!(s.pattern_file, Some(PathBuf::from("/etc/include")));
+        assert_eq!(s.output, Some(dir.join("target/app")));
+
         let mut c = cli();
-        c.stats_interval = Some(0);
+        c.root = Some(PathBuf::from("/repo"));
+        c.ignore_file = Some(PathBuf::from(".flashignore"));
+        c.output = Some(PathBuf::from("target/app"));
         let s = Settings::merge(c, None);
-        assert_eq!(s.stats_interval, Duration::from_secs(1));
+        assert_eq!(s.ignore_file, Some(PathBuf::from("/repo/.flashignore")));
+        assert_eq!(s.output, Some(PathBuf::from("/repo/target/app")));
+    }
+
+    #[test]
+    fn a_leading_tilde_expands_to_the_home_directory() {
+        let home = home::home_dir().unwrap().display().to_string();
+        assert_eq!(expand_home("~/x"), format!("{home}/x"));
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("src/~/x"), "src/~/x");
+        assert_eq!(expand_home("**/*~"), "**/*~");
+        assert_eq!(expand_home("~*.tmp"), "~*.tmp");
+        assert_eq!(expand_home("~no-such-user-here/x"), "~no-such-user-here/x");
+
+        let mut c = cli();
+        c.watch = vec!["~/projects/app/src".into()];
+        c.pattern = vec!["~/projects/**/*.rs".into()];
+        c.ignore = vec!["~/projects/app/target/**".into(), "a~b".into()];
+        let settings = Settings::merge(c, None);
+        assert_eq!(settings.watch, vec![format!("{home}/projects/app/src")]);
+        assert_eq!(settings.include, vec![format!("{home}/projects/**/*.rs")]);
+        assert_eq!(
+            settings.ignore,
+            vec![format!("{home}/projects/app/target/**"), "a~b".into()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tilde_user_expands_to_that_users_home() {
+        let expanded = expand_home("~root/x");
+        assert!(
+            expanded.starts_with('/') && expanded.ends_with("/x"),
+            "{expanded}"
+        );
+    }
+
+    #[test]
+    fn projects_take_unset_keys_from_the_top_level() {
+        let file = write_config(
+            "debounce: 40\nroot: svc\nprojects:\n  \
+             - {watch: [api], command: [cargo, test]}\n  \
+             - {watch: [web], command: [npm, test], debounce: 5, label: frontend}\n  \
+             - {command: [make]}\n",
+        );
+        let dir = file.path().parent().unwrap();
+        let s = Settings::merge(cli(), Some(Config::load(file.path()).unwrap()));
+        let projects: Vec<Settings> = s
+            .projects
+            .into_iter()
+            .map(|p| Settings::merge(cli(), Some(p)))
+            .collect();
+        let api = &projects[0];
+        assert_eq!(api.command, ["cargo", "test"]);
+        assert_eq!(api.debounce, Duration::from_millis(40));
+        assert_eq!(api.watch, [dir.join("svc/api").display().to_string()]);
+        assert_eq!(api.label.as_deref(), Some("api"));
+        assert_eq!(projects[1].debounce, Duration::from_millis(5));
+        assert_eq!(projects[1].label.as_deref(), Some("frontend"));
+        assert_eq!(projects[2].label.as_deref(), Some("project 3"));
+    }
+
+    #[test]
+    fn clear_mode_overrides_clear() {
+        let file = write_config("command: [make]\nclear: true\nclear_mode: on-success\n");
+        let cfg = Config::load(file.path()).unwrap();
+        let s = Settings::merge(cli(), Some(cfg));
+        assert_eq!(s.clear_mode, Some(ClearMode::OnSuccess));
+
+        let mut c = cli();
+        c.clear_mode = Some(ClearMode::Never);
+        let cfg = Config::load(file.path()).unwrap();
+        assert_eq!(
+            Settings::merge(c, Some(cfg)).clear_mode,
+            Some(ClearMode::Never)
+        );
+    }
+
+    #[test]
+    fn kind_commands_come_from_the_config_file() {
+        let file = write_config("command: [make]\non_remove: [./delete-remote.sh]\n");
+        let settings = Settings::merge(cli(), Some(Config::load(file.path()).unwrap()));
+        assert_eq!(settings.on_remove, Some(vec!["./delete-remote.sh".into()]));
+        assert_eq!(settings.on_create, None);
+        assert!(Config::from(&settings)
+            .to_yaml()
+            .unwrap()
+            .contains("on_remove:\n- ./delete-remote.sh\n"));
+    }
+
+    #[test]
+    fn validate_bounds_the_matrix() {
+        let file = write_config("command: [make]\nmatrix:\n  target: []\n");
+        let cfg = Config::load(file.path()).unwrap();
+        let err = Settings::merge(cli(), Some(cfg)).validate().unwrap_err();
+        assert!(
+            matches!(&err, FlashError::EmptyMatrixKey { key } if key == "target"),
+            "{err:?}"
+        );
+
+        let values = format!(
+            "[{}]",
+            (0..9).map(|i| i.to_string()).collect::<Vec<_>>().join(", ")
+        );
+        let file = write_config(&format!(
+            "command: [make]\nmatrix:\n  a: {values}\n  b: {values}\n"
+        ));
+        let cfg = Config::load(file.path()).unwrap();
+        let err = Settings::merge(cli(), Some(cfg)).validate().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                FlashError::MatrixTooLarge {
+                    runs: 81,
+                    limit: 64
+                }
+            ),
+            "{err:?}"
+        );
     }
 }
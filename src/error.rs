@@ -0,0 +1,95 @@
+use std::io;
+use std::path::PathBuf;
+
+/// Errors from the library's configuration and validation steps, for callers
+/// that embed Flash and want to react to a specific failure. The binary just
+/// prints them through `anyhow`.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum FlashError {
+    #[error("reading config file '{}'", path.display())]
+    ConfigRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("parsing config file '{}'", path.display())]
+    ConfigParse {
+        path: PathBuf,
+        #[source]
+        source: serde_yaml::Error,
+    },
+
+    /// A config file's `extends` chain leads back to `path`.
+    #[error("config file '{}' is part of an `extends` cycle", path.display())]
+    ConfigCycle { path: PathBuf },
+
+    #[error("reading ignore file '{}'", path.display())]
+    IgnoreFileRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("reading pattern file '{}'", path.display())]
+    PatternFileRead {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// A `--pattern-file` line that isn't a valid glob. `line` is 1-based,
+    /// and `source` is the [`FlashError::InvalidPattern`] it caused.
+    #[error("pattern file '{}', line {line}", path.display())]
+    PatternFileLine {
+        path: PathBuf,
+        line: usize,
+        #[source]
+        source: Box<FlashError>,
+    },
+
+    /// `set` names the pattern list the glob came from: `include` or `ignore`.
+    /// `column` is the 1-based character the problem is at, where it can be
+    /// pinned down: an unclosed `[` or `{`, a stray `}`, or a trailing `\`.
+    #[error(
+        "compiling {set} patterns: invalid glob '{pattern}'{}: {kind}",
+        .column.map(|c| format!(" at column {c}")).unwrap_or_default()
+    )]
+    InvalidPattern {
+        set: &'static str,
+        pattern: String,
+        kind: globset::ErrorKind,
+        column: Option<usize>,
+    },
+
+    #[error("compiling {set} patterns: building glob set")]
+    GlobSet {
+        set: &'static str,
+        #[source]
+        source: globset::Error,
+    },
+
+    #[error(
+        "no command specified {} pass one after the flags, or set `command:` in your config",
+        crate::style::DASH
+    )]
+    EmptyCommand,
+
+    #[error("max_runs must be at least 1")]
+    ZeroMaxRuns,
+
+    /// `key` names the interval: `stats_interval` or `summary_interval`.
+    #[error("{key} must be at least 1 second")]
+    ZeroInterval { key: &'static str },
+
+    /// `message` is git's own complaint, or why it couldn't be run.
+    #[error("listing git-tracked files under '{}': {message}", root.display())]
+    GitLsFiles { root: PathBuf, message: String },
+
+    #[error("matrix key '{key}' has no values")]
+    EmptyMatrixKey { key: String },
+
+    #[error("matrix expands to {runs} runs per change; at most {limit} are allowed")]
+    MatrixTooLarge { runs: usize, limit: usize },
+}
@@ -1,7 +1,31 @@
-use std::path::Path;
+use std::ffi::OsStr;
+use std::fmt;
+use std::path::{Component, Path};
 
-use anyhow::{Context, Result};
-use globset::{Glob, GlobSet, GlobSetBuilder};
+use clap::ValueEnum;
+use globset::{ErrorKind, Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::error::FlashError;
+
+/// Scratch files editors create and delete around a save, ignored when
+/// `--ignore-editor-temp` is set:
+///
+/// - Vim: `.file.swp`/`.swo`/`.swx` swap files, `file~` backups, and the
+///   `4913` probe file it writes to test directory permissions.
+/// - Emacs: `#file#` auto-saves and `.#file` lock links.
+/// - JetBrains IDEs: `file___jb_tmp___` and `file___jb_old___` safe-write
+///   temporaries.
+pub const EDITOR_TEMP_PATTERNS: &[&str] = &[
+    "**/.*.sw[pox]",
+    "**/*~",
+    "**/4913",
+    "**/#*#",
+    "**/.#*",
+    "**/*___jb_tmp___",
+    "**/*___jb_old___",
+];
 
 /// Decides whether a path should trigger a command run.
 ///
@@ -9,52 +33,473 @@ use globset::{Glob, GlobSet, GlobSetBuilder};
 ///   1. Ignore patterns: anything matching is dropped immediately.
 ///   2. Extension filter: when set, the path's extension must match.
 ///   3. Include patterns: when set, at least one must match.
+///
+/// Globs are tried against the path relative to its watch root as well as the
+/// full path, so `src/**/*.rs` means the same thing whether Flash was pointed
+/// at `.` or at `/home/me/project`.
+#[derive(Debug, Clone)]
 pub struct Filter {
     extensions: Vec<String>,
+    excluded_extensions: Vec<String>,
     include: GlobSet,
     ignore: GlobSet,
-    include_set: bool,
+    /// The part before `/**` of each ignore pattern that ends in it: the
+    /// directories everything under which is ignored.
+    ignored_subtrees: GlobSet,
+    include_patterns: Vec<String>,
+    ignore_patterns: Vec<String>,
+    /// The `--watch` glob a path must also match, for the roots it expanded
+    /// to.
+    watch_glob: Option<(GlobSet, String)>,
+    include_hidden: bool,
+    ignore_case: bool,
+}
+
+/// Why a [`Filter`] accepted or rejected a path. Its `Display` is the
+/// one-line explanation `--explain` prints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathDecision {
+    /// `matched` is the first include pattern that matched, if any were set.
+    Accepted {
+        matched: Option<String>,
+    },
+    Rejected {
+        reason: RejectReason,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The path matched this ignore pattern.
+    IgnorePattern(String),
+    /// An extension filter is set and the path's extension is not in it.
+    Extension,
+    /// The path has this extension, excluded with `!` in the extension list.
+    ExcludedExtension(String),
+    /// Include patterns are set and none of them matched.
+    NoIncludeMatch,
+    /// The path is under a root a `--watch` glob expanded to, but doesn't
+    /// match the glob.
+    WatchGlob(String),
+    /// Hidden paths are excluded and this one has a `.name` component.
+    Hidden,
+    /// The path is not directly in its watch root, which is watched
+    /// shallowly: under `--no-recursive`, or because it came from this
+    /// `--watch` glob without `**`.
+    NotDirectChild(Option<String>),
+    /// `--files-only` is set and the path is a directory.
+    Directory,
+    /// `--dirs-only` is set and the path is not a directory.
+    NotDirectory,
+    /// `--git-tracked-only` is set and git doesn't track the path.
+    Untracked,
+}
+
+/// Which kinds of entries may trigger a run (`--files-only`, `--dirs-only`).
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    /// Files and directories alike.
+    #[default]
+    All,
+    Files,
+    Dirs,
+}
+
+impl EntryKind {
+    /// Why `path` is the wrong kind of entry, if it is. This stats the path,
+    /// so it is only asked once the globs have accepted it. A path that no
+    /// longer exists cannot be told apart and counts as a file.
+    pub fn check(self, path: &Path) -> Option<RejectReason> {
+        match self {
+            Self::All => None,
+            Self::Files => path.is_dir().then_some(RejectReason::Directory),
+            Self::Dirs => (!path.is_dir()).then_some(RejectReason::NotDirectory),
+        }
+    }
+}
+
+impl PathDecision {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Self::Accepted { .. })
+    }
+}
+
+impl RejectReason {
+    /// A short name for the kind of rule, without the pattern or extension
+    /// that matched, as `--stats` groups rejections.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::IgnorePattern(_) => "ignore-pattern",
+            Self::Extension | Self::ExcludedExtension(_) => "extension",
+            Self::NoIncludeMatch | Self::WatchGlob(_) => "include-pattern",
+            Self::Hidden => "hidden",
+            Self::NotDirectChild(_) => "not-direct-child",
+            Self::Directory | Self::NotDirectory => "entry-kind",
+            Self::Untracked => "untracked",
+        }
+    }
+}
+
+impl fmt::Display for PathDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Accepted { matched: None } => f.write_str("accepted"),
+            Self::Accepted {
+                matched: Some(pattern),
+            } => write!(f, "accepted: matches include pattern '{pattern}'"),
+            Self::Rejected { reason } => write!(f, "rejected: {reason}"),
+        }
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IgnorePattern(pattern) => write!(f, "matches ignore pattern '{pattern}'"),
+            Self::Extension => f.write_str("extension is not in the --ext list"),
+            Self::ExcludedExtension(ext) => write!(f, "extension is excluded by '!{ext}' in --ext"),
+            Self::NoIncludeMatch => f.write_str("matches no include pattern"),
+            Self::WatchGlob(glob) => write!(f, "doesn't match the --watch glob '{glob}'"),
+            Self::Hidden => f.write_str("hidden, and hidden paths are excluded (--exclude-hidden)"),
+            Self::NotDirectChild(None) => {
+                f.write_str("not directly inside a watch root (--no-recursive)")
+            }
+            Self::NotDirectChild(Some(glob)) => write!(
+                f,
+                "deeper than the --watch glob '{glob}' reaches (add '**' to go further)"
+            ),
+            Self::Directory => f.write_str("a directory, and only files count (--files-only)"),
+            Self::NotDirectory => {
+                f.write_str("not a directory, and only directories count (--dirs-only)")
+            }
+            Self::Untracked => f.write_str("not tracked by git (--git-tracked-only)"),
+        }
+    }
 }
 
 impl Filter {
-    pub fn new(extensions: &[String], include: &[String], ignore: &[String]) -> Result<Self> {
+    pub fn new(
+        extensions: &[String],
+        include: &[String],
+        ignore: &[String],
+    ) -> Result<Self, FlashError> {
+        // A leading `!` turns an `--ext` entry into an exclusion.
+        let (excluded, listed): (Vec<&String>, Vec<&String>) =
+            extensions.iter().partition(|e| e.starts_with('!'));
         Ok(Self {
-            extensions: extensions.to_vec(),
-            include: build_set(include).context("compiling include patterns")?,
-            ignore: build_set(ignore).context("compiling ignore patterns")?,
-            include_set: !include.is_empty(),
+            extensions: listed.into_iter().cloned().collect(),
+            excluded_extensions: excluded
+                .into_iter()
+                .map(|e| e.trim_start_matches('!').to_string())
+                .filter(|e| !e.is_empty())
+                .collect(),
+            include: build_set("include", include, false)?,
+            ignore: build_set("ignore", ignore, false)?,
+            ignored_subtrees: build_set("ignore", &subtree_patterns(ignore), false)?,
+            include_patterns: include.to_vec(),
+            ignore_patterns: ignore.to_vec(),
+            watch_glob: None,
+            include_hidden: true,
+            ignore_case: false,
         })
     }
 
+    /// Also require paths to match `glob`, the `--watch` glob whose roots
+    /// this filter is for. With no include patterns, it is the include
+    /// pattern `--explain` names.
+    pub fn watch_glob(mut self, glob: &str) -> Result<Self, FlashError> {
+        let set = build_set(
+            "include",
+            std::slice::from_ref(&glob.to_string()),
+            self.ignore_case,
+        )?;
+        self.watch_glob = Some((set, glob.to_string()));
+        Ok(self)
+    }
+
+    /// Whether paths with a component starting with `.` are considered
+    /// (`--exclude-hidden` turns this off). With a watch root, only the
+    /// components below it count, so watching `~/.config/app` still works;
+    /// without one, only the file name does.
+    pub fn include_hidden(mut self, include: bool) -> Self {
+        self.include_hidden = include;
+        self
+    }
+
+    /// Whether globs and extensions match regardless of case
+    /// (`--ignore-case-paths`), so `**/*.JPG` also takes `photo.jpg`.
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        if ignore_case == self.ignore_case {
+            return self;
+        }
+        // Case doesn't change whether a glob parses, and these all did in
+        // `Filter::new`.
+        let compile = |set, patterns: &[String]| {
+            build_set(set, patterns, ignore_case).expect("patterns compiled once already")
+        };
+        self.include = compile("include", &self.include_patterns);
+        self.ignore = compile("ignore", &self.ignore_patterns);
+        self.ignored_subtrees = compile("ignore", &subtree_patterns(&self.ignore_patterns));
+        if let Some((set, glob)) = &mut self.watch_glob {
+            *set = compile("include", std::slice::from_ref(glob));
+        }
+        self.ignore_case = ignore_case;
+        self
+    }
+
     pub fn accepts(&self, path: &Path) -> bool {
-        if self.ignore.is_match(path) {
-            return false;
+        self.explain(path, None).is_accepted()
+    }
+
+    /// Like [`Filter::accepts`], with globs also matched against `path`
+    /// relative to the watch `root` that produced it.
+    pub fn accepts_in(&self, root: &Path, path: &Path) -> bool {
+        self.explain(path, Some(root)).is_accepted()
+    }
+
+    /// Whether the ignore patterns reject everything under the directory
+    /// `dir`, because one of them is `<a glob matching dir>/**`. Such a
+    /// subtree doesn't need watching at all.
+    pub fn ignores_subtree(&self, dir: &Path, root: Option<&Path>) -> bool {
+        let relative = root.and_then(|r| dir.strip_prefix(r).ok());
+        self.ignored_subtrees.is_match(dir)
+            || relative.is_some_and(|r| self.ignored_subtrees.is_match(r))
+    }
+
+    pub(crate) fn ignores_any_subtree(&self) -> bool {
+        !self.ignored_subtrees.is_empty()
+    }
+
+    /// The decision behind [`Filter::accepts`] and [`Filter::accepts_in`],
+    /// naming the pattern or rule that settled it.
+    pub fn explain(&self, path: &Path, root: Option<&Path>) -> PathDecision {
+        let relative = root.and_then(|r| path.strip_prefix(r).ok());
+        // The first pattern in `patterns` that matches either form of the path.
+        let first_match = |set: &GlobSet, patterns: &[String]| {
+            let mut hits = set.matches(path);
+            if let Some(relative) = relative {
+                hits.extend(set.matches(relative));
+            }
+            hits.into_iter().min().map(|i| patterns[i].clone())
+        };
+
+        if let Some(pattern) = first_match(&self.ignore, &self.ignore_patterns) {
+            return PathDecision::Rejected {
+                reason: RejectReason::IgnorePattern(pattern),
+            };
         }
 
-        if !self.extensions.is_empty() {
-            let Some(ext) = path.extension().and_then(|e| e.to_str()) else {
-                return false;
+        if !self.include_hidden && is_hidden(relative.unwrap_or(path), relative.is_some()) {
+            return PathDecision::Rejected {
+                reason: RejectReason::Hidden,
+            };
+        }
+
+        // Exclusions win over listed extensions, so `rs,!generated.rs` keeps
+        // `lib.rs` but not `schema.generated.rs`.
+        if let Some(ext) = self
+            .excluded_extensions
+            .iter()
+            .find(|e| has_extension(path, e, self.ignore_case))
+        {
+            return PathDecision::Rejected {
+                reason: RejectReason::ExcludedExtension(ext.clone()),
             };
-            if !self.extensions.iter().any(|e| e == ext) {
-                return false;
+        }
+
+        if !self.extensions.is_empty() {
+            let listed = self
+                .extensions
+                .iter()
+                .any(|e| has_extension(path, e, self.ignore_case));
+            if !listed {
+                return PathDecision::Rejected {
+                    reason: RejectReason::Extension,
+                };
+            }
+        }
+
+        if let Some((set, glob)) = &self.watch_glob {
+            if first_match(set, std::slice::from_ref(glob)).is_none() {
+                return PathDecision::Rejected {
+                    reason: RejectReason::WatchGlob(glob.clone()),
+                };
             }
+            if self.include_patterns.is_empty() {
+                return PathDecision::Accepted {
+                    matched: Some(glob.clone()),
+                };
+            }
+        }
+        if self.include_patterns.is_empty() {
+            return PathDecision::Accepted { matched: None };
+        }
+        match first_match(&self.include, &self.include_patterns) {
+            Some(pattern) => PathDecision::Accepted {
+                matched: Some(pattern),
+            },
+            None => PathDecision::Rejected {
+                reason: RejectReason::NoIncludeMatch,
+            },
         }
+    }
+}
 
-        if self.include_set && !self.include.is_match(path) {
-            return false;
+/// Whether `path`'s file name ends in `.ext`. `ext` may span several dots,
+/// as in `generated.rs`. Compared as bytes so a file whose name is not valid
+/// UTF-8 still matches on its (ASCII) extension. With `ignore_case`, ASCII
+/// letters compare without regard to case.
+fn has_extension(path: &Path, ext: &str, ignore_case: bool) -> bool {
+    let same = |a: &[u8], b: &[u8]| {
+        if ignore_case {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
         }
+    };
+    if !ext.contains('.') {
+        return path
+            .extension()
+            .is_some_and(|e| same(e.as_encoded_bytes(), ext.as_bytes()));
+    }
+    path.file_name().is_some_and(|name| {
+        let name = name.as_encoded_bytes();
+        let suffix = ext.as_bytes();
+        name.len() > suffix.len() + 1
+            && same(&name[name.len() - suffix.len()..], suffix)
+            && name[name.len() - suffix.len() - 1] == b'.'
+    })
+}
+
+/// The part before `/**` of each ignore pattern that ends in it.
+fn subtree_patterns(ignore: &[String]) -> Vec<String> {
+    ignore
+        .iter()
+        .filter_map(|p| p.strip_suffix("/**"))
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect()
+}
 
-        true
+/// Whether `path` is hidden: any component (or, unless `every_component`,
+/// just the file name) starts with `.`.
+fn is_hidden(path: &Path, every_component: bool) -> bool {
+    let dotted = |name: &OsStr| name.as_encoded_bytes().first() == Some(&b'.');
+    if every_component {
+        path.components().any(|c| match c {
+            Component::Normal(name) => dotted(name),
+            _ => false,
+        })
+    } else {
+        path.file_name().is_some_and(dotted)
     }
 }
 
-fn build_set(patterns: &[String]) -> Result<GlobSet> {
+/// Read glob patterns for `--ignore-file`: one per line, with blank lines and
+/// lines starting with `#` skipped.
+pub fn read_ignore_file(path: &Path) -> Result<Vec<String>, FlashError> {
+    let raw = std::fs::read_to_string(path).map_err(|source| FlashError::IgnoreFileRead {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(pattern_lines(&raw)
+        .map(|(_, pattern)| pattern.to_owned())
+        .collect())
+}
+
+/// Read include globs for `--pattern-file`, laid out as for
+/// [`read_ignore_file`]. Each is checked here, so a bad one is reported with
+/// the file and line it came from.
+pub fn read_pattern_file(path: &Path) -> Result<Vec<String>, FlashError> {
+    let raw = std::fs::read_to_string(path).map_err(|source| FlashError::PatternFileRead {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    pattern_lines(&raw)
+        .map(|(line, pattern)| {
+            compile_glob("include", pattern, false)
+                .map(|_| pattern.to_owned())
+                .map_err(|source| FlashError::PatternFileLine {
+                    path: path.to_path_buf(),
+                    line,
+                    source: Box::new(source),
+                })
+        })
+        .collect()
+}
+
+/// The patterns in a pattern file's text, trimmed, with their 1-based line
+/// numbers. Blank lines and `#` comments are skipped.
+fn pattern_lines(raw: &str) -> impl Iterator<Item = (usize, &str)> {
+    raw.lines()
+        .map(str::trim)
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| (i + 1, line))
+}
+
+fn build_set(
+    set: &'static str,
+    patterns: &[String],
+    ignore_case: bool,
+) -> Result<GlobSet, FlashError> {
     let mut builder = GlobSetBuilder::new();
     for pattern in patterns {
-        let glob = Glob::new(pattern).with_context(|| format!("invalid glob '{pattern}'"))?;
-        builder.add(glob);
+        builder.add(compile_glob(set, pattern, ignore_case)?);
+    }
+    builder
+        .build()
+        .map_err(|source| FlashError::GlobSet { set, source })
+}
+
+fn compile_glob(set: &'static str, pattern: &str, ignore_case: bool) -> Result<Glob, FlashError> {
+    GlobBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|err| FlashError::InvalidPattern {
+            set,
+            pattern: pattern.to_owned(),
+            column: error_column(pattern, err.kind()),
+            kind: err.kind().clone(),
+        })
+}
+
+/// Where in `pattern` the problem globset reported lies, as a 1-based
+/// character column. globset names the kind of problem but not its place.
+fn error_column(pattern: &str, kind: &ErrorKind) -> Option<usize> {
+    let mut braces = Vec::new();
+    let mut class = None;
+    let mut chars = pattern.chars().enumerate().peekable();
+    while let Some((i, c)) = chars.next() {
+        let column = i + 1;
+        match c {
+            '\\' if chars.next().is_none() => {
+                return matches!(kind, ErrorKind::DanglingEscape).then_some(column);
+            }
+            '\\' => {}
+            ']' if class.is_some() => class = None,
+            _ if class.is_some() => {}
+            '[' => {
+                class = Some(column);
+                // A leading `!` or `^`, then a leading `]`, are part of the set.
+                chars.next_if(|&(_, c)| c == '!' || c == '^');
+                chars.next_if(|&(_, c)| c == ']');
+            }
+            '{' => braces.push(column),
+            '}' if braces.pop().is_none() => {
+                return matches!(kind, ErrorKind::UnopenedAlternates).then_some(column);
+            }
+            _ => {}
+        }
+    }
+    match kind {
+        ErrorKind::UnclosedClass => class,
+        ErrorKind::UnclosedAlternates => braces.first().copied(),
+        _ => None,
     }
-    builder.build().context("building glob set")
 }
 
 #[cfg(test)]
@@ -69,6 +514,161 @@ mod tests {
         Filter::new(&extensions, &include, &ignore).expect("valid filter")
     }
 
+    #[test]
+    fn entry_kind_tells_files_from_directories() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        let dir = tmp.path().join("module");
+        let file = tmp.path().join("lib.rs");
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(&file, "").unwrap();
+        let gone = tmp.path().join("gone");
+
+        assert_eq!(EntryKind::All.check(&dir), None);
+        assert_eq!(EntryKind::All.check(&file), None);
+        assert_eq!(EntryKind::Files.check(&dir), Some(RejectReason::Directory));
+        assert_eq!(EntryKind::Files.check(&file), None);
+        assert_eq!(EntryKind::Files.check(&gone), None);
+        assert_eq!(EntryKind::Dirs.check(&dir), None);
+        assert_eq!(
+            EntryKind::Dirs.check(&file),
+            Some(RejectReason::NotDirectory)
+        );
+        assert_eq!(
+            EntryKind::Dirs.check(&gone),
+            Some(RejectReason::NotDirectory)
+        );
+    }
+
+    #[test]
+    fn explain_names_the_rule_behind_each_decision() {
+        let f = filter(
+            &["rs", "md"],
+            &["src/**", "docs/**"],
+            &["**/target/**", "**/*.bak"],
+        );
+        let explain = |p: &str| f.explain(Path::new(p), None);
+
+        assert_eq!(
+            explain("src/target/debug/a.rs"),
+            PathDecision::Rejected {
+                reason: RejectReason::IgnorePattern("**/target/**".into())
+            }
+        );
+        assert_eq!(
+            explain("src/main.txt"),
+            PathDecision::Rejected {
+                reason: RejectReason::Extension
+            }
+        );
+        assert_eq!(
+            explain("tests/it.rs"),
+            PathDecision::Rejected {
+                reason: RejectReason::NoIncludeMatch
+            }
+        );
+        assert_eq!(
+            explain("docs/guide.md"),
+            PathDecision::Accepted {
+                matched: Some("docs/**".into())
+            }
+        );
+        assert_eq!(
+            filter(&[], &[], &[]).explain(Path::new("anything"), None),
+            PathDecision::Accepted { matched: None }
+        );
+    }
+
+    #[test]
+    fn negated_extensions_exclude_and_win_over_listed_ones() {
+        let mixed = filter(&["rs", "!generated.rs", "toml"], &[], &[]);
+        assert!(mixed.accepts(Path::new("src/lib.rs")));
+        assert!(mixed.accepts(Path::new("Cargo.toml")));
+        assert!(!mixed.accepts(Path::new("README.md")), "not listed");
+        assert_eq!(
+            mixed.explain(Path::new("src/schema.generated.rs"), None),
+            PathDecision::Rejected {
+                reason: RejectReason::ExcludedExtension("generated.rs".into())
+            }
+        );
+        assert!(mixed.accepts(Path::new("src/generated.rs")), "just a stem");
+
+        let only_excludes = filter(&["!md", "!lock"], &[], &[]);
+        assert!(only_excludes.accepts(Path::new("src/main.rs")));
+        assert!(only_excludes.accepts(Path::new("Makefile")));
+        assert!(!only_excludes.accepts(Path::new("docs/guide.md")));
+        assert!(!only_excludes.accepts(Path::new("Cargo.lock")));
+
+        let contradictory = filter(&["rs", "!rs"], &[], &[]);
+        assert!(!contradictory.accepts(Path::new("lib.rs")), "excludes win");
+    }
+
+    #[test]
+    fn explain_reports_matches_against_the_root_relative_path() {
+        let f = filter(&[], &["src/**/*.rs"], &[]);
+        let root = Path::new("/work/project");
+        assert_eq!(
+            f.explain(&root.join("src/lib.rs"), Some(root)),
+            PathDecision::Accepted {
+                matched: Some("src/**/*.rs".into())
+            }
+        );
+        assert!(!f.explain(&root.join("src/lib.rs"), None).is_accepted());
+    }
+
+    #[test]
+    fn hidden_files_and_directories_follow_the_hidden_setting() {
+        let root = Path::new("/work/project");
+        let env = root.join(".env");
+        let nested = root.join(".config").join("app.toml");
+        let plain = root.join("src").join("main.rs");
+
+        let included = filter(&[], &[], &[]);
+        for path in [&env, &nested, &plain] {
+            assert!(
+                included.accepts_in(root, path),
+                "{} by default",
+                path.display()
+            );
+        }
+
+        let excluded = filter(&[], &[], &[]).include_hidden(false);
+        assert_eq!(
+            excluded.explain(&env, Some(root)),
+            PathDecision::Rejected {
+                reason: RejectReason::Hidden
+            }
+        );
+        assert!(!excluded.accepts_in(root, &nested), "inside a hidden dir");
+        assert!(excluded.accepts_in(root, &plain));
+    }
+
+    #[test]
+    fn hidden_components_above_the_watch_root_do_not_count() {
+        let root = Path::new("/home/me/.config/app");
+        let excluded = filter(&[], &[], &[]).include_hidden(false);
+        assert!(excluded.accepts_in(root, &root.join("settings.toml")));
+        assert!(!excluded.accepts(Path::new("/home/me/.config/app/.cache")));
+        assert!(excluded.accepts(Path::new("/home/me/.config/app/settings.toml")));
+    }
+
+    #[test]
+    fn decisions_read_as_one_line_explanations() {
+        let ignored = PathDecision::Rejected {
+            reason: RejectReason::IgnorePattern("**/target/**".into()),
+        };
+        assert_eq!(
+            ignored.to_string(),
+            "rejected: matches ignore pattern '**/target/**'"
+        );
+        let kept = PathDecision::Accepted {
+            matched: Some("src/**/*.rs".into()),
+        };
+        assert_eq!(
+            kept.to_string(),
+            "accepted: matches include pattern 'src/**/*.rs'"
+        );
+    }
+
     #[test]
     fn extension_match_passes() {
         let f = filter(&["rs"], &[], &[]);
@@ -97,15 +697,250 @@ mod tests {
         assert!(!f.accepts(&PathBuf::from("src/util/app.py")));
     }
 
+    #[test]
+    fn bare_brace_pattern_matches_every_alternative() {
+        let f = filter(&[], &["*.{js,ts}"], &[]);
+        assert!(f.accepts(&PathBuf::from("app.js")));
+        assert!(f.accepts(&PathBuf::from("app.ts")));
+        assert!(!f.accepts(&PathBuf::from("app.tsx")));
+    }
+
+    #[test]
+    fn ignore_brace_expansion() {
+        let f = filter(&[], &[], &["**/{dist,build}/**"]);
+        assert!(!f.accepts(&PathBuf::from("web/dist/app.js")));
+        assert!(!f.accepts(&PathBuf::from("web/build/app.js")));
+        assert!(f.accepts(&PathBuf::from("web/src/app.js")));
+    }
+
     #[test]
     fn no_filters_accepts_everything() {
         let f = filter(&[], &[], &[]);
         assert!(f.accepts(&PathBuf::from("anywhere/file.bin")));
     }
 
+    #[test]
+    fn include_matches_relative_to_the_watch_root() {
+        let f = filter(&[], &["src/**/*.rs"], &[]);
+        let root = PathBuf::from("/home/me/project");
+        let path = root.join("src/util/mod.rs");
+        assert!(!f.accepts(&path), "absolute path alone should not match");
+        assert!(f.accepts_in(&root, &path));
+        assert!(f.accepts_in(Path::new("project"), Path::new("project/src/lib.rs")));
+    }
+
+    #[test]
+    fn ignore_matches_relative_to_the_watch_root() {
+        let f = filter(&[], &[], &["target/**"]);
+        let root = PathBuf::from("/home/me/project");
+        assert!(!f.accepts_in(&root, &root.join("target/debug/build.log")));
+        assert!(f.accepts_in(&root, &root.join("src/target.rs")));
+    }
+
+    #[test]
+    fn only_patterns_ending_in_a_double_star_ignore_whole_subtrees() {
+        let f = filter(&[], &[], &["target/**", "**/node_modules/**", "build/*.o"]);
+        let root = PathBuf::from("/home/me/project");
+        assert!(f.ignores_subtree(&root.join("target"), Some(&root)));
+        assert!(f.ignores_subtree(&root.join("web/node_modules"), Some(&root)));
+        assert!(!f.ignores_subtree(&root.join("build"), Some(&root)));
+        assert!(!f.ignores_subtree(&root.join("src"), Some(&root)));
+        assert!(!filter(&[], &[], &["*.log"]).ignores_any_subtree());
+    }
+
+    #[test]
+    fn ignore_case_matches_mixed_case_paths_and_patterns() {
+        let f = filter(&["Md", "!Gen.rs", "rs"], &["Docs/**"], &["**/Build/**"]);
+        let root = PathBuf::from("/home/me/Project");
+        assert!(!f.accepts_in(&root, &root.join("docs/README.MD")));
+
+        let f = f.ignore_case(true);
+        assert!(f.accepts_in(&root, &root.join("docs/README.MD")));
+        assert!(f.accepts_in(&root, &root.join("DOCS/Lib.RS")));
+        assert!(!f.accepts_in(&root, &root.join("docs/schema.gen.RS")));
+        assert!(!f.accepts_in(&root, &root.join("docs/build/out.md")));
+        assert!(f.ignores_subtree(&root.join("docs/BUILD"), Some(&root)));
+        assert!(!f.accepts_in(&root, &root.join("src/notes.md")));
+    }
+
+    #[test]
+    fn absolute_patterns_still_match_the_full_path() {
+        let f = filter(&[], &["/home/me/project/**/*.rs"], &[]);
+        let root = PathBuf::from("/home/me/project");
+        assert!(f.accepts_in(&root, &root.join("src/lib.rs")));
+    }
+
+    fn editor_temp_filter() -> Filter {
+        let ignore: Vec<String> = EDITOR_TEMP_PATTERNS.iter().map(|s| s.to_string()).collect();
+        Filter::new(&[], &[], &ignore).expect("editor temp patterns compile")
+    }
+
+    #[test]
+    fn editor_temp_ignores_vim_scratch_files() {
+        let f = editor_temp_filter();
+        assert!(!f.accepts(&PathBuf::from("src/.main.rs.swp")));
+        assert!(!f.accepts(&PathBuf::from("src/.main.rs.swo")));
+        assert!(!f.accepts(&PathBuf::from("src/main.rs~")));
+        assert!(!f.accepts(&PathBuf::from("src/4913")));
+        assert!(
+            f.accepts(&PathBuf::from("data/2024")),
+            "other numeric names"
+        );
+        assert!(f.accepts(&PathBuf::from("data/12345")));
+        assert!(f.accepts(&PathBuf::from("src/main.rs")));
+        assert!(f.accepts(&PathBuf::from("src/v2024.rs")));
+    }
+
+    #[test]
+    fn editor_temp_ignores_emacs_scratch_files() {
+        let f = editor_temp_filter();
+        assert!(!f.accepts(&PathBuf::from("src/#main.rs#")));
+        assert!(!f.accepts(&PathBuf::from("src/.#main.rs")));
+        assert!(f.accepts(&PathBuf::from("src/main#1.rs")));
+    }
+
+    #[test]
+    fn editor_temp_ignores_jetbrains_scratch_files() {
+        let f = editor_temp_filter();
+        assert!(!f.accepts(&PathBuf::from("src/main.rs___jb_tmp___")));
+        assert!(!f.accepts(&PathBuf::from("src/main.rs___jb_old___")));
+        assert!(f.accepts(&PathBuf::from("src/jb_tmp.rs")));
+    }
+
+    #[test]
+    fn ignore_file_skips_comments_and_blank_lines() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            b"# shared ignores\n\n**/target/**\n  **/*.log  \n# trailing comment\n*.tmp\n",
+        )
+        .unwrap();
+
+        let patterns = read_ignore_file(file.path()).unwrap();
+        assert_eq!(patterns, ["**/target/**", "**/*.log", "*.tmp"]);
+
+        let f = Filter::new(&[], &[], &patterns).expect("valid filter");
+        assert!(!f.accepts(&PathBuf::from("crate/target/debug/app")));
+        assert!(!f.accepts(&PathBuf::from("logs/app.log")));
+        assert!(!f.accepts(&PathBuf::from("scratch.tmp")));
+        assert!(f.accepts(&PathBuf::from("src/main.rs")));
+    }
+
+    #[test]
+    fn pattern_file_filters_like_the_same_inline_patterns() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(
+            &mut file,
+            b"# sources\nsrc/**/*.rs\n\n  Cargo.toml  \n# docs too\ndocs/*.md\n",
+        )
+        .unwrap();
+
+        let patterns = read_pattern_file(file.path()).unwrap();
+        assert_eq!(patterns, ["src/**/*.rs", "Cargo.toml", "docs/*.md"]);
+
+        let from_file = Filter::new(&[], &patterns, &[]).unwrap();
+        let inline = Filter::new(
+            &[],
+            &[
+                "src/**/*.rs".into(),
+                "Cargo.toml".into(),
+                "docs/*.md".into(),
+            ],
+            &[],
+        )
+        .unwrap();
+        for path in [
+            "src/main.rs",
+            "src/bin/tool.rs",
+            "Cargo.toml",
+            "docs/guide.md",
+            "docs/api/deep.md",
+            "README.md",
+            "tests/cli.rs",
+        ] {
+            let path = PathBuf::from(path);
+            assert_eq!(from_file.accepts(&path), inline.accepts(&path), "{path:?}");
+        }
+        assert!(from_file.accepts(&PathBuf::from("src/main.rs")));
+        assert!(!from_file.accepts(&PathBuf::from("README.md")));
+    }
+
+    #[test]
+    fn invalid_pattern_file_line_names_the_file_and_line() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, b"# includes\nsrc/**\n\nsrc/[ab\n").unwrap();
+
+        let err = read_pattern_file(file.path()).unwrap_err();
+        assert!(
+            matches!(&err, FlashError::PatternFileLine { line: 4, source, .. }
+                if matches!(**source, FlashError::InvalidPattern { .. })),
+            "{err:?}"
+        );
+        let message = format!("{:#}", anyhow::Error::from(err));
+        assert!(
+            message.contains(&format!("'{}', line 4", file.path().display()))
+                && message.contains("invalid glob 'src/[ab'"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn missing_ignore_file_names_the_path() {
+        let err = read_ignore_file(Path::new("/definitely/not/here.ignore")).unwrap_err();
+        assert!(matches!(err, FlashError::IgnoreFileRead { .. }));
+        assert!(err.to_string().contains("/definitely/not/here.ignore"));
+    }
+
     #[test]
     fn invalid_glob_surfaces_error() {
         let bad = Filter::new(&[], &["[unterminated".to_string()], &[]);
         assert!(bad.is_err());
     }
+
+    #[test]
+    fn invalid_glob_names_the_pattern_and_its_set() {
+        let err = Filter::new(&[], &[], &["ok/**".to_string(), "a{b".to_string()])
+            .expect_err("unclosed brace should fail");
+        match err {
+            FlashError::InvalidPattern { set, pattern, .. } => {
+                assert_eq!(set, "ignore");
+                assert_eq!(pattern, "a{b");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn invalid_glob_errors_say_what_is_wrong_and_where() {
+        let message = |pattern: &str| {
+            Filter::new(&[], &[pattern.to_string()], &[])
+                .expect_err(pattern)
+                .to_string()
+        };
+        let unclosed = message("src/[ab.rs");
+        assert!(unclosed.contains("'src/[ab.rs' at column 5"), "{unclosed}");
+        assert!(unclosed.contains("unclosed character class"), "{unclosed}");
+        assert!(message("{a,{b}").contains("at column 1"));
+        assert!(message("a}b").contains("at column 2"));
+        assert!(
+            message("[]x").contains("at column 1"),
+            "']' first is literal"
+        );
+        let range = message("[z-a]");
+        assert!(range.contains("invalid range"), "{range}");
+        assert!(!range.contains("column"), "{range}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_file_names_are_matched_without_lossy_conversion() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"src/caf\xe9.rs");
+        let f = filter(&["rs"], &["src/**"], &["**/*.tmp"]);
+        assert!(f.accepts(Path::new(name)));
+
+        let ignored = OsStr::from_bytes(b"src/caf\xe9.tmp");
+        assert!(!f.accepts(Path::new(ignored)));
+    }
 }
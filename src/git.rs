@@ -0,0 +1,143 @@
+//! `--git-tracked-only`: the files git tracks under the watch roots.
+//!
+//! The set comes from `git ls-files`, run once per root at startup and again
+//! whenever it is older than [`REFRESH`] by the time a change is judged. A
+//! file created and `git add`ed in the meantime is therefore ignored until
+//! the next refresh.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::error::FlashError;
+
+/// How long a listing is trusted before git is asked again.
+const REFRESH: Duration = Duration::from_secs(2);
+
+pub(crate) struct Tracked {
+    roots: Vec<PathBuf>,
+    listing: RefCell<Listing>,
+}
+
+struct Listing {
+    /// Tracked files, plus every directory between them and their root, so
+    /// events on a directory that holds tracked files count too.
+    paths: HashSet<PathBuf>,
+    taken: Instant,
+}
+
+impl Tracked {
+    /// List the tracked files under `roots`. Fails if git can't be run or a
+    /// root is not inside a work tree.
+    pub(crate) fn load(roots: &[PathBuf]) -> Result<Self, FlashError> {
+        let paths = list(roots)?;
+        Ok(Self {
+            roots: roots.to_vec(),
+            listing: RefCell::new(Listing {
+                paths,
+                taken: Instant::now(),
+            }),
+        })
+    }
+
+    /// Whether git tracks `path`, re-listing first if the listing is stale.
+    /// A failed refresh keeps the previous listing.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        let mut listing = self.listing.borrow_mut();
+        if listing.taken.elapsed() >= REFRESH {
+            if let Ok(paths) = list(&self.roots) {
+                listing.paths = paths;
+            }
+            listing.taken = Instant::now();
+        }
+        listing.paths.contains(path)
+    }
+}
+
+fn list(roots: &[PathBuf]) -> Result<HashSet<PathBuf>, FlashError> {
+    let mut paths = HashSet::new();
+    for root in roots {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(root)
+            .args(["ls-files", "-z"])
+            .output()
+            .map_err(|err| FlashError::GitLsFiles {
+                root: root.clone(),
+                message: err.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(FlashError::GitLsFiles {
+                root: root.clone(),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        for name in output.stdout.split(|&b| b == 0).filter(|n| !n.is_empty()) {
+            let mut path = root.join(path_from_bytes(name));
+            while path != *root && paths.insert(path.clone()) {
+                if !path.pop() {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(std::ffi::OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .status()
+            .expect("git should be installed");
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    #[test]
+    fn lists_tracked_files_and_their_directories_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(root.join("build.log"), "").unwrap();
+        git(&root, &["init", "-q"]);
+        git(&root, &["add", "src/lib.rs"]);
+
+        let tracked = Tracked::load(std::slice::from_ref(&root)).unwrap();
+        assert!(tracked.contains(&root.join("src/lib.rs")));
+        assert!(tracked.contains(&root.join("src")));
+        assert!(!tracked.contains(&root.join("build.log")));
+        assert!(!tracked.contains(&root));
+    }
+
+    #[test]
+    fn a_root_outside_a_work_tree_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let err = Tracked::load(std::slice::from_ref(&root))
+            .err()
+            .expect("not a repo");
+        assert!(
+            matches!(&err, FlashError::GitLsFiles { root: r, .. } if *r == root),
+            "{err:?}"
+        );
+    }
+}
@@ -3,19 +3,37 @@
 //! The library is intentionally small. The CLI is a thin shell around
 //! [`run`], which loads the merged [`Settings`], compiles the path
 //! [`Filter`], wires up a debounced [`notify`] watcher, and dispatches
-//! changes to a [`Runner`].
+//! changes to a [`Runner`]. What happens along the way is reported to an
+//! [`EventSink`]; the CLI's is [`ConsoleSink`]. Tools that want the
+//! filtering and debouncing without notify or a command can use
+//! [`EventPipeline`].
 
+mod alert;
 mod bench;
 mod cli;
 mod config;
+mod error;
 mod filter;
+mod git;
+mod pipeline;
 mod runner;
+mod sink;
 mod stats;
+mod style;
+mod version;
 mod watcher;
 
+pub use alert::FailureHook;
 pub use cli::Cli;
-pub use config::{Config, Settings};
-pub use filter::Filter;
-pub use runner::Runner;
+pub use config::{Config, ScopedWatch, Settings, WatchEntry};
+pub use error::FlashError;
+pub use filter::{
+    read_ignore_file, read_pattern_file, EntryKind, Filter, PathDecision, RejectReason,
+    EDITOR_TEMP_PATTERNS,
+};
+pub use pipeline::{Debounced, EventPipeline};
+pub use runner::{ClearMode, RestartSignal, Runner};
+pub use sink::{ChangeEvent, ChangeKind, ConsoleSink, EventSink, LinePrefix, LogFile, PathDisplay};
 pub use stats::Stats;
-pub use watcher::run;
+pub use version::{BuildInfo, VersionFormat};
+pub use watcher::{catch_ctrl_c, interrupt, run, run_with_sink};
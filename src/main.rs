@@ -1,12 +1,15 @@
 use std::process::ExitCode;
 
-use clap::Parser;
 use flash_watcher::Cli;
 
 fn main() -> ExitCode {
-    let cli = Cli::parse();
+    let cli = Cli::parse_args(std::env::args_os());
+    if let Err(err) = flash_watcher::catch_ctrl_c() {
+        eprintln!("flash-watcher: {err:#}");
+        return ExitCode::FAILURE;
+    }
     match flash_watcher::run(cli) {
-        Ok(()) => ExitCode::SUCCESS,
+        Ok(code) => code,
         Err(err) => {
             eprintln!("flash-watcher: {err:#}");
             ExitCode::FAILURE
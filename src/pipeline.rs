@@ -0,0 +1,182 @@
+//! The path-handling half of the watch loop, without notify or a command.
+//!
+//! [`EventPipeline`] turns raw changed paths into the ones that should cause
+//! a run: a batch is deduplicated, directories reported alongside something
+//! inside them are dropped, and what is left goes through a [`Filter`].
+//! [`EventPipeline::debounced`] adds the time dimension, grouping paths from
+//! a channel into batches separated by a quiet period.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::config::DEFAULT_DEBOUNCE_MS;
+use crate::filter::Filter;
+use crate::watcher::{drop_parent_dirs, root_of};
+
+/// Filters and debounces a stream of changed paths.
+#[derive(Debug, Clone)]
+pub struct EventPipeline {
+    filter: Filter,
+    roots: Vec<PathBuf>,
+    debounce: Duration,
+}
+
+impl EventPipeline {
+    /// A pipeline with the CLI's default 50ms debounce and no watch roots.
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            roots: Vec::new(),
+            debounce: Duration::from_millis(DEFAULT_DEBOUNCE_MS),
+        }
+    }
+
+    /// Judge paths relative to the most specific of `roots` they fall under,
+    /// as the watcher does for its watch roots. Include and ignore globs are
+    /// matched against that relative path.
+    pub fn with_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.roots = roots;
+        self
+    }
+
+    /// How long the input has to stay quiet before [`debounced`] yields a
+    /// batch. Zero yields every path on its own.
+    ///
+    /// [`debounced`]: Self::debounced
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// The paths in `batch` that should cause a run, in their first-seen
+    /// order.
+    pub fn process(&self, batch: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+        let mut unique: Vec<PathBuf> = Vec::new();
+        for path in batch {
+            if !unique.contains(&path) {
+                unique.push(path);
+            }
+        }
+        drop_parent_dirs(unique)
+            .into_iter()
+            .filter(|path| {
+                self.filter
+                    .explain(path, root_of(&self.roots, path))
+                    .is_accepted()
+            })
+            .collect()
+    }
+
+    /// Read paths from `rx` and yield them in debounced, processed batches.
+    /// Batches with nothing left after [`process`](Self::process) are
+    /// skipped. The iterator ends once every sender is gone and the last
+    /// batch is out.
+    pub fn debounced(self, rx: Receiver<PathBuf>) -> Debounced {
+        Debounced { pipeline: self, rx }
+    }
+}
+
+/// The iterator behind [`EventPipeline::debounced`].
+#[derive(Debug)]
+pub struct Debounced {
+    pipeline: EventPipeline,
+    rx: Receiver<PathBuf>,
+}
+
+impl Iterator for Debounced {
+    type Item = Vec<PathBuf>;
+
+    fn next(&mut self) -> Option<Vec<PathBuf>> {
+        loop {
+            let mut batch = vec![self.rx.recv().ok()?];
+            let mut quiet_until = Instant::now() + self.pipeline.debounce;
+            while !self.pipeline.debounce.is_zero() {
+                let wait = quiet_until.saturating_duration_since(Instant::now());
+                match self.rx.recv_timeout(wait) {
+                    Ok(path) => {
+                        batch.push(path);
+                        quiet_until = Instant::now() + self.pipeline.debounce;
+                    }
+                    Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+                }
+            }
+            let accepted = self.pipeline.process(batch);
+            if !accepted.is_empty() {
+                return Some(accepted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use std::sync::mpsc::channel;
+    use std::thread;
+
+    fn rust_only() -> EventPipeline {
+        let filter = Filter::new(&["rs".to_string()], &[], &["target/**".to_string()]).unwrap();
+        EventPipeline::new(filter).with_roots(vec![PathBuf::from("/repo")])
+    }
+
+    #[test]
+    fn process_dedupes_drops_parents_and_filters() {
+        let batch = [
+            "/repo/src",
+            "/repo/src/lib.rs",
+            "/repo/src/lib.rs",
+            "/repo/README.md",
+            "/repo/target/debug/build.rs",
+            "/repo/src/main.rs",
+        ]
+        .map(PathBuf::from);
+        assert_eq!(
+            rust_only().process(batch),
+            [
+                PathBuf::from("/repo/src/lib.rs"),
+                PathBuf::from("/repo/src/main.rs")
+            ]
+        );
+    }
+
+    #[test]
+    fn a_burst_becomes_one_batch_and_a_gap_starts_another() {
+        let (tx, rx) = channel();
+        let pipeline = rust_only().with_debounce(Duration::from_millis(100));
+        let sender = thread::spawn(move || {
+            for path in ["/repo/a.rs", "/repo/notes.txt", "/repo/a.rs", "/repo/b.rs"] {
+                tx.send(PathBuf::from(path)).unwrap();
+            }
+            thread::sleep(Duration::from_millis(400));
+            tx.send(PathBuf::from("/repo/c.rs")).unwrap();
+            tx.send(PathBuf::from("/repo/target/x.rs")).unwrap();
+        });
+
+        let batches: Vec<Vec<PathBuf>> = pipeline.debounced(rx).collect();
+        sender.join().unwrap();
+        let expected: Vec<Vec<PathBuf>> = vec![
+            vec!["/repo/a.rs".into(), "/repo/b.rs".into()],
+            vec!["/repo/c.rs".into()],
+        ];
+        assert_eq!(batches, expected);
+    }
+
+    #[test]
+    fn zero_debounce_yields_each_accepted_path_alone() {
+        let (tx, rx) = channel();
+        for path in ["/repo/a.rs", "/repo/skip.md", "/repo/b.rs"] {
+            tx.send(PathBuf::from(path)).unwrap();
+        }
+        drop(tx);
+        let batches: Vec<Vec<PathBuf>> = rust_only()
+            .with_debounce(Duration::ZERO)
+            .debounced(rx)
+            .collect();
+        assert_eq!(
+            batches,
+            [[Path::new("/repo/a.rs")], [Path::new("/repo/b.rs")]]
+        );
+    }
+}
@@ -1,18 +1,48 @@
-use std::process::{Child, Command};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::alert::FailureHook;
+use crate::sink::{ChangeKind, EventSink};
 
 /// Executes the user's command in response to file events.
 ///
 /// In *restart* mode the previous child is killed before each new run, so
 /// long-running processes (servers, watchers, REPLs) come back fresh on every
 /// change. In the default mode each invocation is one-shot: the runner waits
-/// for it to finish so failures surface immediately.
+/// for it to finish so failures surface immediately. With
+/// [`with_parallel`](Self::with_parallel) one-shot runs become background
+/// jobs instead, several at a time.
 pub struct Runner {
     command: Vec<String>,
     restart: bool,
-    clear: bool,
+    clear: ClearMode,
+    raw: bool,
+    literal: bool,
+    group: bool,
+    capture: bool,
+    stop_signal: Option<RestartSignal>,
+    grace: Duration,
+    parallel: Option<usize>,
+    /// One entry per matrix combination: each `{key}` with its value.
+    matrix: Vec<Vec<(String, String)>>,
+    /// Commands that replace `command` for changes of one kind.
+    by_kind: BTreeMap<ChangeKind, Vec<String>>,
+    jobs: Vec<Job>,
+    next_job: u64,
     current: Option<Child>,
+    last_status: Option<ExitStatus>,
+    on_failure: Option<Box<dyn FailureHook>>,
 }
 
 impl Runner {
@@ -20,60 +50,595 @@ impl Runner {
         Self {
             command,
             restart,
-            clear,
+            clear: if clear {
+                ClearMode::Always
+            } else {
+                ClearMode::Never
+            },
+            raw: false,
+            literal: false,
+            group: false,
+            capture: false,
+            stop_signal: None,
+            grace: Duration::ZERO,
+            parallel: None,
+            matrix: vec![Vec::new()],
+            by_kind: BTreeMap::new(),
+            jobs: Vec::new(),
+            next_job: 0,
             current: None,
+            last_status: None,
+            on_failure: None,
         }
     }
 
-    /// Invoke the command. Returns `Ok(())` if the command was launched; a
-    /// non-zero exit status from a one-shot run is reported on stderr but does
-    /// not bubble up as an error — the watcher keeps running.
-    pub fn run(&mut self) -> Result<()> {
+    /// Report failed one-shot runs to `hook` (e.g. a desktop notifier).
+    pub fn with_failure_hook(mut self, hook: Box<dyn FailureHook>) -> Self {
+        self.on_failure = Some(hook);
+        self
+    }
+
+    /// Join the command with spaces and always run it through the shell, so
+    /// tokens such as `&&` or `|` act as shell syntax (`--raw-command`).
+    pub fn with_raw_command(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Never hand a single-argument command to the shell: run it as the
+    /// program's name, spaces and all (a command given after `--`).
+    /// [`with_raw_command`](Self::with_raw_command) still wins.
+    pub fn with_literal_command(mut self, literal: bool) -> Self {
+        self.literal = literal;
+        self
+    }
+
+    /// Start the command as the leader of a new process group and kill the
+    /// whole group when stopping it, so whatever a shell script launched goes
+    /// down with it (`--process-group`). Unix only; elsewhere this does
+    /// nothing.
+    pub fn with_process_group(mut self, group: bool) -> Self {
+        self.group = group;
+        self
+    }
+
+    /// Collect a one-shot run's stdout and stderr and hand them to the sink's
+    /// [`EventSink::on_output`] once it exits, instead of letting the command
+    /// write to the terminal directly (`--capture`). Restart-mode runs keep
+    /// the terminal, since they may never exit.
+    pub fn with_capture(mut self, capture: bool) -> Self {
+        self.capture = capture;
+        self
+    }
+
+    /// When to clear the terminal before a run, in place of the `clear` flag
+    /// given to [`Runner::new`] (`--clear-mode`).
+    pub fn with_clear_mode(mut self, mode: ClearMode) -> Self {
+        self.clear = mode;
+        self
+    }
+
+    /// Stop a restart-mode child with `signal` instead of killing it outright,
+    /// and give it up to `grace` to exit before falling back to SIGKILL
+    /// (`--restart-signal`, `--restart-grace`). With `--process-group` the
+    /// signal goes to the whole group. Unix only; elsewhere the child is
+    /// killed as before.
+    pub fn with_restart_signal(mut self, signal: Option<RestartSignal>, grace: Duration) -> Self {
+        self.stop_signal = signal;
+        self.grace = grace;
+        self
+    }
+
+    /// Start one-shot runs in the background, up to `limit` at once, rather
+    /// than waiting for each (`--parallel`). A run past the limit waits for
+    /// a slot. Each job's output is relayed a line at a time behind a
+    /// `[job N]` tag, so interleaved output stays attributable. Finished
+    /// jobs are reported to the sink by [`reap`](Self::reap). Has no effect
+    /// in restart mode.
+    pub fn with_parallel(mut self, limit: Option<usize>) -> Self {
+        self.parallel = limit.map(|n| n.max(1));
+        self
+    }
+
+    /// Run the command once per combination of `matrix` values instead of
+    /// once, one after another, with each `{key}` in it replaced by that
+    /// combination's value. Keys are combined in sorted order, the last
+    /// varying fastest. Bounding the number of combinations is up to the
+    /// caller ([`Settings::validate`](crate::Settings::validate) does). Has
+    /// no effect in restart mode, which keeps a single process.
+    pub fn with_matrix(mut self, matrix: &BTreeMap<String, Vec<String>>) -> Self {
+        self.matrix = combinations(matrix);
+        self
+    }
+
+    /// Run `command` instead of the default one for changes of `kind`
+    /// (`on_create`, `on_modify`, `on_remove` in the config file). `None`
+    /// keeps the default.
+    pub fn with_kind_command(mut self, kind: ChangeKind, command: Option<Vec<String>>) -> Self {
+        match command.filter(|command| !command.is_empty()) {
+            Some(command) => self.by_kind.insert(kind, command),
+            None => self.by_kind.remove(&kind),
+        };
+        self
+    }
+
+    /// Invoke the command. Returns `Ok(())` if the command was launched; the
+    /// exit status of a one-shot run goes to `sink` rather than bubbling up
+    /// as an error — the watcher keeps running.
+    pub fn run(&mut self, sink: &dyn EventSink) -> Result<()> {
+        self.run_for(sink, &[])
+    }
+
+    /// [`run`](Self::run) on behalf of changes of the given kinds. Each
+    /// `{event}` in the command becomes the kind, or when a debounced batch
+    /// mixed several, each of them once, comma-separated in the order
+    /// `create,modify,remove`. Plain [`run`](Self::run) expands it to
+    /// nothing.
+    ///
+    /// Kinds with a command of their own
+    /// ([`with_kind_command`](Self::with_kind_command)) run it, the rest the
+    /// default command, so a mixed batch can run several commands in turn.
+    pub fn run_for(&mut self, sink: &dyn EventSink, kinds: &[ChangeKind]) -> Result<()> {
+        self.run_for_scripts(sink, &[], kinds)
+    }
+
+    /// [`run_for`](Self::run_for) for a batch some of whose changes have a
+    /// script of their own (`--command-from-matched-file`), each given with
+    /// the kinds of the changes it claimed. Every script runs once, as a
+    /// program rather than through the shell, ahead of the commands for
+    /// `kinds`: those of the changes no script claimed. A batch the scripts
+    /// claimed entirely runs nothing else. Restart mode keeps a single
+    /// process, so there a batch that would need several runs the default
+    /// command.
+    pub fn run_for_scripts(
+        &mut self,
+        sink: &dyn EventSink,
+        scripts: &[(PathBuf, Vec<ChangeKind>)],
+        kinds: &[ChangeKind],
+    ) -> Result<()> {
         if self.restart {
             self.stop_current();
         }
 
-        if self.clear {
+        if self.should_clear() {
             // CSI 2J clears the screen, CSI H homes the cursor.
             print!("\x1B[2J\x1B[H");
         }
 
-        let child = self.spawn().context("launching command")?;
+        let mut commands: Vec<(Vec<String>, bool)> = scripts
+            .iter()
+            .map(|(script, _)| (vec![script.to_string_lossy().into_owned()], true))
+            .collect();
+        if scripts.is_empty() || !kinds.is_empty() {
+            let planned = self.commands_for(kinds);
+            if self.restart {
+                commands.extend(
+                    planned
+                        .iter()
+                        .map(|(command, kinds)| (expand_event(command, kinds), false)),
+                );
+            } else {
+                commands.extend(planned.iter().flat_map(|(command, kinds)| {
+                    self.matrix.iter().map(move |combination| {
+                        (
+                            expand_event(&expand_matrix(command, combination), kinds),
+                            false,
+                        )
+                    })
+                }));
+            }
+        }
+        if self.restart && commands.len() > 1 {
+            let all: Vec<ChangeKind> = scripts
+                .iter()
+                .flat_map(|(_, kinds)| kinds.iter().copied())
+                .chain(kinds.iter().copied())
+                .collect();
+            commands = vec![(expand_event(&self.command, &all), false)];
+        }
+        for (command, script) in commands {
+            self.launch(sink, command, script)?;
+        }
+        Ok(())
+    }
+
+    /// The commands a batch of `kinds` runs, each with the kinds it is for, in
+    /// the order `create,modify,remove`. Restart mode keeps a single
+    /// process, so a batch that would need several runs the default command.
+    fn commands_for(&self, kinds: &[ChangeKind]) -> Vec<(Vec<String>, Vec<ChangeKind>)> {
+        let mut sorted = kinds.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+        let mut commands: Vec<(Vec<String>, Vec<ChangeKind>)> = Vec::new();
+        for kind in sorted {
+            let command = self.by_kind.get(&kind).unwrap_or(&self.command);
+            match commands.iter_mut().find(|(c, _)| c == command) {
+                Some((_, kinds)) => kinds.push(kind),
+                None => commands.push((command.clone(), vec![kind])),
+            }
+        }
+        if commands.is_empty() || (self.restart && commands.len() > 1) {
+            return vec![(self.command.clone(), kinds.to_vec())];
+        }
+        commands
+    }
+
+    /// Whether the next run starts on a clean screen. Under
+    /// [`ClearMode::OnSuccess`] a failed run's output stays up.
+    fn should_clear(&self) -> bool {
+        match self.clear {
+            ClearMode::Always => true,
+            ClearMode::OnSuccess => self.last_status.is_none_or(|s| s.success()),
+            ClearMode::Never => false,
+        }
+    }
+
+    /// Start one expanded command: in the background under `--parallel`,
+    /// held on to in restart mode, and otherwise waited for. A `script` is
+    /// never handed to the shell.
+    fn launch(&mut self, sink: &dyn EventSink, command: Vec<String>, script: bool) -> Result<()> {
+        let limit = self.parallel.filter(|_| !self.restart);
+        if let Some(limit) = limit {
+            self.reap(sink);
+            while self.jobs.len() >= limit {
+                thread::sleep(JOB_POLL);
+                self.reap(sink);
+            }
+        }
+
+        if limit.is_some() {
+            let child = self
+                .spawn(&command, true, script)
+                .context("launching command")?;
+            sink.on_run(&command);
+            self.next_job += 1;
+            self.jobs.push(Job::start(self.next_job, command, child));
+            return Ok(());
+        }
+
+        let capture = self.capture && !self.restart;
+        let mut child = self
+            .spawn(&command, capture, script)
+            .context("launching command")?;
+        sink.on_run(&command);
 
         if self.restart {
             self.current = Some(child);
         } else {
-            let mut child = child;
-            let status = child.wait().context("waiting on command")?;
-            if !status.success() {
-                eprintln!("flash-watcher: command exited with {status}");
-            }
+            let leader = child.id();
+            let status = if capture {
+                child.wait_with_output().map(|output| {
+                    sink.on_output(&command, &output);
+                    output.status
+                })
+            } else {
+                child.wait()
+            };
+            self.release_group(leader);
+            let status = status.context("waiting on command")?;
+            self.report(sink, &command, status);
         }
 
         Ok(())
     }
 
-    fn spawn(&self) -> std::io::Result<Child> {
-        if needs_shell(&self.command) {
-            let joined = self.command.join(" ");
-            if cfg!(windows) {
-                Command::new("cmd").args(["/C", &joined]).spawn()
-            } else {
-                Command::new("sh").args(["-c", &joined]).spawn()
+    fn report(&mut self, sink: &dyn EventSink, command: &[String], status: ExitStatus) {
+        sink.on_exit(status);
+        if !status.success() {
+            if let Some(hook) = self.on_failure.as_mut() {
+                hook.command_failed(command, status);
             }
+        }
+        self.last_status = Some(status);
+    }
+
+    /// Report the background jobs that have finished since the last call, in
+    /// the order they were started. If any of them failed,
+    /// [`last_status`](Self::last_status) is that failure rather than
+    /// whichever finished last, so one bad job in a batch is not hidden.
+    pub fn reap(&mut self, sink: &dyn EventSink) {
+        let mut failed = None;
+        let mut i = 0;
+        while i < self.jobs.len() {
+            let status = match self.jobs[i].child.try_wait() {
+                Ok(None) => {
+                    i += 1;
+                    continue;
+                }
+                Ok(Some(status)) => Some(status),
+                Err(_) => None,
+            };
+            let job = self.jobs.remove(i);
+            let command = job.finish();
+            if let Some(status) = status {
+                self.report(sink, &command, status);
+                if !status.success() {
+                    failed.get_or_insert(status);
+                }
+            }
+        }
+        if failed.is_some() {
+            self.last_status = failed;
+        }
+    }
+
+    /// How many background jobs are still running.
+    pub fn running_jobs(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Wait for every background job, reporting each as it finishes.
+    pub fn wait_for_jobs(&mut self, sink: &dyn EventSink) {
+        self.reap(sink);
+        while !self.jobs.is_empty() {
+            thread::sleep(JOB_POLL);
+            self.reap(sink);
+        }
+    }
+
+    /// Run the command once and wait for it, bypassing the sink, the failure
+    /// hook, and `--clear`. For setup and teardown commands that are not the
+    /// watched command itself.
+    pub fn run_to_end(&mut self) -> Result<ExitStatus> {
+        let status = self
+            .spawn(&self.command, false, false)
+            .context("launching command")?
+            .wait()
+            .context("waiting on command")?;
+        self.last_status = Some(status);
+        Ok(status)
+    }
+
+    fn spawn(&self, argv: &[String], capture: bool, script: bool) -> std::io::Result<Child> {
+        let mut command = if !script && (self.raw || (!self.literal && needs_shell(argv))) {
+            shell(&argv.join(" "))
         } else {
             // Skip the shell entirely. Saves a fork/exec on every run and
             // preserves the user's argument quoting exactly as clap parsed it.
-            Command::new(&self.command[0])
-                .args(&self.command[1..])
-                .spawn()
+            let mut command = Command::new(&argv[0]);
+            command.args(&argv[1..]);
+            command
+        };
+        #[cfg(unix)]
+        if self.group {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        if capture {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let child = command.spawn()?;
+        #[cfg(unix)]
+        if self.group {
+            groups().push(child.id());
+        }
+        Ok(child)
+    }
+
+    /// Forget the group led by `leader` once it has been reaped, so
+    /// [`interrupt_group`] never hits a recycled pid.
+    fn release_group(&self, leader: u32) {
+        #[cfg(unix)]
+        if self.group {
+            release_group(leader);
         }
+        #[cfg(not(unix))]
+        let _ = leader;
+    }
+
+    /// How the most recent one-shot run exited, without waiting on a
+    /// restart-mode child. `None` until a run has finished.
+    pub fn last_status(&self) -> Option<ExitStatus> {
+        self.last_status
+    }
+
+    /// Wind down for exit: stop a restart-mode child that is still running,
+    /// as the next change would, then report how the most recent run exited.
+    /// A child that already exited counts as that run; one stopped here
+    /// doesn't, since a server never ends on its own.
+    pub fn finish(&mut self) -> Option<ExitStatus> {
+        match self.current.as_mut().map(Child::try_wait) {
+            Some(Ok(Some(status))) => {
+                self.last_status = Some(status);
+                if let Some(child) = self.current.take() {
+                    self.release_group(child.id());
+                }
+            }
+            Some(_) => self.stop_current(),
+            None => {}
+        }
+        self.last_status
     }
 
     fn stop_current(&mut self) {
         if let Some(mut child) = self.current.take() {
+            if self.stop_gracefully(&mut child) {
+                self.release_group(child.id());
+                return;
+            }
+            #[cfg(unix)]
+            if self.group {
+                // SAFETY: plain syscall; a negative pid names the group the
+                // child leads, which it still does until it is reaped below.
+                unsafe {
+                    libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+                }
+            }
             let _ = child.kill();
             let _ = child.wait();
+            self.release_group(child.id());
+        }
+    }
+
+    /// Send the configured stop signal and wait out the grace period.
+    /// Returns whether the child exited (and was reaped) in time.
+    fn stop_gracefully(&self, child: &mut Child) -> bool {
+        let Some(signal) = self.stop_signal else {
+            return false;
+        };
+        if !signal.send(child.id(), self.group) {
+            return false;
+        }
+        let deadline = Instant::now() + self.grace;
+        loop {
+            match child.try_wait() {
+                Ok(Some(_)) => return true,
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(STOP_POLL);
+                }
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// How often [`Runner::stop_gracefully`] checks whether the child is gone.
+const STOP_POLL: Duration = Duration::from_millis(10);
+
+/// How often a run waiting for a free `--parallel` slot checks for one.
+const JOB_POLL: Duration = Duration::from_millis(10);
+
+/// How long a finished job's relays get to pass on the rest of its output.
+/// A process it left behind can hold the pipes open for much longer.
+const RELAY_GRACE: Duration = Duration::from_millis(200);
+
+/// A `--parallel` run in the background, with the threads relaying its
+/// output.
+struct Job {
+    child: Child,
+    command: Vec<String>,
+    relays: Vec<JoinHandle<()>>,
+}
+
+impl Job {
+    fn start(id: u64, command: Vec<String>, mut child: Child) -> Self {
+        let tag = format!("[job {id}] ");
+        let mut relays = Vec::with_capacity(2);
+        if let Some(stdout) = child.stdout.take() {
+            relays.push(relay(stdout, tag.clone(), std::io::stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            relays.push(relay(stderr, tag, std::io::stderr));
+        }
+        Self {
+            child,
+            command,
+            relays,
+        }
+    }
+
+    /// Give the relays [`RELAY_GRACE`] to flush what the job printed last,
+    /// then hand back its command for reporting. Relays still reading then,
+    /// because something the job started kept the pipes, are left to run.
+    fn finish(self) -> Vec<String> {
+        let deadline = Instant::now() + RELAY_GRACE;
+        while self.relays.iter().any(|relay| !relay.is_finished()) && Instant::now() < deadline {
+            thread::sleep(STOP_POLL);
+        }
+        for relay in self.relays {
+            if relay.is_finished() {
+                let _ = relay.join();
+            }
+        }
+        #[cfg(unix)]
+        release_group(self.child.id());
+        self.command
+    }
+}
+
+/// Copy `source` to `sink` a line at a time, each behind `tag`. Each line
+/// goes out in a single write, so two jobs never share a line.
+fn relay<R, W>(source: R, tag: String, sink: fn() -> W) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+    W: Write + 'static,
+{
+    thread::spawn(move || {
+        let mut reader = BufReader::new(source);
+        let mut line = tag.clone().into_bytes();
+        while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+            if !line.ends_with(b"\n") {
+                line.push(b'\n');
+            }
+            let mut out = sink();
+            let _ = out.write_all(&line);
+            let _ = out.flush();
+            line.truncate(tag.len());
+        }
+    })
+}
+
+/// When to clear the terminal before a run (`--clear-mode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClearMode {
+    /// Before every run, like `--clear`.
+    Always,
+    /// Only if the previous run succeeded, so a failure stays on screen.
+    OnSuccess,
+    /// Never.
+    Never,
+}
+
+/// The signal a restart-mode child is stopped with (`--restart-signal`).
+/// Without one the child is killed with SIGKILL right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema)]
+pub enum RestartSignal {
+    #[value(name = "SIGTERM", alias = "TERM")]
+    #[serde(rename = "SIGTERM", alias = "TERM")]
+    Term,
+    #[value(name = "SIGINT", alias = "INT")]
+    #[serde(rename = "SIGINT", alias = "INT")]
+    Int,
+    #[value(name = "SIGHUP", alias = "HUP")]
+    #[serde(rename = "SIGHUP", alias = "HUP")]
+    Hup,
+    #[value(name = "SIGQUIT", alias = "QUIT")]
+    #[serde(rename = "SIGQUIT", alias = "QUIT")]
+    Quit,
+    #[value(name = "SIGUSR1", alias = "USR1")]
+    #[serde(rename = "SIGUSR1", alias = "USR1")]
+    Usr1,
+    #[value(name = "SIGUSR2", alias = "USR2")]
+    #[serde(rename = "SIGUSR2", alias = "USR2")]
+    Usr2,
+    #[value(name = "SIGKILL", alias = "KILL")]
+    #[serde(rename = "SIGKILL", alias = "KILL")]
+    Kill,
+}
+
+impl RestartSignal {
+    /// Deliver the signal to `pid`, or to the group it leads. Returns false
+    /// where there are no signals to send, so the caller kills instead.
+    fn send(self, pid: u32, group: bool) -> bool {
+        #[cfg(unix)]
+        {
+            let target = if group {
+                -(pid as libc::pid_t)
+            } else {
+                pid as libc::pid_t
+            };
+            // SAFETY: plain syscall on a child (or the group it leads) that
+            // has not been reaped yet.
+            unsafe { libc::kill(target, self.raw()) == 0 }
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (pid, group);
+            false
+        }
+    }
+
+    #[cfg(unix)]
+    fn raw(self) -> libc::c_int {
+        match self {
+            Self::Term => libc::SIGTERM,
+            Self::Int => libc::SIGINT,
+            Self::Hup => libc::SIGHUP,
+            Self::Quit => libc::SIGQUIT,
+            Self::Usr1 => libc::SIGUSR1,
+            Self::Usr2 => libc::SIGUSR2,
+            Self::Kill => libc::SIGKILL,
         }
     }
 }
@@ -84,6 +649,115 @@ impl Drop for Runner {
     }
 }
 
+/// Leaders of the running `--process-group` children, one per `--parallel`
+/// job. A child in its own group no longer gets the terminal's Ctrl-C, so
+/// [`interrupt_group`] passes it on.
+#[cfg(unix)]
+static GROUPS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+#[cfg(unix)]
+fn groups() -> std::sync::MutexGuard<'static, Vec<u32>> {
+    GROUPS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(unix)]
+fn release_group(leader: u32) {
+    groups().retain(|&g| g != leader);
+}
+
+/// Send SIGINT to every running `--process-group` child's group. Called from
+/// the Ctrl-C handler's thread.
+pub(crate) fn interrupt_group() {
+    #[cfg(unix)]
+    for &leader in groups().iter() {
+        // SAFETY: plain syscall on a group we created and have not reaped.
+        unsafe {
+            libc::kill(-(leader as libc::pid_t), libc::SIGINT);
+        }
+    }
+}
+
+/// Every combination of one value per key: `{a: [1, 2], b: [x]}` gives
+/// `[{a}=1, {b}=x]` and `[{a}=2, {b}=x]`. An empty matrix has one, empty,
+/// combination.
+fn combinations(matrix: &BTreeMap<String, Vec<String>>) -> Vec<Vec<(String, String)>> {
+    let mut combinations: Vec<Vec<(String, String)>> = vec![Vec::new()];
+    for (key, values) in matrix {
+        let placeholder = format!("{{{key}}}");
+        let mut next = Vec::with_capacity(combinations.len() * values.len());
+        for combination in &combinations {
+            for value in values {
+                let mut combination = combination.clone();
+                combination.push((placeholder.clone(), value.clone()));
+                next.push(combination);
+            }
+        }
+        combinations = next;
+    }
+    combinations
+}
+
+/// Substitute one matrix combination for [`Runner::run_for`].
+fn expand_matrix(command: &[String], combination: &[(String, String)]) -> Vec<String> {
+    command
+        .iter()
+        .map(|arg| {
+            combination
+                .iter()
+                .fold(arg.clone(), |arg, (placeholder, value)| {
+                    arg.replace(placeholder, value)
+                })
+        })
+        .collect()
+}
+
+/// Substitute `{event}` for [`Runner::run_for`].
+fn expand_event(command: &[String], kinds: &[ChangeKind]) -> Vec<String> {
+    if !command.iter().any(|arg| arg.contains("{event}")) {
+        return command.to_vec();
+    }
+    let mut kinds = kinds.to_vec();
+    kinds.sort_unstable();
+    kinds.dedup();
+    let event = kinds
+        .iter()
+        .map(|kind| kind.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    command
+        .iter()
+        .map(|arg| arg.replace("{event}", &event))
+        .collect()
+}
+
+/// Build the platform shell invocation for one command line: `sh -c <line>`
+/// on Unix, `cmd /S /C "<line>"` on Windows.
+fn shell(line: &str) -> Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        // Rust quotes arguments by MSVC rules, which cmd.exe does not follow,
+        // so hand it the command line verbatim.
+        let mut command = Command::new("cmd");
+        command.raw_arg(cmd_argument(line));
+        command
+    }
+    #[cfg(not(windows))]
+    {
+        let mut command = Command::new("sh");
+        command.args(["-c", line]);
+        command
+    }
+}
+
+/// The argument string for `cmd`. With `/S`, cmd strips exactly the outer
+/// pair of quotes and runs what is left as typed, so quotes inside `line`
+/// survive — the same contract as `sh -c`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn cmd_argument(line: &str) -> String {
+    format!("/S /C \"{line}\"")
+}
+
 /// Decide whether the user's command needs a shell to run as intended.
 ///
 /// Rule of thumb: if the user split their command into multiple arguments
@@ -110,6 +784,21 @@ fn is_shell_metachar(c: char) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::sink::ConsoleSink;
+    use std::sync::{Arc, Mutex};
+
+    type Calls = Arc<Mutex<Vec<(String, Option<i32>)>>>;
+
+    struct Recorder(Calls);
+
+    impl FailureHook for Recorder {
+        fn command_failed(&mut self, command: &[String], status: ExitStatus) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((command.join(" "), status.code()));
+        }
+    }
 
     #[test]
     fn spawns_and_waits_for_simple_command() {
@@ -118,7 +807,9 @@ mod tests {
             /* restart */ false,
             /* clear */ false,
         );
-        runner.run().expect("true should always succeed");
+        runner
+            .run(&ConsoleSink::default())
+            .expect("true should always succeed");
     }
 
     #[test]
@@ -129,14 +820,35 @@ mod tests {
             false,
         );
         runner
-            .run()
+            .run(&ConsoleSink::default())
             .expect("runner should not propagate exit codes");
     }
 
+    #[test]
+    fn failure_hook_fires_only_on_failure() {
+        let calls = Calls::default();
+        let mut failing = Runner::new(
+            vec!["sh".to_string(), "-c".to_string(), "exit 5".to_string()],
+            false,
+            false,
+        )
+        .with_failure_hook(Box::new(Recorder(calls.clone())));
+        failing.run(&ConsoleSink::default()).unwrap();
+
+        let mut passing = Runner::new(vec!["true".to_string()], false, false)
+            .with_failure_hook(Box::new(Recorder(calls.clone())));
+        passing.run(&ConsoleSink::default()).unwrap();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![("sh -c exit 5".to_string(), Some(5))]
+        );
+    }
+
     #[test]
     fn restart_mode_holds_onto_the_child() {
         let mut runner = Runner::new(vec!["sleep".to_string(), "30".to_string()], true, false);
-        runner.run().expect("spawn sleep");
+        runner.run(&ConsoleSink::default()).expect("spawn sleep");
         assert!(
             runner.current.is_some(),
             "restart mode should keep a handle on the running child"
@@ -144,6 +856,433 @@ mod tests {
         // Dropping the runner kills the long-running child.
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn process_group_restart_kills_grandchildren_too() {
+        // Alive means listed by `ps` and not a zombie awaiting its reaper.
+        fn alive(pid: &str) -> bool {
+            let out = Command::new("ps")
+                .args(["-o", "stat=", "-p", pid])
+                .output()
+                .expect("run ps");
+            let stat = String::from_utf8_lossy(&out.stdout);
+            !stat.trim().is_empty() && !stat.trim_start().starts_with('Z')
+        }
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let pid_file = dir.path().join("grandchild.pid");
+        let script = format!("sleep 30 & echo $! > {}; wait", pid_file.display());
+        let mut runner = Runner::new(vec![script], true, false).with_process_group(true);
+        runner.run(&ConsoleSink::default()).unwrap();
+
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        let grandchild = loop {
+            match std::fs::read_to_string(&pid_file) {
+                Ok(pid) if pid.ends_with('\n') => break pid.trim().to_string(),
+                _ if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(20))
+                }
+                _ => panic!("the script never reported its child"),
+            }
+        };
+        let shell = runner.current.as_ref().unwrap().id().to_string();
+        assert!(alive(&shell) && alive(&grandchild));
+
+        runner.stop_current();
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while alive(&grandchild) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        assert!(!alive(&shell), "the shell should be gone");
+        assert!(!alive(&grandchild), "the shell's child should be gone too");
+    }
+
+    /// Wait for a script to write `path`, e.g. once its traps are set.
+    #[cfg(unix)]
+    fn wait_for_file(path: &std::path::Path) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !path.exists() {
+            assert!(
+                Instant::now() < deadline,
+                "{} never appeared",
+                path.display()
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_signal_lets_the_command_shut_down_cleanly() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let ready = dir.path().join("ready");
+        let done = dir.path().join("done");
+        let script = format!(
+            "trap 'echo clean > {done}; exit 0' TERM; touch {ready}; while :; do sleep 0.05; done",
+            done = done.display(),
+            ready = ready.display(),
+        );
+        let mut runner = Runner::new(vec![script], true, false)
+            .with_restart_signal(Some(RestartSignal::Term), Duration::from_secs(5));
+        runner.run(&ConsoleSink::default()).unwrap();
+        wait_for_file(&ready);
+
+        let started = Instant::now();
+        runner.stop_current();
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "a command that exits on SIGTERM should not wait out the grace period"
+        );
+        let written = std::fs::read_to_string(&done).unwrap_or_default();
+        assert_eq!(written.trim(), "clean", "the TERM trap should have run");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restart_signal_escalates_to_a_kill_after_the_grace_period() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let ready = dir.path().join("ready");
+        let script = format!(
+            "trap '' TERM; touch {}; while :; do sleep 0.05; done",
+            ready.display()
+        );
+        let grace = Duration::from_millis(300);
+        let mut runner = Runner::new(vec![script], true, false)
+            .with_restart_signal(Some(RestartSignal::Term), grace);
+        runner.run(&ConsoleSink::default()).unwrap();
+        wait_for_file(&ready);
+
+        let started = Instant::now();
+        runner.stop_current();
+        let took = started.elapsed();
+        assert!(
+            took >= grace,
+            "killed after {took:?}, before the grace period"
+        );
+        assert!(
+            took < Duration::from_secs(3),
+            "still waiting after {took:?}"
+        );
+        assert!(runner.current.is_none());
+    }
+
+    #[test]
+    fn finish_reports_the_last_exit_status() {
+        let mut runner = Runner::new(
+            vec!["sh".to_string(), "-c".to_string(), "exit 3".to_string()],
+            false,
+            false,
+        );
+        assert_eq!(runner.finish(), None, "nothing has run yet");
+        runner.run(&ConsoleSink::default()).unwrap();
+        assert_eq!(runner.finish().and_then(|s| s.code()), Some(3));
+    }
+
+    #[test]
+    fn run_to_end_returns_the_status_without_touching_the_sink() {
+        let mut runner = Runner::new(vec!["exit 5".to_string()], true, false);
+        let status = runner.run_to_end().unwrap();
+        assert_eq!(status.code(), Some(5));
+        assert!(runner.current.is_none(), "never held as a restart child");
+        assert_eq!(runner.finish(), Some(status));
+    }
+
+    #[derive(Default)]
+    struct OutputRecorder(Mutex<Vec<(Vec<u8>, Vec<u8>)>>);
+
+    impl EventSink for OutputRecorder {
+        fn on_change(&self, _event: &crate::sink::ChangeEvent) {}
+        fn on_run(&self, _command: &[String]) {}
+        fn on_exit(&self, _status: ExitStatus) {}
+        fn on_output(&self, _command: &[String], output: &std::process::Output) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((output.stdout.clone(), output.stderr.clone()));
+        }
+    }
+
+    #[test]
+    fn captured_output_goes_to_the_sink_once_the_run_ends() {
+        let sink = OutputRecorder::default();
+        let mut runner = Runner::new(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "echo out; echo err >&2; exit 3".to_string(),
+            ],
+            false,
+            false,
+        )
+        .with_capture(true);
+        runner.run(&sink).unwrap();
+        assert_eq!(runner.last_status().and_then(|s| s.code()), Some(3));
+        assert_eq!(
+            *sink.0.lock().unwrap(),
+            [(b"out\n".to_vec(), b"err\n".to_vec())]
+        );
+
+        let mut uncaptured = Runner::new(vec!["true".to_string()], false, false);
+        uncaptured.run(&sink).unwrap();
+        assert_eq!(
+            sink.0.lock().unwrap().len(),
+            1,
+            "only --capture reports output"
+        );
+    }
+
+    #[derive(Default)]
+    struct ExitRecorder(Mutex<Vec<Option<i32>>>);
+
+    impl EventSink for ExitRecorder {
+        fn on_change(&self, _event: &crate::sink::ChangeEvent) {}
+        fn on_run(&self, _command: &[String]) {}
+        fn on_exit(&self, status: ExitStatus) {
+            self.0.lock().unwrap().push(status.code());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parallel_jobs_run_side_by_side() {
+        let sink = ExitRecorder::default();
+        let sleep = vec!["sleep".to_string(), "0.5".to_string()];
+        let mut runner = Runner::new(sleep, false, false).with_parallel(Some(3));
+        let started = Instant::now();
+        for _ in 0..3 {
+            runner.run(&sink).unwrap();
+        }
+        assert_eq!(
+            runner.running_jobs(),
+            3,
+            "runs should not wait for each other"
+        );
+        runner.wait_for_jobs(&sink);
+        let took = started.elapsed();
+        assert!(
+            took < Duration::from_millis(1_200),
+            "three 0.5s jobs took {took:?}; run one after another they take 1.5s"
+        );
+        assert_eq!(*sink.0.lock().unwrap(), [Some(0); 3]);
+        assert_eq!(runner.running_jobs(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_run_past_the_parallel_limit_waits_for_a_slot() {
+        let sink = ExitRecorder::default();
+        let sleep = vec!["sleep".to_string(), "0.3".to_string()];
+        let mut runner = Runner::new(sleep, false, false).with_parallel(Some(2));
+        let started = Instant::now();
+        for _ in 0..3 {
+            runner.run(&sink).unwrap();
+        }
+        assert!(
+            started.elapsed() >= Duration::from_millis(300),
+            "the third run started before either of the first two finished"
+        );
+        assert!(
+            !sink.0.lock().unwrap().is_empty(),
+            "the job that freed the slot should have been reported"
+        );
+        runner.wait_for_jobs(&sink);
+        assert_eq!(sink.0.lock().unwrap().len(), 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_parallel_jobs_reach_the_hook_and_the_last_status() {
+        let calls: Calls = Arc::default();
+        let mut runner = Runner::new(vec!["exit 3".to_string()], false, false)
+            .with_parallel(Some(2))
+            .with_failure_hook(Box::new(Recorder(calls.clone())));
+        let sink = ExitRecorder::default();
+        runner.run(&sink).unwrap();
+        runner.run(&sink).unwrap();
+        runner.wait_for_jobs(&sink);
+        assert_eq!(runner.last_status().and_then(|s| s.code()), Some(3));
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_job_is_reaped_while_a_process_it_left_holds_its_output() {
+        let mut runner = Runner::new(vec!["sleep 5 & echo started".to_string()], false, false)
+            .with_parallel(Some(1));
+        let sink = ExitRecorder::default();
+        runner.run(&sink).unwrap();
+        let started = Instant::now();
+        runner.wait_for_jobs(&sink);
+        assert!(
+            started.elapsed() < Duration::from_secs(2),
+            "waited on the background sleep: {:?}",
+            started.elapsed()
+        );
+        assert_eq!(*sink.0.lock().unwrap(), [Some(0)]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn every_parallel_job_group_is_tracked_until_reaped() {
+        let mut runner = Runner::new(vec!["sleep 0.3".to_string()], false, false)
+            .with_parallel(Some(2))
+            .with_process_group(true);
+        let sink = ExitRecorder::default();
+        runner.run(&sink).unwrap();
+        runner.run(&sink).unwrap();
+        let leaders: Vec<u32> = runner.jobs.iter().map(|job| job.child.id()).collect();
+        assert_eq!(leaders.len(), 2);
+        assert!(leaders.iter().all(|leader| groups().contains(leader)));
+
+        runner.wait_for_jobs(&sink);
+        assert!(!leaders.iter().any(|leader| groups().contains(leader)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn matrix_runs_the_command_once_per_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("targets");
+        let command = vec![format!("echo {{target}} >> {}", log.display())];
+        let matrix = BTreeMap::from([(
+            "target".to_string(),
+            vec!["x86_64".to_string(), "aarch64".to_string()],
+        )]);
+        let sink = ExitRecorder::default();
+        let mut runner = Runner::new(command, false, false).with_matrix(&matrix);
+        runner.run(&sink).unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "x86_64\naarch64\n");
+        assert_eq!(*sink.0.lock().unwrap(), [Some(0); 2]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn each_kind_runs_its_own_command_or_the_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let echo = |word: &str| vec![format!("echo {word} >> {}", log.display())];
+        let mut runner = Runner::new(echo("default:{event}"), false, false)
+            .with_kind_command(ChangeKind::Create, Some(echo("upload")))
+            .with_kind_command(ChangeKind::Remove, Some(echo("delete-remote")));
+        let sink = ConsoleSink::default();
+
+        let mut ran = |kinds: &[ChangeKind]| {
+            std::fs::write(&log, "").unwrap();
+            runner.run_for(&sink, kinds).unwrap();
+            std::fs::read_to_string(&log).unwrap()
+        };
+        assert_eq!(ran(&[ChangeKind::Create]), "upload\n");
+        assert_eq!(ran(&[ChangeKind::Modify]), "default:modify\n");
+        assert_eq!(ran(&[ChangeKind::Remove]), "delete-remote\n");
+        assert_eq!(
+            ran(&[ChangeKind::Remove, ChangeKind::Modify, ChangeKind::Create]),
+            "upload\ndefault:modify\ndelete-remote\n"
+        );
+        assert_eq!(ran(&[]), "default:\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scripts_replace_the_command_for_the_changes_they_claim() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("log");
+        let script = dir.path().join("build script.sh");
+        std::fs::write(
+            &script,
+            format!("#!/bin/sh\necho script >> '{}'\n", log.display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let mut runner = Runner::new(
+            vec![format!("echo default:{{event}} >> {}", log.display())],
+            false,
+            false,
+        );
+        let sink = ConsoleSink::default();
+
+        let scripts = [(script, vec![ChangeKind::Create])];
+        let mut ran = |rest: &[ChangeKind]| {
+            std::fs::write(&log, "").unwrap();
+            runner.run_for_scripts(&sink, &scripts, rest).unwrap();
+            std::fs::read_to_string(&log).unwrap()
+        };
+        assert_eq!(
+            ran(&[]),
+            "script\n",
+            "a fully claimed batch runs only scripts"
+        );
+        assert_eq!(ran(&[ChangeKind::Modify]), "script\ndefault:modify\n");
+    }
+
+    #[test]
+    fn restart_mode_runs_the_default_command_for_a_mixed_batch() {
+        let runner = Runner::new(argv(&["serve"]), true, false)
+            .with_kind_command(ChangeKind::Create, Some(argv(&["reload"])));
+        let kinds = [ChangeKind::Create, ChangeKind::Modify];
+        assert_eq!(
+            runner.commands_for(&kinds[..1]),
+            vec![(argv(&["reload"]), vec![ChangeKind::Create])]
+        );
+        assert_eq!(
+            runner.commands_for(&kinds),
+            vec![(argv(&["serve"]), kinds.to_vec())]
+        );
+    }
+
+    #[test]
+    fn clear_modes_follow_the_previous_outcome() {
+        let sink = ConsoleSink::default();
+        for (mode, after_failure, after_success) in [
+            (ClearMode::Always, true, true),
+            (ClearMode::OnSuccess, false, true),
+            (ClearMode::Never, false, false),
+        ] {
+            let mut failing =
+                Runner::new(vec!["exit 1".to_string()], false, false).with_clear_mode(mode);
+            assert_eq!(failing.should_clear(), mode != ClearMode::Never);
+            failing.run(&sink).unwrap();
+            assert_eq!(
+                failing.should_clear(),
+                after_failure,
+                "{mode:?} after a failure"
+            );
+
+            let mut passing =
+                Runner::new(vec!["true".to_string()], false, false).with_clear_mode(mode);
+            passing.run(&sink).unwrap();
+            assert_eq!(
+                passing.should_clear(),
+                after_success,
+                "{mode:?} after a success"
+            );
+        }
+    }
+
+    #[test]
+    fn finish_stops_a_running_restart_mode_child() {
+        let mut runner = Runner::new(vec!["sleep".to_string(), "30".to_string()], true, false);
+        runner.run(&ConsoleSink::default()).unwrap();
+        let asked = Instant::now();
+        assert_eq!(runner.finish(), None);
+        assert!(asked.elapsed() < Duration::from_secs(5));
+        assert!(runner.current.is_none());
+    }
+
+    #[test]
+    fn finish_reports_a_restart_mode_child_that_already_exited() {
+        let mut runner = Runner::new(
+            vec!["sh".to_string(), "-c".to_string(), "exit 4".to_string()],
+            true,
+            false,
+        );
+        runner.run(&ConsoleSink::default()).unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(runner.finish().and_then(|s| s.code()), Some(4));
+        assert!(runner.current.is_none());
+    }
+
     #[test]
     fn multi_token_commands_skip_the_shell() {
         let cmd = vec!["cargo".to_string(), "test".to_string()];
@@ -174,6 +1313,113 @@ mod tests {
         assert!(needs_shell(&cmd));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn arguments_with_spaces_and_metacharacters_arrive_intact() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        let tricky = r#"it's "a b" & $HOME; *|<x>"#;
+        let mut runner = Runner::new(
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                r#"printf %s "$1" > "$2""#.to_string(),
+                "sh".to_string(),
+                tricky.to_string(),
+                out.display().to_string(),
+            ],
+            false,
+            false,
+        );
+        runner.run(&ConsoleSink::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), tricky);
+    }
+
+    fn argv(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn event_placeholder_expands_to_each_kind() {
+        let command = argv(&["./on-change.sh", "--kind={event}"]);
+        for (kind, word) in [
+            (ChangeKind::Create, "create"),
+            (ChangeKind::Modify, "modify"),
+            (ChangeKind::Remove, "remove"),
+        ] {
+            assert_eq!(
+                expand_event(&command, &[kind]),
+                argv(&["./on-change.sh", &format!("--kind={word}")])
+            );
+        }
+    }
+
+    #[test]
+    fn a_mixed_batch_lists_each_kind_once_in_a_fixed_order() {
+        let kinds = [
+            ChangeKind::Remove,
+            ChangeKind::Modify,
+            ChangeKind::Create,
+            ChangeKind::Modify,
+        ];
+        assert_eq!(
+            expand_event(&argv(&["echo", "{event}"]), &kinds),
+            argv(&["echo", "create,modify,remove"])
+        );
+    }
+
+    #[test]
+    fn event_placeholder_is_empty_without_a_change() {
+        assert_eq!(
+            expand_event(&argv(&["echo", "[{event}]"]), &[]),
+            argv(&["echo", "[]"])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn raw_command_hands_the_joined_line_to_the_shell() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out");
+        let cmd = vec![
+            "printf".to_string(),
+            "x".to_string(),
+            ">".to_string(),
+            out.display().to_string(),
+        ];
+
+        let mut plain = Runner::new(cmd.clone(), false, false);
+        plain.run(&ConsoleSink::default()).unwrap();
+        assert!(
+            !out.exists(),
+            "without --raw-command `>` is just an argument"
+        );
+
+        let mut raw = Runner::new(cmd, false, false).with_raw_command(true);
+        raw.run(&ConsoleSink::default()).unwrap();
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "x");
+    }
+
+    #[test]
+    fn cmd_argument_wraps_the_line_once_and_keeps_inner_quotes() {
+        assert_eq!(cmd_argument("echo hi"), r#"/S /C "echo hi""#);
+        assert_eq!(
+            cmd_argument(r#"git commit -m "a b" && echo "done""#),
+            r#"/S /C "git commit -m "a b" && echo "done"""#
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn cmd_runs_a_line_with_quoted_arguments() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let out = dir.path().join("out.txt");
+        let line = format!(r#"echo "a b"> "{}""#, out.display());
+        let status = shell(&line).status().unwrap();
+        assert!(status.success());
+        assert_eq!(std::fs::read_to_string(&out).unwrap().trim(), r#""a b""#);
+    }
+
     #[test]
     fn direct_exec_preserves_quoted_arguments() {
         // The shell path would join with spaces and lose the original
@@ -183,6 +1429,8 @@ mod tests {
             false,
             false,
         );
-        runner.run().expect("multi-token command should run");
+        runner
+            .run(&ConsoleSink::default())
+            .expect("multi-token command should run");
     }
 }
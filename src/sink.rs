@@ -0,0 +1,784 @@
+//! Where the watch loop reports what it is doing.
+//!
+//! [`run`](crate::run) and the [`Runner`](crate::Runner) never print change
+//! or exit notices themselves; they call an [`EventSink`]. The CLI uses
+//! [`ConsoleSink`], and embedders can supply their own to log, count, or
+//! forward events elsewhere.
+
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Output};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::style::{strip_ansi, Colorize, CHANGE, RULE, TIMES};
+
+/// A file change that passed the filters and is about to trigger a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ChangeEvent {
+    pub path: PathBuf,
+    /// The watch root the change was seen under, if any.
+    pub root: Option<PathBuf>,
+    pub kind: ChangeKind,
+}
+
+impl ChangeEvent {
+    /// A [`ChangeKind::Modify`] of `path`, seen under no particular root.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            root: None,
+            kind: ChangeKind::Modify,
+        }
+    }
+
+    pub fn with_root(mut self, root: Option<PathBuf>) -> Self {
+        self.root = root;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: ChangeKind) -> Self {
+        self.kind = kind;
+        self
+    }
+}
+
+/// What happened to a changed path, as the `{event}` placeholder spells it.
+///
+/// With debouncing on, notify only reports that a path changed, so the kind
+/// is read off the file afterwards: gone is a removal, and a file whose
+/// birth time is within the debounce window of its last write is new.
+/// Filesystems without birth times report every surviving file as modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Remove,
+}
+
+impl ChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Modify => "modify",
+            Self::Remove => "remove",
+        }
+    }
+
+    /// What two changes to one path add up to when a single run covers
+    /// both, `self` being the earlier: the later kind, except that writing
+    /// to a file created since the last run leaves it created. A file
+    /// created and then written in quick succession is reported once, as
+    /// [`Create`](Self::Create).
+    pub fn then(self, later: ChangeKind) -> ChangeKind {
+        match (self, later) {
+            (Self::Create, Self::Modify) => Self::Create,
+            _ => later,
+        }
+    }
+}
+
+impl Display for ChangeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How [`ConsoleSink`] renders a changed path (`--path-display`).
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum PathDisplay {
+    /// Just the file name: `lib.rs`.
+    Basename,
+    /// Relative to the watch root it was seen under: `src/lib.rs`.
+    #[default]
+    Relative,
+    /// The absolute path as reported by the OS.
+    Full,
+}
+
+impl PathDisplay {
+    pub fn render(self, path: &Path, root: Option<&Path>) -> String {
+        let shown = match self {
+            Self::Basename => path.file_name().map_or(path, Path::new),
+            Self::Relative => match root.and_then(|r| path.strip_prefix(r).ok()) {
+                Some(rel) if !rel.as_os_str().is_empty() => rel,
+                _ => return display_path(path),
+            },
+            Self::Full => path,
+        };
+        flag_lossy(shown)
+    }
+}
+
+/// Receives the side effects of a watch session, in order: a change (or
+/// several, when they were coalesced), then the run they caused, then — for
+/// one-shot runs — what it printed if `--capture` is on, and how the command
+/// exited.
+pub trait EventSink {
+    fn on_change(&self, event: &ChangeEvent);
+    fn on_run(&self, command: &[String]);
+    fn on_exit(&self, status: ExitStatus);
+
+    /// The output of a captured run. By default it is passed through as is,
+    /// stdout to stdout and stderr to stderr.
+    fn on_output(&self, _command: &[String], output: &Output) {
+        write_output(output);
+    }
+}
+
+fn write_output(output: &Output) {
+    let _ = std::io::stdout().write_all(&output.stdout);
+    let _ = std::io::stdout().flush();
+    let _ = std::io::stderr().write_all(&output.stderr);
+}
+
+/// What goes in front of every line Flash prints: `[label]` for `--label`,
+/// then the local `HH:MM:SS` for `--timestamps`. Empty by default. Lines
+/// printed through it are also copied to the `--log-file`, if any.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinePrefix {
+    label: Option<String>,
+    timestamps: bool,
+    log: Option<LogFile>,
+    printed: Printed,
+}
+
+/// How many lines clones of one [`LinePrefix`] have printed, so
+/// [`ConsoleSink`] can tell whether its last line is still the last one.
+#[derive(Debug, Clone, Default)]
+struct Printed(Arc<AtomicU64>);
+
+impl PartialEq for Printed {
+    /// Counters aren't part of what a prefix looks like.
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Printed {}
+
+impl LinePrefix {
+    pub fn new(label: Option<String>, timestamps: bool) -> Self {
+        Self {
+            label,
+            timestamps,
+            log: None,
+            printed: Printed::default(),
+        }
+    }
+
+    pub fn with_log(mut self, log: LogFile) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Prefix every line of `text`.
+    pub fn apply(&self, text: impl Display) -> String {
+        let text = text.to_string();
+        if self.label.is_none() && !self.timestamps {
+            return text;
+        }
+        let mut head = String::new();
+        if let Some(label) = &self.label {
+            head.push_str(&format!("[{label}] "));
+        }
+        if self.timestamps {
+            head.push_str(&chrono::Local::now().format("%H:%M:%S ").to_string());
+        }
+        text.lines()
+            .map(|line| format!("{head}{line}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub(crate) fn println(&self, text: impl Display) {
+        let line = self.apply(text);
+        println!("{line}");
+        self.log(&line);
+        self.printed.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn eprintln(&self, text: impl Display) {
+        let line = self.apply(text);
+        eprintln!("{line}");
+        self.log(&line);
+        self.printed.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Replace the terminal's previous line with `text`, leaving the log
+    /// file alone.
+    fn reprint(&self, text: impl Display) {
+        println!("\x1b[1A\r\x1b[2K{}", self.apply(text));
+    }
+
+    /// Lines printed so far through this prefix or any clone of it.
+    fn printed(&self) -> u64 {
+        self.printed.0.load(Ordering::Relaxed)
+    }
+
+    /// Copy `text` to the log file alone, e.g. output already on the terminal.
+    pub(crate) fn log(&self, text: &str) {
+        if let Some(log) = &self.log {
+            log.write(text);
+        }
+    }
+}
+
+/// `--log-file`: a copy of Flash's output with the colors stripped. Clones
+/// share the one open file. A failed write, such as on a full disk, turns
+/// logging off with a single warning instead of ending the session.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    path: PathBuf,
+    file: Arc<Mutex<Option<File>>>,
+}
+
+impl LogFile {
+    /// Open `path` for appending, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Arc::new(Mutex::new(Some(file))),
+        })
+    }
+
+    fn write(&self, text: &str) {
+        let mut slot = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(file) = slot.as_mut() else {
+            return;
+        };
+        let mut text = strip_ansi(text);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        if let Err(err) = file.write_all(text.as_bytes()) {
+            *slot = None;
+            eprintln!(
+                "flash-watcher: writing to log file '{}' failed: {err}; no longer logging to it",
+                self.path.display()
+            );
+        }
+    }
+}
+
+impl PartialEq for LogFile {
+    /// The same open file, not merely the same path.
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file)
+    }
+}
+
+impl Eq for LogFile {}
+
+/// The terminal output of the CLI: a `↻ path` line per change, and a note on
+/// stderr when the command fails.
+///
+/// Saving the same file over and over while the same command keeps passing
+/// prints its line once. On a terminal, as long as that line is still the
+/// last one (nothing, not even the command, has printed since), each repeat
+/// rewrites it as `↻ path (×N)`. Otherwise the repeats are held and folded
+/// into a single `↻ path (×N)` line when the streak ends: on a different
+/// change, a failure, another command, a pause of more than ten seconds, or
+/// when the sink is dropped.
+#[derive(Debug)]
+pub struct ConsoleSink {
+    log_changes: bool,
+    path_display: PathDisplay,
+    prefix: LinePrefix,
+    /// Whether repeats may rewrite the change line in place.
+    in_place: bool,
+    repeats: Mutex<Repeats>,
+}
+
+/// How soon an identical change has to follow the previous one to add to its
+/// count.
+const REPEAT_WINDOW: Duration = Duration::from_secs(10);
+
+impl ConsoleSink {
+    /// `log_changes: false` keeps the per-change lines out of the way of
+    /// `--fast`, `--stats`, and `--summary-interval` output.
+    pub fn new(log_changes: bool) -> Self {
+        Self {
+            log_changes,
+            path_display: PathDisplay::default(),
+            prefix: LinePrefix::default(),
+            in_place: io::stdout().is_terminal(),
+            repeats: Mutex::default(),
+        }
+    }
+
+    pub fn with_path_display(mut self, path_display: PathDisplay) -> Self {
+        self.path_display = path_display;
+        self
+    }
+
+    pub fn with_prefix(mut self, prefix: LinePrefix) -> Self {
+        self.prefix = prefix;
+        self
+    }
+}
+
+impl ConsoleSink {
+    fn change_line(path: &str, times: u32) -> String {
+        let times = if times > 1 {
+            format!(" ({TIMES}{times})")
+        } else {
+            String::new()
+        };
+        format!(
+            "{}  {}{}",
+            CHANGE.bright_blue(),
+            path.bright_white(),
+            times.bright_black()
+        )
+    }
+
+    /// Print the line that closes a streak, if it needs one. A count the
+    /// terminal already shows goes to the log file alone.
+    fn end_streak(&self, ended: Option<Streak>) {
+        let Some(streak) = ended else {
+            return;
+        };
+        let line = Self::change_line(&streak.path, streak.times);
+        if streak.shown {
+            self.prefix.log(&self.prefix.apply(line));
+        } else {
+            self.prefix.println(line);
+        }
+    }
+
+    fn repeats(&self) -> std::sync::MutexGuard<'_, Repeats> {
+        self.repeats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Clone for ConsoleSink {
+    /// The copy starts a count of its own.
+    fn clone(&self) -> Self {
+        Self {
+            log_changes: self.log_changes,
+            path_display: self.path_display,
+            prefix: self.prefix.clone(),
+            in_place: self.in_place,
+            repeats: Mutex::default(),
+        }
+    }
+}
+
+impl Drop for ConsoleSink {
+    fn drop(&mut self) {
+        let ended = self.repeats().end();
+        self.end_streak(ended);
+    }
+}
+
+impl Default for ConsoleSink {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl EventSink for ConsoleSink {
+    fn on_change(&self, event: &ChangeEvent) {
+        if !self.log_changes {
+            return;
+        }
+        let path = self.path_display.render(&event.path, event.root.as_deref());
+        let mut repeats = self.repeats();
+        match repeats.change(&path, Instant::now(), self.prefix.printed()) {
+            Change::New(ended) => {
+                drop(repeats);
+                self.end_streak(ended);
+                self.prefix.println(Self::change_line(&path, 1));
+                self.repeats().printed(self.prefix.printed());
+            }
+            Change::Repeat { times, last_line } if self.in_place && last_line => {
+                self.prefix.reprint(Self::change_line(&path, times));
+                repeats.shown(times);
+            }
+            Change::Repeat { .. } => {}
+        }
+    }
+
+    fn on_run(&self, command: &[String]) {
+        let ended = self.repeats().run(command);
+        self.end_streak(ended);
+    }
+
+    /// Frames the output between rules naming the command, so it stands
+    /// apart from Flash's own lines. A run that printed nothing shows nothing.
+    fn on_output(&self, command: &[String], output: &Output) {
+        self.repeats().captured();
+        if output.stdout.is_empty() && output.stderr.is_empty() {
+            return;
+        }
+        let rule = RULE.repeat(2);
+        self.prefix
+            .println(format!("{rule} output of `{}` {rule}", command.join(" ")).bright_black());
+        write_output(output);
+        for stream in [&output.stdout, &output.stderr] {
+            if !stream.is_empty() {
+                self.prefix.log(&String::from_utf8_lossy(stream));
+            }
+        }
+        self.prefix
+            .println(format!("{rule} end of output {rule}").bright_black());
+    }
+
+    fn on_exit(&self, status: ExitStatus) {
+        if !status.success() {
+            let ended = self.repeats().end();
+            self.end_streak(ended);
+            self.prefix
+                .eprintln(format_args!("flash-watcher: command exited with {status}"));
+        }
+    }
+}
+
+/// The bookkeeping behind [`ConsoleSink`]'s folding of repeated changes.
+///
+/// A streak is one path changing again and again, each time within
+/// [`REPEAT_WINDOW`] of the last, with every run in between passing and for
+/// the same command.
+#[derive(Debug, Default)]
+struct Repeats {
+    path: Option<String>,
+    times: u32,
+    /// The count the terminal shows for the streak.
+    shown: u32,
+    last: Option<Instant>,
+    command: Option<Vec<String>>,
+    /// How many lines Flash had printed right after the streak's line, to
+    /// tell whether anything came after it.
+    printed: u64,
+    /// Whether a run since then may have written to the terminal directly.
+    /// Only captured output is seen by the sink.
+    uncaptured_run: bool,
+}
+
+/// A streak that ended with repeats to report.
+#[derive(Debug, PartialEq, Eq)]
+struct Streak {
+    path: String,
+    times: u32,
+    /// Whether the terminal already shows `times`.
+    shown: bool,
+}
+
+/// What [`Repeats::change`] makes of a change.
+#[derive(Debug, PartialEq, Eq)]
+enum Change {
+    /// The first of a streak, whose line prints now, after the line closing
+    /// the streak it ended, if any.
+    New(Option<Streak>),
+    /// Seen `times` in a row. `last_line` if nothing has printed since the
+    /// streak's line, so it may be rewritten in place.
+    Repeat { times: u32, last_line: bool },
+}
+
+impl Repeats {
+    /// Note a change, with `printed` lines printed so far.
+    fn change(&mut self, path: &str, now: Instant, printed: u64) -> Change {
+        let recent = self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) <= REPEAT_WINDOW);
+        self.last = Some(now);
+        if recent && self.path.as_deref() == Some(path) {
+            self.times += 1;
+            return Change::Repeat {
+                times: self.times,
+                last_line: !self.uncaptured_run && printed == self.printed,
+            };
+        }
+        let ended = self.end();
+        self.path = Some(path.to_string());
+        self.times = 1;
+        self.shown = 1;
+        Change::New(ended)
+    }
+
+    /// The streak's line was printed, leaving `printed` lines in all.
+    fn printed(&mut self, printed: u64) {
+        self.printed = printed;
+        self.uncaptured_run = false;
+    }
+
+    /// The terminal's line was rewritten to show `times`.
+    fn shown(&mut self, times: u32) {
+        self.shown = times;
+    }
+
+    /// A run started. One for another command ends the streak.
+    fn run(&mut self, command: &[String]) -> Option<Streak> {
+        self.uncaptured_run = true;
+        if self.command.as_deref() == Some(command) {
+            return None;
+        }
+        let had_command = self.command.replace(command.to_vec()).is_some();
+        if had_command {
+            self.end()
+        } else {
+            None
+        }
+    }
+
+    /// The run's output came through the sink, not straight to the terminal.
+    fn captured(&mut self) {
+        self.uncaptured_run = false;
+    }
+
+    /// End the streak, returning it if it has repeats to report.
+    fn end(&mut self) -> Option<Streak> {
+        let path = self.path.take()?;
+        (self.times > 1).then_some(Streak {
+            path,
+            times: self.times,
+            shown: self.shown == self.times,
+        })
+    }
+}
+
+/// Render a path for the console. Names that are not valid UTF-8 are shown
+/// lossily and flagged, so two such files never look silently identical.
+pub(crate) fn display_path(path: &Path) -> String {
+    let shown = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok())
+        .unwrap_or(path);
+    flag_lossy(shown)
+}
+
+fn flag_lossy(shown: &Path) -> String {
+    if shown.to_str().is_some() {
+        shown.display().to_string()
+    } else {
+        format!("{} (non-UTF-8 name)", shown.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_changes_to_one_path_coalesce_to_a_single_kind() {
+        use ChangeKind::{Create, Modify, Remove};
+        for (earlier, later, both) in [
+            (Create, Modify, Create),
+            (Create, Create, Create),
+            (Create, Remove, Remove),
+            (Modify, Create, Create),
+            (Modify, Modify, Modify),
+            (Modify, Remove, Remove),
+            (Remove, Create, Create),
+            (Remove, Modify, Modify),
+            (Remove, Remove, Remove),
+        ] {
+            assert_eq!(earlier.then(later), both, "{earlier} then {later}");
+        }
+    }
+
+    #[test]
+    fn line_prefix_labels_and_timestamps_every_line() {
+        assert_eq!(LinePrefix::default().apply("a\nb"), "a\nb");
+        assert_eq!(
+            LinePrefix::new(Some("api".into()), false).apply("a\nb"),
+            "[api] a\n[api] b"
+        );
+
+        let stamped = LinePrefix::new(Some("api".into()), true).apply("ready");
+        let (label, rest) = stamped.split_at("[api] ".len());
+        assert_eq!(label, "[api] ");
+        let (time, text) = rest.split_once(' ').unwrap();
+        assert_eq!(text, "ready");
+        assert_eq!(time.len(), "HH:MM:SS".len());
+        assert!(time
+            .split(':')
+            .all(|part| part.len() == 2 && part.bytes().all(|b| b.is_ascii_digit())));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn log_file_stops_after_a_failed_write() {
+        // Every write to /dev/full fails as a full disk would.
+        let log = LogFile::open(Path::new("/dev/full")).unwrap();
+        let prefix = LinePrefix::default().with_log(log.clone());
+        prefix.log("first");
+        assert!(log.file.lock().unwrap().is_none());
+        prefix.log("second");
+    }
+
+    /// A console sink logging to a fresh file, with everything it logged.
+    fn logged_sink(in_place: bool) -> (ConsoleSink, impl Fn() -> Vec<String>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("flash.log");
+        let log = LogFile::open(&path).unwrap();
+        let mut sink = ConsoleSink::new(true).with_prefix(LinePrefix::default().with_log(log));
+        sink.in_place = in_place;
+        let lines = move || {
+            let _ = &dir;
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .lines()
+                .map(str::to_string)
+                .collect()
+        };
+        (sink, lines)
+    }
+
+    fn change_line(path: &str, times: u32) -> String {
+        strip_ansi(&ConsoleSink::change_line(path, times))
+    }
+
+    #[test]
+    fn identical_passing_runs_fold_into_one_counted_line() {
+        let command = vec!["cargo".to_string(), "check".to_string()];
+        let (sink, lines) = logged_sink(false);
+        for _ in 0..5 {
+            sink.on_change(&ChangeEvent::new("src/lib.rs"));
+            sink.on_run(&command);
+        }
+        assert_eq!(lines(), [change_line("src/lib.rs", 1)], "repeats are held");
+
+        sink.on_change(&ChangeEvent::new("src/main.rs"));
+        assert_eq!(
+            lines(),
+            [
+                change_line("src/lib.rs", 1),
+                change_line("src/lib.rs", 5),
+                change_line("src/main.rs", 1),
+            ],
+            "five saves take two lines"
+        );
+    }
+
+    #[test]
+    fn repeats_rewrite_the_last_line_on_a_terminal() {
+        let command = vec!["cargo".to_string(), "check".to_string()];
+        let silent = Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        let (sink, lines) = logged_sink(true);
+        for _ in 0..3 {
+            sink.on_change(&ChangeEvent::new("src/lib.rs"));
+            sink.on_run(&command);
+            sink.on_output(&command, &silent);
+        }
+        assert_eq!(sink.repeats().shown, 3, "the terminal line was rewritten");
+        drop(sink);
+        assert_eq!(
+            lines(),
+            [change_line("src/lib.rs", 1), change_line("src/lib.rs", 3)],
+            "the log gets the final count once"
+        );
+
+        // A run writing straight to the terminal leaves the line alone.
+        let (sink, lines) = logged_sink(true);
+        for _ in 0..3 {
+            sink.on_change(&ChangeEvent::new("src/lib.rs"));
+            sink.on_run(&command);
+        }
+        assert_eq!(sink.repeats().shown, 1);
+        drop(sink);
+        assert_eq!(
+            lines(),
+            [change_line("src/lib.rs", 1), change_line("src/lib.rs", 3)]
+        );
+    }
+
+    #[test]
+    fn repeats_start_over_after_a_failure_a_pause_or_another_command() {
+        let check = vec!["cargo".to_string(), "check".to_string()];
+        let test = vec!["cargo".to_string(), "test".to_string()];
+        let start = Instant::now();
+        let mut repeats = Repeats::default();
+        let repeat = |times| Change::Repeat {
+            times,
+            last_line: false,
+        };
+
+        assert_eq!(repeats.change("lib.rs", start, 0), Change::New(None));
+        repeats.run(&check);
+        assert_eq!(repeats.change("lib.rs", start, 0), repeat(2));
+        repeats.run(&check);
+        assert_eq!(
+            repeats.end(),
+            Some(Streak {
+                path: "lib.rs".into(),
+                times: 2,
+                shown: false
+            }),
+            "a failure ends the streak"
+        );
+        assert_eq!(repeats.change("lib.rs", start, 0), Change::New(None));
+        repeats.run(&check);
+
+        let later = start + REPEAT_WINDOW + Duration::from_secs(1);
+        assert_eq!(
+            repeats.change("lib.rs", later, 0),
+            Change::New(None),
+            "after a pause"
+        );
+        repeats.run(&check);
+        assert_eq!(repeats.change("lib.rs", later, 0), repeat(2));
+        assert!(
+            repeats.run(&test).is_some(),
+            "a different command ends the streak"
+        );
+        assert_eq!(repeats.change("lib.rs", later, 0), Change::New(None));
+    }
+
+    #[test]
+    fn path_display_modes_over_a_nested_path() {
+        let root = Path::new("/work/project");
+        let path = root.join("src").join("net").join("mod.rs");
+        let rendered = |mode: PathDisplay| mode.render(&path, Some(root));
+
+        assert_eq!(rendered(PathDisplay::Basename), "mod.rs");
+        assert_eq!(
+            rendered(PathDisplay::Relative),
+            Path::new("src")
+                .join("net")
+                .join("mod.rs")
+                .display()
+                .to_string()
+        );
+        assert_eq!(rendered(PathDisplay::Full), path.display().to_string());
+    }
+
+    #[test]
+    fn relative_display_without_a_root_falls_back_to_the_working_directory() {
+        let cwd = std::env::current_dir().unwrap();
+        let path = cwd.join("nested").join("file.txt");
+        assert_eq!(
+            PathDisplay::Relative.render(&path, None),
+            Path::new("nested").join("file.txt").display().to_string()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn display_path_flags_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let plain = display_path(Path::new("/nowhere/plain.rs"));
+        assert_eq!(plain, "/nowhere/plain.rs");
+        let lossy = display_path(Path::new(OsStr::from_bytes(b"/nowhere/caf\xe9.rs")));
+        assert_eq!(lossy, "/nowhere/caf\u{fffd}.rs (non-UTF-8 name)");
+    }
+}
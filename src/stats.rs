@@ -1,17 +1,43 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 
-use colored::Colorize;
-use sysinfo::{Pid, System};
+use sysinfo::{Pid, ProcessRefreshKind, System, MINIMUM_CPU_UPDATE_INTERVAL};
+
+use crate::filter::RejectReason;
+use crate::style::{Colorize, RULE, SEP};
+
+/// Width of the sliding window behind the events-per-second rate, in seconds.
+const RATE_WINDOW_SECS: u64 = 60;
+
+/// How many rejection reasons the stats block names.
+const TOP_REASONS: usize = 3;
+
+/// The rejection reason for paths folded into another in the same batch.
+const COALESCED: &str = "debounce";
 
 /// Lightweight rolling counters and resource samples for the `--stats` flag.
 pub struct Stats {
     started_at: Instant,
     changes: u64,
+    rejected: u64,
+    /// Rejections by [`RejectReason::label`], plus [`COALESCED`].
+    reasons: BTreeMap<&'static str, u64>,
     events: u64,
+    runs: u64,
+    failures: u64,
+    /// Changes lost because the event buffer was full (`--buffer-size`).
+    dropped: u64,
+    rate: EventRate,
+    window: Window,
+    /// Whether the window collects changed files, which only
+    /// [`take_summary`](Self::take_summary) ever drains.
+    summaries: bool,
     memory_bytes: u64,
     cpu_percent: f32,
     system: System,
     pid: Pid,
+    last_sample: Instant,
 }
 
 impl Default for Stats {
@@ -22,27 +48,117 @@ impl Default for Stats {
 
 impl Stats {
     pub fn new() -> Self {
+        let pid = Pid::from_u32(std::process::id());
+        let mut system = System::new();
+        // Take the baseline sample up front so the first `refresh` already
+        // has a previous CPU reading to diff against. `System::new()` starts
+        // empty; only our own PID is ever loaded into it.
+        system.refresh_process_specifics(pid, sample_kind());
+        let memory_bytes = system.process(pid).map_or(0, |p| p.memory());
         Self {
             started_at: Instant::now(),
             changes: 0,
+            rejected: 0,
+            reasons: BTreeMap::new(),
             events: 0,
-            memory_bytes: 0,
+            runs: 0,
+            failures: 0,
+            dropped: 0,
+            rate: EventRate::new(Instant::now()),
+            window: Window::default(),
+            summaries: false,
+            memory_bytes,
             cpu_percent: 0.0,
-            system: System::new(),
-            pid: Pid::from_u32(std::process::id()),
+            system,
+            pid,
+            last_sample: Instant::now(),
         }
     }
 
-    pub fn record_change(&mut self) {
+    /// Count distinct changed files for `--summary-interval`. Off by default,
+    /// so a session nobody takes summaries from doesn't remember every path.
+    pub fn with_summaries(mut self, summaries: bool) -> Self {
+        self.summaries = summaries;
+        self
+    }
+
+    pub fn record_change(&mut self, path: &Path) {
         self.changes += 1;
+        self.window.changes += 1;
+        if self.summaries {
+            self.window.files.insert(path.to_path_buf());
+        }
+    }
+
+    /// A change the filters turned away, and why.
+    pub fn record_rejected(&mut self, reason: &RejectReason) {
+        self.reject(reason.label(), 1);
+    }
+
+    /// Raw events that never became a change of their own: a second
+    /// spelling of the same file, or a directory reported with its child.
+    pub fn record_coalesced(&mut self, events: u64) {
+        if events > 0 {
+            self.reject(COALESCED, events);
+        }
+    }
+
+    fn reject(&mut self, reason: &'static str, n: u64) {
+        self.rejected += n;
+        *self.reasons.entry(reason).or_default() += n;
+    }
+
+    pub fn record_run(&mut self) {
+        self.runs += 1;
+        self.window.runs += 1;
+    }
+
+    /// A run that exited unsuccessfully, for the exit summary.
+    pub fn record_failure(&mut self) {
+        self.failures += 1;
+    }
+
+    /// Changes the full event buffer had no room for. Any at all mean the
+    /// command can't keep up with the changes.
+    pub fn record_dropped(&mut self, changes: u64) {
+        self.dropped += changes;
+    }
+
+    /// Activity since the previous call (or since startup), for the
+    /// `--summary-interval` report. Resets the window.
+    pub fn take_summary(&mut self) -> Summary {
+        let window = std::mem::take(&mut self.window);
+        Summary {
+            changes: window.changes,
+            files: window.files.len(),
+            runs: window.runs,
+        }
     }
 
     pub fn record_event(&mut self) {
+        self.record_event_at(Instant::now());
+    }
+
+    fn record_event_at(&mut self, now: Instant) {
         self.events += 1;
+        self.rate.record(now);
     }
 
+    /// Sample memory and CPU usage for this process.
+    ///
+    /// sysinfo reports CPU usage as the delta between two refreshes of the
+    /// same process, so the collector keeps its `System` across calls and
+    /// each refresh diffs against the previous one (the first against the
+    /// baseline taken in [`Stats::new`]). Refreshes closer together than
+    /// [`MINIMUM_CPU_UPDATE_INTERVAL`] are skipped: the delta would be noise,
+    /// and any refresh — even a memory-only one — moves the CPU baseline.
     pub fn refresh(&mut self) {
-        self.system.refresh_process(self.pid);
+        if self.last_sample.elapsed() < MINIMUM_CPU_UPDATE_INTERVAL {
+            return;
+        }
+        self.system
+            .refresh_process_specifics(self.pid, sample_kind());
+        self.last_sample = Instant::now();
         if let Some(proc) = self.system.process(self.pid) {
             self.memory_bytes = proc.memory();
             self.cpu_percent = proc.cpu_usage();
@@ -57,10 +173,40 @@ impl Stats {
         self.changes
     }
 
+    pub fn rejected(&self) -> u64 {
+        self.rejected
+    }
+
+    /// Rejections by reason, most frequent first.
+    pub fn rejections(&self) -> Vec<(&'static str, u64)> {
+        let mut reasons: Vec<(&'static str, u64)> =
+            self.reasons.iter().map(|(&r, &n)| (r, n)).collect();
+        reasons.sort_by_key(|&(_, n)| std::cmp::Reverse(n));
+        reasons
+    }
+
     pub fn events(&self) -> u64 {
         self.events
     }
 
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Average raw events per second over the last [`RATE_WINDOW_SECS`]
+    /// seconds (or since startup, if that is shorter).
+    pub fn events_per_second(&self) -> f64 {
+        self.rate.per_second(Instant::now())
+    }
+
     pub fn memory_bytes(&self) -> u64 {
         self.memory_bytes
     }
@@ -69,19 +215,161 @@ impl Stats {
         self.cpu_percent
     }
 
+    /// The `--count-only` tally: "12 accepted · 40 rejected".
+    pub fn render_counts(&self) -> String {
+        format!("{} accepted {SEP} {} rejected", self.changes, self.rejected)
+    }
+
+    /// The `--print-events-summary-on-exit` line: "12 changes · 5 runs ·
+    /// 1 failed · up 3m 2s", with "· 40 dropped" before the uptime if the
+    /// event buffer ever overflowed.
+    pub fn render_session(&self) -> String {
+        let dropped = if self.dropped > 0 {
+            format!(" {SEP} {} dropped", self.dropped)
+        } else {
+            String::new()
+        };
+        format!(
+            "{} {SEP} {} {SEP} {} failed{dropped} {SEP} up {}",
+            plural(self.changes, "change"),
+            plural(self.runs, "run"),
+            self.failures,
+            format_duration(self.uptime()),
+        )
+    }
+
+    /// " (extension 30, ignore-pattern 8, debounce 2)", or nothing before
+    /// the first rejection.
+    fn render_top_reasons(&self) -> String {
+        let top: Vec<String> = self
+            .rejections()
+            .into_iter()
+            .take(TOP_REASONS)
+            .map(|(reason, n)| format!("{reason} {n}"))
+            .collect();
+        if top.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", top.join(", "))
+        }
+    }
+
     pub fn render(&self) -> String {
         format!(
-            "{header}\n  uptime    {uptime}\n  changes   {changes}\n  events    {events}\n  memory    {memory}\n  cpu       {cpu:.1} %",
-            header = "── flash · live stats ──".bright_cyan(),
+            "{header}\n  uptime    {uptime}\n  changes   {changes}\n  rejected  {rejected}{reasons}\n  events    {events}\n  dropped   {dropped}\n  rate      {rate:.1} ev/s\n  memory    {memory}\n  cpu       {cpu:.1} %",
+            header = format!("{rule} flash {SEP} live stats {rule}", rule = RULE.repeat(2)).bright_cyan(),
             uptime = format_duration(self.started_at.elapsed()),
             changes = self.changes,
+            rejected = self.rejected,
+            reasons = self.render_top_reasons(),
             events = self.events,
+            dropped = self.dropped,
+            rate = self.events_per_second(),
             memory = format_bytes(self.memory_bytes),
             cpu = self.cpu_percent,
         )
     }
 }
 
+/// Counters accumulated between two `--summary-interval` reports.
+#[derive(Default)]
+struct Window {
+    changes: u64,
+    runs: u64,
+    files: HashSet<PathBuf>,
+}
+
+/// Aggregate activity over one `--summary-interval` window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    pub changes: u64,
+    pub files: usize,
+    pub runs: u64,
+}
+
+impl Summary {
+    pub fn is_empty(&self) -> bool {
+        self.changes == 0 && self.runs == 0
+    }
+
+    /// One line, e.g. "47 changes across 12 files in the last 10s, ran
+    /// command 4 times".
+    pub fn render(&self, over: Duration) -> String {
+        format!(
+            "{} across {} in the last {}, ran command {}",
+            plural(self.changes, "change"),
+            plural(self.files as u64, "file"),
+            format_duration(over),
+            plural(self.runs, "time"),
+        )
+    }
+}
+
+fn plural(n: u64, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+/// Just the fields the stats block shows. The default `refresh_process` kind
+/// also reads disk I/O counters and the executable path, which we never use.
+fn sample_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::new().with_memory().with_cpu()
+}
+
+/// Per-second event counts over a fixed sliding window.
+///
+/// Memory is bounded by the window width: each slot holds the count for one
+/// second, and slots are recycled as the window slides forward rather than
+/// storing individual timestamps.
+struct EventRate {
+    origin: Instant,
+    buckets: [u64; RATE_WINDOW_SECS as usize],
+    /// Second (relative to `origin`) that the most recent slot belongs to.
+    head: u64,
+}
+
+impl EventRate {
+    fn new(origin: Instant) -> Self {
+        Self {
+            origin,
+            buckets: [0; RATE_WINDOW_SECS as usize],
+            head: 0,
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        let second = self.second_of(now);
+        if second > self.head {
+            // Zero every slot the window slid past since the last event.
+            let stale = (second - self.head).min(RATE_WINDOW_SECS);
+            for s in (second + 1 - stale)..=second {
+                self.buckets[slot(s)] = 0;
+            }
+            self.head = second;
+        }
+        self.buckets[slot(self.head)] += 1;
+    }
+
+    fn per_second(&self, now: Instant) -> f64 {
+        let second = self.second_of(now).max(self.head);
+        let oldest = (second + 1).saturating_sub(RATE_WINDOW_SECS);
+        let total: u64 = (oldest..=self.head).map(|s| self.buckets[slot(s)]).sum();
+        let span = (second + 1).min(RATE_WINDOW_SECS);
+        total as f64 / span as f64
+    }
+
+    fn second_of(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.origin).as_secs()
+    }
+}
+
+fn slot(second: u64) -> usize {
+    (second % RATE_WINDOW_SECS) as usize
+}
+
 pub fn format_duration(d: Duration) -> String {
     let secs = d.as_secs();
     let (h, m, s) = (secs / 3600, (secs / 60) % 60, secs % 60);
@@ -119,9 +407,171 @@ mod tests {
         let mut s = Stats::new();
         s.record_event();
         s.record_event();
-        s.record_change();
+        s.record_change(Path::new("a.rs"));
+        s.record_rejected(&RejectReason::Extension);
+        s.record_run();
         assert_eq!(s.events(), 2);
         assert_eq!(s.changes(), 1);
+        assert_eq!(s.rejected(), 1);
+        assert_eq!(s.runs(), 1);
+    }
+
+    #[test]
+    fn rejections_break_down_by_reason_most_frequent_first() {
+        let mut s = Stats::new();
+        for reason in [
+            RejectReason::Extension,
+            RejectReason::IgnorePattern("target/**".into()),
+            RejectReason::ExcludedExtension("md".into()),
+            RejectReason::IgnorePattern("*.bak".into()),
+            RejectReason::Extension,
+            RejectReason::NoIncludeMatch,
+        ] {
+            s.record_rejected(&reason);
+        }
+        s.record_coalesced(2);
+        s.record_coalesced(0);
+
+        assert_eq!(s.rejected(), 8);
+        assert_eq!(
+            s.rejections(),
+            [
+                ("extension", 3),
+                ("debounce", 2),
+                ("ignore-pattern", 2),
+                ("include-pattern", 1)
+            ]
+        );
+        assert!(
+            s.render()
+                .contains("rejected  8 (extension 3, debounce 2, ignore-pattern 2)"),
+            "only the top three are named:\n{}",
+            s.render()
+        );
+    }
+
+    #[test]
+    fn counts_render_accepted_and_rejected() {
+        let mut s = Stats::new();
+        s.record_change(Path::new("a.rs"));
+        s.record_change(Path::new("b.rs"));
+        for _ in 0..3 {
+            s.record_rejected(&RejectReason::Hidden);
+        }
+        assert_eq!(s.render_counts(), format!("2 accepted {SEP} 3 rejected"));
+    }
+
+    #[test]
+    fn session_line_counts_changes_runs_and_failures() {
+        let mut s = Stats::new();
+        s.record_change(Path::new("a.rs"));
+        s.record_run();
+        s.record_run();
+        s.record_failure();
+        let line = s.render_session();
+        assert!(
+            line.starts_with(&format!("1 change {SEP} 2 runs {SEP} 1 failed {SEP} up ")),
+            "{line}"
+        );
+
+        s.record_dropped(40);
+        let line = s.render_session();
+        assert!(
+            line.contains(&format!("1 failed {SEP} 40 dropped {SEP} up ")),
+            "{line}"
+        );
+        assert!(s.render().contains("dropped   40"), "{}", s.render());
+    }
+
+    #[test]
+    fn summary_reports_deltas_since_the_last_take() {
+        let mut s = Stats::new().with_summaries(true);
+        for _ in 0..3 {
+            s.record_change(Path::new("a.rs"));
+        }
+        s.record_change(Path::new("b.rs"));
+        s.record_run();
+        s.record_run();
+
+        let first = s.take_summary();
+        assert_eq!(
+            first,
+            Summary {
+                changes: 4,
+                files: 2,
+                runs: 2
+            }
+        );
+        assert_eq!(
+            first.render(Duration::from_secs(10)),
+            "4 changes across 2 files in the last 10s, ran command 2 times"
+        );
+
+        assert!(s.take_summary().is_empty(), "window resets after a take");
+        s.record_change(Path::new("a.rs"));
+        s.record_run();
+        assert_eq!(
+            s.take_summary().render(Duration::from_secs(5)),
+            "1 change across 1 file in the last 5s, ran command 1 time"
+        );
+        assert_eq!(s.changes(), 5, "cumulative counters are untouched");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_that_render_alike_count_as_distinct_files() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let a = Path::new(OsStr::from_bytes(b"caf\xe9.rs"));
+        let b = Path::new(OsStr::from_bytes(b"caf\xff.rs"));
+        assert_eq!(a.to_string_lossy(), b.to_string_lossy());
+
+        let mut s = Stats::new().with_summaries(true);
+        s.record_change(a);
+        s.record_change(b);
+        s.record_change(a);
+        assert_eq!(s.take_summary().files, 2);
+    }
+
+    #[test]
+    fn changed_files_are_only_kept_for_summaries() {
+        let mut s = Stats::new();
+        for i in 0..100 {
+            s.record_change(&PathBuf::from(format!("{i}.rs")));
+        }
+        assert!(s.window.files.is_empty());
+        assert_eq!(s.changes(), 100);
+    }
+
+    #[test]
+    fn event_rate_rises_with_a_burst_then_decays_after_the_window() {
+        let mut s = Stats::new();
+        let start = s.rate.origin;
+        for _ in 0..30 {
+            s.record_event_at(start);
+        }
+        assert!(s.rate.per_second(start) > 0.0);
+
+        let later = start + Duration::from_secs(RATE_WINDOW_SECS / 2);
+        let mid_window = s.rate.per_second(later);
+        assert!(mid_window > 0.0 && mid_window < 30.0);
+
+        let expired = start + Duration::from_secs(RATE_WINDOW_SECS + 1);
+        assert_eq!(s.rate.per_second(expired), 0.0);
+        assert_eq!(s.events(), 30, "cumulative count is unaffected by decay");
+    }
+
+    #[test]
+    fn event_rate_recycles_slots_as_the_window_slides() {
+        let mut rate = EventRate::new(Instant::now());
+        let origin = rate.origin;
+        rate.record(origin);
+        let next_lap = origin + Duration::from_secs(RATE_WINDOW_SECS);
+        rate.record(next_lap);
+        // The first event shares a slot with the second but is out of window.
+        let avg = rate.per_second(next_lap);
+        assert!((avg - 1.0 / RATE_WINDOW_SECS as f64).abs() < f64::EPSILON);
     }
 
     #[test]
@@ -146,4 +596,32 @@ mod tests {
         // Memory may be zero on some sandboxes; just ensure refresh is sound.
         assert!(s.cpu_percent() >= 0.0);
     }
+
+    #[test]
+    fn memory_is_populated_from_our_own_process() {
+        let s = Stats::new();
+        assert!(s.memory_bytes() > 0, "baseline sample should read our RSS");
+        assert_eq!(s.system.processes().len(), 1, "only our PID is loaded");
+    }
+
+    #[test]
+    fn cpu_is_sampled_against_the_previous_refresh() {
+        fn burn(d: Duration) {
+            let until = Instant::now() + d;
+            while Instant::now() < until {
+                std::hint::spin_loop();
+            }
+        }
+
+        // sysinfo ignores a baseline with zero accumulated CPU ticks, which a
+        // freshly started test binary may well have.
+        burn(MINIMUM_CPU_UPDATE_INTERVAL);
+        let mut s = Stats::new();
+        s.refresh();
+        assert_eq!(s.cpu_percent(), 0.0, "too soon for a second CPU sample");
+
+        burn(MINIMUM_CPU_UPDATE_INTERVAL * 2);
+        s.refresh();
+        assert!(s.cpu_percent() > 0.0, "a busy loop should register CPU use");
+    }
 }
@@ -0,0 +1,128 @@
+//! Terminal styling.
+//!
+//! With the default `color` feature, [`Colorize`] is the `colored` crate's
+//! and the markers are the usual glyphs. Building with
+//! `--no-default-features` drops `colored` for a shim that returns text
+//! unchanged, and the markers fall back to plain ASCII tags.
+
+#[cfg(feature = "color")]
+pub(crate) use colored::Colorize;
+
+#[cfg(feature = "color")]
+mod marks {
+    pub(crate) const CHANGE: &str = "↻";
+    pub(crate) const SUMMARY: &str = "Σ";
+    pub(crate) const BULLET: &str = "•";
+    pub(crate) const SEP: &str = "·";
+    pub(crate) const DASH: &str = "—";
+    pub(crate) const RULE: &str = "─";
+    pub(crate) const TIMES: &str = "×";
+}
+
+#[cfg(not(feature = "color"))]
+mod marks {
+    pub(crate) const CHANGE: &str = "[CHANGE]";
+    pub(crate) const SUMMARY: &str = "[SUMMARY]";
+    pub(crate) const BULLET: &str = "-";
+    pub(crate) const SEP: &str = "-";
+    pub(crate) const DASH: &str = "-";
+    pub(crate) const RULE: &str = "-";
+    pub(crate) const TIMES: &str = "x";
+}
+
+pub(crate) use marks::*;
+
+/// `text` without its ANSI escape sequences, for output that isn't going to a
+/// terminal.
+pub(crate) fn strip_ansi(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            plain.push(c);
+            continue;
+        }
+        // A control sequence: `ESC [`, parameters, then one final character
+        // from `@` to `~`. Any other escape loses just the ESC.
+        if chars.clone().next() == Some('[') {
+            chars.next();
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    plain
+}
+
+/// The subset of `colored::Colorize` Flash uses, as no-ops.
+#[cfg(not(feature = "color"))]
+pub(crate) trait Colorize {
+    fn plain(&self) -> String;
+
+    fn normal(&self) -> String {
+        self.plain()
+    }
+    fn bold(&self) -> String {
+        self.plain()
+    }
+    fn yellow(&self) -> String {
+        self.plain()
+    }
+    fn bright_black(&self) -> String {
+        self.plain()
+    }
+    fn bright_blue(&self) -> String {
+        self.plain()
+    }
+    fn bright_cyan(&self) -> String {
+        self.plain()
+    }
+    fn bright_green(&self) -> String {
+        self.plain()
+    }
+    fn bright_white(&self) -> String {
+        self.plain()
+    }
+    fn bright_yellow(&self) -> String {
+        self.plain()
+    }
+}
+
+#[cfg(not(feature = "color"))]
+impl<S: AsRef<str>> Colorize for S {
+    fn plain(&self) -> String {
+        self.as_ref().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "color")]
+    #[test]
+    fn default_build_keeps_the_glyphs() {
+        assert_eq!(
+            [CHANGE, SUMMARY, BULLET, SEP, DASH, RULE, TIMES],
+            ["↻", "Σ", "•", "·", "—", "─", "×"]
+        );
+    }
+
+    #[cfg(not(feature = "color"))]
+    #[test]
+    fn plain_build_is_ascii_and_unstyled() {
+        for mark in [CHANGE, SUMMARY, BULLET, SEP, DASH, RULE, TIMES] {
+            assert!(mark.is_ascii(), "{mark:?} is not ASCII");
+        }
+        assert_eq!("ready".bright_green().bold(), "ready");
+    }
+
+    #[test]
+    fn strip_ansi_leaves_only_the_text() {
+        assert_eq!(strip_ansi("\x1b[1;92mready\x1b[0m · ok"), "ready · ok");
+        assert_eq!(strip_ansi("plain"), "plain");
+        assert_eq!(strip_ansi("cut\x1b[3"), "cut");
+    }
+}
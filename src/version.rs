@@ -0,0 +1,99 @@
+//! `--version`, for humans and for bug reports.
+//!
+//! The plain form matches what clap prints (`flash-watcher 0.2.0`).
+//! `--format json` adds the build metadata `build.rs` captured, so an issue
+//! can say exactly which binary misbehaved.
+
+use std::fmt::Write;
+
+use clap::ValueEnum;
+
+/// How `--version` prints (`--format`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VersionFormat {
+    /// `flash-watcher 0.2.0`.
+    #[default]
+    Text,
+    /// One JSON object with the version, git commit, rustc, and target.
+    Json,
+}
+
+/// What this binary is and how it was built. Fields `build.rs` could not
+/// determine read `"unknown"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub commit: &'static str,
+    pub rustc: &'static str,
+    pub target: &'static str,
+}
+
+impl BuildInfo {
+    pub const CURRENT: Self = Self {
+        version: env!("CARGO_PKG_VERSION"),
+        commit: env!("FLASH_GIT_COMMIT"),
+        rustc: env!("FLASH_RUSTC_VERSION"),
+        target: env!("FLASH_TARGET"),
+    };
+
+    pub fn render(&self, format: VersionFormat) -> String {
+        match format {
+            VersionFormat::Text => format!("{} {}", env!("CARGO_PKG_NAME"), self.version),
+            VersionFormat::Json => {
+                let fields = [
+                    ("name", env!("CARGO_PKG_NAME")),
+                    ("version", self.version),
+                    ("commit", self.commit),
+                    ("rustc", self.rustc),
+                    ("target", self.target),
+                ];
+                let body: Vec<String> = fields
+                    .iter()
+                    .map(|(key, value)| format!("\"{key}\":{}", json_string(value)))
+                    .collect();
+                format!("{{{}}}", body.join(","))
+            }
+        }
+    }
+}
+
+/// Quote `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_output_escapes_and_keeps_every_field() {
+        let info = BuildInfo {
+            version: "1.2.3",
+            commit: "abc\"def",
+            rustc: "rustc 1.95.0 (x\\y)",
+            target: "x86_64-unknown-linux-gnu\n",
+        };
+        assert_eq!(
+            info.render(VersionFormat::Json),
+            r#"{"name":"flash-watcher","version":"1.2.3","commit":"abc\"def","rustc":"rustc 1.95.0 (x\\y)","target":"x86_64-unknown-linux-gnu\n"}"#
+        );
+        assert_eq!(info.render(VersionFormat::Text), "flash-watcher 1.2.3");
+    }
+}
@@ -1,255 +1,3750 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::BufRead;
 use std::path::{Path, PathBuf};
-use std::sync::mpsc::{channel, RecvTimeoutError};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::Duration;
+use std::process::{ExitCode, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::{bail, Context, Result};
-use colored::Colorize;
-use notify::RecursiveMode;
-use notify_debouncer_mini::{new_debouncer, DebounceEventResult};
+use anyhow::{anyhow, bail, Context, Result};
+use globset::GlobBuilder;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher, WatcherKind};
+use notify_debouncer_mini::{new_debouncer_opt, DebounceEventResult, Debouncer};
 
 use crate::cli::Cli;
-use crate::config::Settings;
-use crate::filter::Filter;
+use crate::config::{parse_extensions, write_starter, Config, Settings, DEFAULT_CONFIG_FILE};
+use crate::filter::{
+    read_ignore_file, read_pattern_file, EntryKind, Filter, PathDecision, RejectReason,
+    EDITOR_TEMP_PATTERNS,
+};
+use crate::git::Tracked;
 use crate::runner::Runner;
-use crate::stats::Stats;
+use crate::sink::{
+    display_path, ChangeEvent, ChangeKind, ConsoleSink, EventSink, LinePrefix, LogFile,
+};
+use crate::stats::{format_bytes, format_duration, Stats};
+use crate::style::{Colorize, BULLET, SEP, SUMMARY};
+use crate::version::BuildInfo;
 
 /// Entry point used by both the binary and the integration tests.
 ///
 /// Loads [`Settings`], validates them, sets up a debounced watcher, and runs
-/// the event loop until the channel from notify closes (i.e. the watcher is
-/// dropped or the process is killed).
-pub fn run(cli: Cli) -> Result<()> {
+/// the event loop until Ctrl-C (exit code 130), the channel from notify
+/// closes, or `--max-runs` is used up. In the latter case the returned code
+/// mirrors the command's last exit status. Every one of these runs `--after`
+/// on the way out.
+///
+/// Changes and runs are reported on the terminal through a [`ConsoleSink`].
+/// Ctrl-C only reaches the loop once [`catch_ctrl_c`] has been called.
+pub fn run(cli: Cli) -> Result<ExitCode> {
+    run_inner(cli, None)
+}
+
+/// Like [`run`], but report changes and runs to `sink` instead of the
+/// terminal. Startup output (banner, `ready` line) is unaffected.
+pub fn run_with_sink(cli: Cli, sink: &dyn EventSink) -> Result<ExitCode> {
+    run_inner(cli, Some(sink))
+}
+
+fn run_inner(cli: Cli, custom_sink: Option<&dyn EventSink>) -> Result<ExitCode> {
+    if cli.version {
+        println!(
+            "{}",
+            BuildInfo::CURRENT.render(cli.format.unwrap_or_default())
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+
     if cli.bench {
-        return crate::bench::run();
+        crate::bench::run()?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if cli.list_watchers {
+        print!("{}", backend_report());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if cli.config_schema {
+        println!("{}", Config::schema()?);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if cli.init {
+        if cli.config.len() > 1 {
+            bail!("--init writes a single file; pass at most one --config");
+        }
+        let path = cli
+            .config
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE));
+        write_starter(&path, cli.force)?;
+        println!("wrote {}", path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut cli = cli;
+    if cli.watch_stdin {
+        let read = read_watch_list(std::io::stdin().lock(), &cli.watch)
+            .context("reading watch paths from stdin")?;
+        cli.watch.extend(read);
+    }
+
+    let print_config = cli.print_config;
+    let check = cli.check_config;
+    let explain = cli.explain.clone();
+    let ready_fd = cli.ready_fd;
+    // Edits to the active config files, or to the ones they extend, never
+    // trigger the command; with --reload-config they re-apply their filters
+    // instead.
+    let config_paths: Vec<PathBuf> = cli.config.iter().flat_map(|p| Config::sources(p)).collect();
+    let reload_from = cli.reload_config.then(|| cli.clone());
+    let started = Instant::now();
+    let settings = Settings::build(cli.clone())?;
+    if print_config {
+        print!("{}", Config::from(&settings).to_yaml()?);
+        return Ok(ExitCode::SUCCESS);
+    }
+    if check {
+        return Ok(check_config(&cli, &settings));
+    }
+    if let Some(path) = explain {
+        let filters = Filters::build(&settings)?;
+        let path = std::path::absolute(&path)
+            .with_context(|| format!("resolving '{}'", path.display()))?;
+        // Events arrive with the canonical path, so judge that one.
+        let path = path.canonicalize().unwrap_or(path);
+        println!(
+            "{}: {}",
+            display_path(&path),
+            filters.explain(&filters.roots, &path)
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !settings.projects.is_empty() {
+        return run_projects(cli, settings, &config_paths, ready_fd, started);
+    }
+    settings.validate()?;
+
+    let ready = || ready_fd.map_or(Ok(()), signal_ready);
+    watch_session(
+        settings,
+        &config_paths,
+        reload_from.as_ref(),
+        &ready,
+        custom_sink,
+        started,
+    )
+}
+
+/// `--check-config`: look for whatever would stop Flash from starting — no
+/// command, a glob that doesn't compile, a watch path that doesn't exist —
+/// without watching anything or running the command. Each project is checked
+/// on its own, and every problem found is reported rather than just the
+/// first.
+fn check_config(cli: &Cli, settings: &Settings) -> ExitCode {
+    let sessions: Vec<(Option<String>, Settings)> = if settings.projects.is_empty() {
+        vec![(None, settings.clone())]
+    } else {
+        settings
+            .projects
+            .iter()
+            .map(|project| {
+                let session = Settings::merge(cli.clone(), Some(project.clone()));
+                (session.label.clone(), session)
+            })
+            .collect()
+    };
+    let mut problems: Vec<String> = Vec::new();
+    for (label, session) in &sessions {
+        let nested = if session.projects.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow!("projects can't list projects of their own"))
+        };
+        let checks = [
+            nested,
+            session.validate().map_err(anyhow::Error::from),
+            Filters::build(session).map(drop),
+        ];
+        for err in checks.into_iter().filter_map(Result::err) {
+            let problem = match label {
+                Some(label) => format!("project '{label}': {err:#}"),
+                None => format!("{err:#}"),
+            };
+            problems.push(problem);
+        }
+    }
+    if problems.is_empty() {
+        match settings.projects.len() {
+            0 => println!("config ok"),
+            1 => println!("config ok {SEP} 1 project"),
+            n => println!("config ok {SEP} {n} projects"),
+        }
+        return ExitCode::SUCCESS;
+    }
+    for problem in &problems {
+        eprintln!("flash-watcher: {problem}");
+    }
+    eprintln!(
+        "flash-watcher: the config has {}",
+        match problems.len() {
+            1 => "1 problem".to_string(),
+            n => format!("{n} problems"),
+        }
+    );
+    ExitCode::FAILURE
+}
+
+/// Run every project in the config file's `projects` list side by side, each
+/// with its own watcher and runner, until all of them are done. The exit code
+/// is the first failing project's, if any.
+fn run_projects(
+    cli: Cli,
+    settings: Settings,
+    config_paths: &[PathBuf],
+    ready_fd: Option<u32>,
+    started: Instant,
+) -> Result<ExitCode> {
+    if !cli.command.is_empty() || !cli.watch.is_empty() {
+        bail!(
+            "the config file lists projects, each with its own command and watch paths; \
+             leave them off the command line"
+        );
+    }
+    if cli.reload_config {
+        eprintln!("flash-watcher: --reload-config has no effect with projects; ignoring it");
+    }
+    let sessions = settings
+        .projects
+        .into_iter()
+        .map(|project| {
+            if project.projects.is_some() {
+                bail!("projects can't list projects of their own");
+            }
+            let session = Settings::merge(cli.clone(), Some(project));
+            session.validate()?;
+            Ok(session)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // --ready-fd fires once, when the last project is watching.
+    let starting = AtomicUsize::new(sessions.len());
+    let ready = || {
+        if starting.fetch_sub(1, Ordering::SeqCst) == 1 {
+            if let Some(fd) = ready_fd {
+                signal_ready(fd)?;
+            }
+        }
+        Ok(())
+    };
+    let results: Vec<Result<ExitCode>> = thread::scope(|scope| {
+        let handles: Vec<_> = sessions
+            .into_iter()
+            .map(|session| {
+                let ready = &ready;
+                scope.spawn(move || {
+                    let result = watch_session(session, config_paths, None, ready, None, started);
+                    // One project failing to start or keep watching stops
+                    // the rest, rather than leaving them running headless.
+                    if result.is_err() {
+                        interrupt_sessions();
+                    }
+                    result
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("a project's watcher panicked")))
+            })
+            .collect()
+    });
+
+    let mut code = ExitCode::SUCCESS;
+    for result in results {
+        let this = result?;
+        if code == ExitCode::SUCCESS {
+            code = this;
+        }
+    }
+    Ok(code)
+}
+
+/// One watch session: set up the watcher and runner for `settings` and loop
+/// until it ends. `ready` is called once every watch is registered.
+/// `started` is when loading the configuration began, for `--verbose`
+/// startup timings.
+fn watch_session(
+    settings: Settings,
+    config_paths: &[PathBuf],
+    reload_from: Option<&Cli>,
+    ready: &(dyn Fn() -> Result<()> + Sync),
+    custom_sink: Option<&dyn EventSink>,
+    started: Instant,
+) -> Result<ExitCode> {
+    let mut startup = StartupTimes::new(started);
+    startup.record("config", started);
+    let phase = Instant::now();
+    let mut filters = Filters::build(&settings)?;
+    let watch_roots = filters.roots.clone();
+    startup.record("filters", phase);
+    // A shallow watch costs one OS watch per root, so there is nothing to
+    // budget.
+    if let Some(limit) = settings.max_dirs.filter(|_| settings.recursive) {
+        check_dir_budget(&watch_roots, limit)?;
+    }
+
+    let mut out = LinePrefix::new(settings.label.clone(), settings.timestamps);
+    if let Some(path) = &settings.log_file {
+        let log = LogFile::open(path)
+            .with_context(|| format!("opening log file '{}'", path.display()))?;
+        out = out.with_log(log);
+    }
+    if let Some(before) = &settings.before {
+        let phase = Instant::now();
+        let status = Runner::new(vec![before.clone()], false, false)
+            .run_to_end()
+            .context("running the --before command")?;
+        if !status.success() {
+            out.eprintln(format_args!(
+                "flash-watcher: --before command `{before}` exited with {status}, not watching"
+            ));
+            return Ok(exit_code(Some(status)));
+        }
+        startup.record("--before", phase);
+    }
+
+    let stats = (settings.stats
+        || settings.summary_interval.is_some()
+        || settings.count_only
+        || settings.print_events_summary_on_exit
+        || settings.max_memory.is_some())
+    .then(|| {
+        Arc::new(Mutex::new(
+            Stats::new().with_summaries(settings.summary_interval.is_some()),
+        ))
+    });
+    // Dropped on every way out of this function, which stops and joins the
+    // periodic printers so nothing is written after we return.
+    let mut background = Background::default();
+    if let Some(stats) = stats.clone() {
+        if settings.stats {
+            let stop = background.stop.clone();
+            background.threads.push(spawn_stats_thread(
+                stats.clone(),
+                settings.stats_interval,
+                out.clone(),
+                stop,
+            ));
+        }
+        if let Some(interval) = settings.summary_interval {
+            let stop = background.stop.clone();
+            background
+                .threads
+                .push(spawn_summary_thread(stats, interval, out.clone(), stop));
+        }
+    }
+
+    print_banner(&settings, &watch_roots, &out);
+
+    let console;
+    let sink: &dyn EventSink = match custom_sink {
+        Some(sink) => sink,
+        None => {
+            console = ConsoleSink::new(
+                !settings.fast
+                    && !settings.stats
+                    && settings.summary_interval.is_none()
+                    && !settings.count_only,
+            )
+            .with_path_display(settings.path_display)
+            .with_prefix(out.clone());
+            &console
+        }
+    };
+    let tally;
+    let sink: &dyn EventSink = match &stats {
+        Some(stats) => {
+            tally = Tally {
+                inner: sink,
+                stats: stats.clone(),
+            };
+            &tally
+        }
+        None => sink,
+    };
+
+    let mut runner = Runner::new(settings.command.clone(), settings.restart, settings.clear)
+        .with_raw_command(settings.raw_command)
+        .with_literal_command(settings.literal_command)
+        .with_process_group(settings.process_group)
+        .with_restart_signal(settings.restart_signal, settings.restart_grace)
+        .with_parallel(
+            settings
+                .parallel
+                .map(|n| n.try_into().unwrap_or(usize::MAX)),
+        )
+        .with_capture(settings.capture)
+        .with_matrix(&settings.matrix)
+        .with_kind_command(ChangeKind::Create, settings.on_create.clone())
+        .with_kind_command(ChangeKind::Modify, settings.on_modify.clone())
+        .with_kind_command(ChangeKind::Remove, settings.on_remove.clone());
+    if let Some(mode) = settings.clear_mode {
+        runner = runner.with_clear_mode(mode);
+    }
+    if cfg!(not(unix)) && settings.process_group {
+        out.eprintln("flash-watcher: --process-group is only supported on Unix; ignoring it");
+    }
+    if cfg!(not(unix)) && settings.restart_signal.is_some() {
+        out.eprintln("flash-watcher: --restart-signal is only supported on Unix; killing instead");
+    }
+    if settings.capture && settings.restart {
+        out.eprintln("flash-watcher: --capture has no effect with --restart; ignoring it");
+    }
+    if settings.fail_fast && settings.restart {
+        out.eprintln("flash-watcher: --fail-fast has no effect with --restart; ignoring it");
+    }
+    if settings.require_initial && settings.restart {
+        out.eprintln("flash-watcher: --require-initial has no effect with --restart; ignoring it");
+    }
+    if !settings.matrix.is_empty() && settings.restart {
+        out.eprintln("flash-watcher: matrix has no effect with --restart; ignoring it");
+    }
+    if settings.watch_extensions_only && !settings.recursive {
+        out.eprintln(
+            "flash-watcher: --watch-extensions-only has no effect with --no-recursive; ignoring it",
+        );
+    } else if settings.watch_extensions_only && settings.entries == EntryKind::Dirs {
+        out.eprintln(
+            "flash-watcher: --watch-extensions-only has no effect with --dirs-only; ignoring it",
+        );
+    }
+    if !settings.no_debounce_pattern.is_empty() && settings.debounce.is_zero() {
+        out.eprintln(
+            "flash-watcher: --no-debounce-pattern has no effect with --debounce 0; ignoring it",
+        );
+    }
+    if settings.parallel.is_some() && settings.restart {
+        out.eprintln("flash-watcher: --parallel has no effect with --restart; ignoring it");
+    } else if settings.parallel.is_some() && settings.capture {
+        out.eprintln("flash-watcher: --capture has no effect with --parallel; ignoring it");
+    }
+    if settings.notify {
+        if let Some(hook) = crate::alert::desktop_notifier() {
+            runner = runner.with_failure_hook(hook);
+        }
+    }
+    let (tx, rx) = sync_channel::<Message>(settings.buffer_size.try_into().unwrap_or(usize::MAX));
+    let _interrupts = catch_interrupts(tx.clone());
+    if let (Some(limit), Some(stats)) = (settings.max_memory, stats.clone()) {
+        let sample = move || {
+            let mut s = stats.lock().ok()?;
+            s.refresh();
+            Some(s.memory_bytes())
+        };
+        background.threads.push(spawn_memory_guard(
+            sample,
+            limit.saturating_mul(1024 * 1024),
+            MEMORY_POLL,
+            tx.clone(),
+            background.stop.clone(),
+        ));
+    }
+    let queue = EventQueue::new(tx);
+
+    let mut session = Session {
+        runner,
+        sink,
+        stats: stats.as_ref(),
+        settings: &settings,
+        out: &out,
+        runs_left: settings.max_runs,
+    };
+    let mut last_run: Option<Instant> = None;
+    if settings.initial && !settings.count_only {
+        last_run = Some(Instant::now());
+        let phase = Instant::now();
+        let ran = session.runner.run(sink);
+        let ok = session.record(ran, "initial run");
+        startup.record("initial run", phase);
+        if settings.require_initial && !settings.restart && !succeeded(ok, &session.runner) {
+            out.eprintln("flash-watcher: the initial run failed, not watching (--require-initial)");
+            return Ok(session.exit_failed());
+        }
+        if let Some(code) = session.end_after(ok, settings.count_initial) {
+            return Ok(code);
+        }
+    }
+
+    let phase = Instant::now();
+    let urgent = if settings.no_debounce_pattern.is_empty() || settings.debounce.is_zero() {
+        None
+    } else {
+        let filter = Filter::new(&[], &settings.no_debounce_pattern, &[])?
+            .ignore_case(settings.ignore_case_paths);
+        Some((filter, watch_roots.clone()))
+    };
+    let mut source = EventSource::new(
+        settings.debounce,
+        settings.canonicalize,
+        settings.entries == EntryKind::Dirs,
+        FrontFilter {
+            content_only: settings.content_only,
+            urgent,
+        },
+        queue.clone(),
+        stats.clone(),
+        out.clone(),
+    )?;
+
+    let extensions_only =
+        settings.watch_extensions_only && settings.recursive && settings.entries != EntryKind::Dirs;
+    // Leaving ignored subtrees unwatched only pays off where a recursive
+    // watch costs one OS watch per directory anyway, as with inotify.
+    let skip_ignored = settings.recursive
+        && cfg!(any(target_os = "linux", target_os = "android"))
+        && filters.prunes_any();
+    let mut pruned = None;
+    if extensions_only || skip_ignored {
+        let watch = PrunedWatch::start(
+            &watch_roots,
+            &filters,
+            extensions_only,
+            settings.watch_retries,
+            |dir| source.watcher().watch(dir, RecursiveMode::NonRecursive),
+        )?;
+        if let Some((_, err)) = watch.unwatched.first() {
+            out.eprintln(format_args!(
+                "flash-watcher: could not watch {} of {} directories after {} retries: {err}; \
+                 continuing without them",
+                watch.unwatched.len(),
+                watch.unwatched.len() + watch.watched.len(),
+                settings.watch_retries
+            ));
+        }
+        if !settings.fast && (extensions_only || watch.watched.len() < watch.scanned) {
+            let why = if extensions_only {
+                "--watch-extensions-only"
+            } else {
+                "ignored subtrees left out"
+            };
+            out.println(
+                format!(
+                    "watching {} of {} directories ({why})",
+                    watch.watched.len(),
+                    watch.scanned
+                )
+                .bright_green(),
+            );
+        }
+        pruned = Some(watch);
+    } else {
+        // Roots the OS had no watches left for, even after retrying. Startup
+        // goes on without them as long as one root is watched.
+        let mut unwatched = Vec::new();
+        for root in &watch_roots {
+            let result = watch_with_retry(settings.watch_retries, || {
+                source.watcher().watch(root, filters.recursive_mode(root))
+            });
+            match result {
+                Ok(()) => {}
+                Err(err) if out_of_watches(&err) => unwatched.push((root, err)),
+                Err(err) => {
+                    return Err(explain_watch_error(err))
+                        .with_context(|| format!("watching '{}'", root.display()));
+                }
+            }
+        }
+        if unwatched.len() == watch_roots.len() {
+            if let Some((root, err)) = unwatched.into_iter().next() {
+                return Err(explain_watch_error(err))
+                    .with_context(|| format!("watching '{}'", root.display()));
+            }
+        } else {
+            for (root, err) in unwatched {
+                out.eprintln(format_args!(
+                    "flash-watcher: could not watch '{}' after {} retries: {err}; \
+                     continuing without it",
+                    display_path(root),
+                    settings.watch_retries
+                ));
+            }
+        }
+    }
+
+    startup.record("watches", phase);
+    // What each root was when it was watched, so one deleted and recreated
+    // between two looks still counts as removed.
+    let mut identities: Vec<(PathBuf, Option<RootId>)> = watch_roots
+        .iter()
+        .map(|root| (root.clone(), root_id(root)))
+        .collect();
+
+    // Scan only once the watches are live, so nothing edited in between is
+    // missed. Everything found is covered by a single run.
+    let is_config = |p: &Path| config_paths.iter().any(|c| c == p);
+    if let Some(window) = settings.changed_since.filter(|_| !settings.count_only) {
+        let mut recent = changed_within(&watch_roots, window, settings.recursive, &filters);
+        recent.retain(|p| !is_config(p));
+        for path in &recent {
+            report_change(sink, stats.as_ref(), &watch_roots, path, ChangeKind::Modify);
+        }
+        if !recent.is_empty() {
+            last_run = Some(Instant::now());
+            let ran = session.runner.run_for(sink, &[ChangeKind::Modify]);
+            let ok = session.record(ran, "command");
+            if let Some(code) = session.end_after(ok, true) {
+                return Ok(code);
+            }
+        }
+    }
+
+    if settings.verbose && !settings.fast {
+        out.println(startup.to_string().bright_black());
+    }
+    if !settings.fast {
+        out.println(format!("ready {SEP} waiting for changes").bright_green());
+    }
+    ready()?;
+
+    // With --throttle, a change that lands too soon after the previous run
+    // books a single run for when the window reopens.
+    let mut due: Option<Instant> = None;
+    // Watch roots deleted since startup. notify stops reporting them, so with
+    // --rewatch the loop polls for them to come back.
+    let mut missing: Vec<PathBuf> = Vec::new();
+    // The changes the next run is for, one per path, reported to the sink
+    // as it starts.
+    let mut batch: Vec<(PathBuf, ChangeKind)> = Vec::new();
+    // When a matching file last changed, for --idle-timeout. Only accepted
+    // changes count; the stats printers never touch the channel.
+    let mut last_change = Instant::now();
+    // --await-stable: the changed files the next run waits on.
+    let mut settler = settings.await_stable.map(Settler::new);
+    'watch: loop {
+        if session.runner.running_jobs() > 0 {
+            session.runner.reap(sink);
+            if let Some(code) = session.end_after(true, false) {
+                return Ok(code);
+            }
+        }
+        if settings.rewatch && !missing.is_empty() {
+            restore_roots(
+                &mut source,
+                &mut missing,
+                &mut identities,
+                &filters,
+                settings.fast,
+                &out,
+            );
+        }
+        let mut wait = due.map_or(Duration::from_secs(60), |at| {
+            at.saturating_duration_since(Instant::now())
+        });
+        if settings.rewatch && !missing.is_empty() {
+            wait = wait.min(REWATCH_POLL);
+        }
+        if session.runner.running_jobs() > 0 {
+            wait = wait.min(JOB_POLL);
+        }
+        if let Some(pruned) = pruned.as_mut().filter(|p| !p.skipped.is_empty()) {
+            pruned.rescan(&mut source, &watch_roots, &filters, &queue);
+            wait = wait.min(SKIPPED_POLL);
+        }
+        let idle_at = settings.idle_timeout.map(|idle| last_change + idle);
+        if let Some(at) = idle_at {
+            wait = wait.min(at.saturating_duration_since(Instant::now()));
+        }
+        match rx.recv_timeout(wait) {
+            Ok(Message::Interrupt) => break,
+            Ok(Message::OverMemory(bytes)) => {
+                out.eprintln(format_args!(
+                    "flash-watcher: using {} of memory, over the --max-memory limit of {} MB; \
+                     stopping",
+                    format_bytes(bytes),
+                    settings.max_memory.unwrap_or_default()
+                ));
+                return Ok(session.exit_failed());
+            }
+            Ok(Message::Change(path, kind)) => {
+                if !path.exists() || watch_roots.contains(&path) {
+                    note_removed_roots(&identities, &mut missing, settings.rewatch, &out);
+                }
+                if let Some(pruned) = pruned.as_mut() {
+                    pruned.adopt(&mut source, &watch_roots, &filters, &path);
+                }
+                if is_config(&path) {
+                    if let Some(cli) = reload_from {
+                        reload_filters(cli, &settings, &mut filters, settings.fast, &out);
+                    }
+                    continue;
+                }
+                if admit(&filters, &watch_roots, &path, stats.as_ref()) {
+                    if settler.as_mut().is_some_and(|s| !s.note(&path)) {
+                        continue;
+                    }
+                    last_change = Instant::now();
+                    if settings.count_only {
+                        report_change(sink, stats.as_ref(), &watch_roots, &path, kind);
+                        print_counts(stats.as_ref(), false, &out);
+                        continue;
+                    }
+                    add_change(&mut batch, path, kind);
+                } else if settings.count_only {
+                    print_counts(stats.as_ref(), false, &out);
+                    continue;
+                } else if !queue.overflowed() {
+                    continue;
+                }
+                // Otherwise the change may have pushed a wanted one out of
+                // the full buffer, so run rather than miss it.
+            }
+            Err(RecvTimeoutError::Timeout) if due.is_some() => {}
+            Err(RecvTimeoutError::Timeout) if idle_at.is_some_and(|at| Instant::now() >= at) => {
+                if !settings.fast {
+                    out.println(format!("idle {SEP} no changes, exiting").bright_green());
+                }
+                break;
+            }
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let mut config_changed = false;
+        let mut pending = true;
+        while pending {
+            if let Some(at) = next_slot(settings.throttle, last_run) {
+                due = Some(at);
+                break;
+            }
+            due = None;
+            let dropped = queue.take_dropped();
+            if dropped > 0 {
+                out.eprintln(format_args!(
+                    "flash-watcher: event buffer full, dropped {dropped} change notifications \
+                     (see --buffer-size)"
+                ));
+            }
+            if let Some(settler) = settler.as_mut() {
+                let unsettled = settler.settle();
+                if INTERRUPTED.load(Ordering::SeqCst) {
+                    break 'watch;
+                }
+                if let Some(path) = unsettled.first() {
+                    out.eprintln(format_args!(
+                        "flash-watcher: '{}'{} still changing after {}; running anyway",
+                        display_path(path),
+                        if unsettled.len() > 1 {
+                            format!(" and {} more", unsettled.len() - 1)
+                        } else {
+                            String::new()
+                        },
+                        format_millis(settler.window * SETTLE_LIMIT_WINDOWS)
+                    ));
+                }
+            }
+            let changes = std::mem::take(&mut batch);
+            for (path, kind) in &changes {
+                report_change(sink, stats.as_ref(), &watch_roots, path, *kind);
+            }
+            let kinds: Vec<ChangeKind> = changes.iter().map(|&(_, kind)| kind).collect();
+            let fresh = settings.output.as_deref().filter(|output| {
+                let inputs: Vec<PathBuf> = changes.iter().map(|(path, _)| path.clone()).collect();
+                up_to_date(output, &inputs)
+            });
+            let scripts = settings
+                .command_from_matched_file
+                .as_deref()
+                .map(|name| matched_scripts(name, &watch_roots, &changes));
+            if let Some(output) = fresh {
+                if !settings.fast {
+                    out.println(
+                        format!("skip {SEP} {} is up to date", display_path(output)).bright_black(),
+                    );
+                }
+                // Nothing ran, so nothing piled up behind it to drain.
+                break;
+            }
+            last_run = Some(Instant::now());
+            let ran = match &scripts {
+                Some((scripts, rest)) => session.runner.run_for_scripts(sink, scripts, rest),
+                None => session.runner.run_for(sink, &kinds),
+            };
+            let ok = session.record(ran, "command");
+            if let Some(code) = session.end_after(ok, true) {
+                return Ok(code);
+            }
+            // Ctrl-C also reaches the command, so a run cut short by it
+            // ends up here rather than back in `recv_timeout`.
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break 'watch;
+            }
+            // Without --restart the run blocked, so anything that changed
+            // meanwhile is sitting in the channel. Fold it into a single
+            // follow-up run rather than one per event, which changes lost to
+            // a full buffer also get.
+            pending = !settings.restart
+                && (drain_pending(&rx, |p, kind| {
+                    if !p.exists() || watch_roots.iter().any(|root| root == p) {
+                        note_removed_roots(&identities, &mut missing, settings.rewatch, &out);
+                    }
+                    if let Some(pruned) = pruned.as_mut() {
+                        pruned.adopt(&mut source, &watch_roots, &filters, p);
+                    }
+                    if is_config(p) {
+                        config_changed = true;
+                        return false;
+                    }
+                    let accepted = admit(&filters, &watch_roots, p, stats.as_ref())
+                        && settler.as_mut().is_none_or(|s| s.note(p));
+                    if accepted {
+                        add_change(&mut batch, p.to_path_buf(), kind);
+                        last_change = Instant::now();
+                    }
+                    accepted
+                }) || queue.overflowed());
+        }
+        if config_changed {
+            if let Some(cli) = reload_from {
+                reload_filters(cli, &settings, &mut filters, settings.fast, &out);
+            }
+        }
+    }
+
+    if settings.count_only {
+        print_counts(stats.as_ref(), true, &out);
+    }
+    session.shut_down();
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        // The shell convention for "terminated by SIGINT".
+        return Ok(ExitCode::from(130));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// What the event loop waits on: a path from notify, Ctrl-C, or a memory
+/// reading over `--max-memory`, in bytes.
+enum Message {
+    Change(PathBuf, ChangeKind),
+    Interrupt,
+    OverMemory(u64),
+}
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// The channel of every running session, keyed by registration, so Ctrl-C
+/// reaches all of them when `projects` runs several at once.
+static INTERRUPT_TX: Mutex<Vec<(u64, SyncSender<Message>)>> = Mutex::new(Vec::new());
+
+/// Keeps a session's channel registered for Ctrl-C until dropped.
+struct Interrupts(u64);
+
+impl Drop for Interrupts {
+    fn drop(&mut self) {
+        if let Ok(mut senders) = INTERRUPT_TX.lock() {
+            senders.retain(|(id, _)| *id != self.0);
+        }
+    }
+}
+
+/// Register `tx` to hear about Ctrl-C, so the loop can wind down through
+/// [`Session::shut_down`], for as long as the session runs. The signal only
+/// arrives once [`catch_ctrl_c`] has installed the process handler.
+fn catch_interrupts(tx: SyncSender<Message>) -> Interrupts {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    if let Ok(mut senders) = INTERRUPT_TX.lock() {
+        // A fresh start, not another project joining a running one.
+        if senders.is_empty() {
+            INTERRUPTED.store(false, Ordering::SeqCst);
+        }
+        senders.push((id, tx));
+    }
+    Interrupts(id)
+}
+
+/// Install a process-wide Ctrl-C handler that calls [`interrupt`]. The
+/// binary does this before [`run`]; a library caller that leaves it out keeps
+/// Ctrl-C to itself and can call [`interrupt`] from its own handler.
+pub fn catch_ctrl_c() -> Result<()> {
+    ctrlc::set_handler(interrupt).context("installing the Ctrl-C handler")
+}
+
+/// Wind down every running session as Ctrl-C does: signal the command, let
+/// it finish, run `--after`, and return from [`run`].
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    crate::runner::interrupt_group();
+    interrupt_sessions();
+}
+
+/// Ask every running session to wind down.
+fn interrupt_sessions() {
+    if let Ok(senders) = INTERRUPT_TX.lock() {
+        // Never block in a signal handler. With the queue full the loop is
+        // about to run anyway and checks the flag after.
+        for (_, tx) in senders.iter() {
+            let _ = tx.try_send(Message::Interrupt);
+        }
+    }
+}
+
+/// A running session's command and what reports on it, for the ways the
+/// loop can end.
+struct Session<'a> {
+    runner: Runner,
+    sink: &'a dyn EventSink,
+    stats: Option<&'a Arc<Mutex<Stats>>>,
+    settings: &'a Settings,
+    out: &'a LinePrefix,
+    /// Runs left before `--max-runs` ends the session.
+    runs_left: Option<u64>,
+}
+
+impl Session<'_> {
+    /// Wind down for exit: wait for the command, and any `--parallel` jobs
+    /// still running, or stop a `--restart` one, then run `--after` whatever
+    /// the command's outcome, and print the `--print-events-summary-on-exit`
+    /// line. Returns the command's last exit status.
+    fn shut_down(&mut self) -> Option<ExitStatus> {
+        self.runner.wait_for_jobs(self.sink);
+        let status = self.runner.finish();
+        if let Some(after) = &self.settings.after {
+            match Runner::new(vec![after.clone()], false, false).run_to_end() {
+                Ok(after_status) if after_status.success() => {}
+                Ok(after_status) => self.out.eprintln(format_args!(
+                    "flash-watcher: --after command `{after}` exited with {after_status}"
+                )),
+                Err(err) => self.out.eprintln(format_args!(
+                    "flash-watcher: --after command failed: {err:#}"
+                )),
+            }
+        }
+        if self.settings.print_events_summary_on_exit {
+            if let Some(Ok(s)) = self.stats.map(|stats| stats.lock()) {
+                self.out.println(format_args!(
+                    "{}  session {SEP} {}",
+                    SUMMARY.bright_blue(),
+                    s.render_session()
+                ));
+            }
+        }
+        status
+    }
+
+    /// Count a run of `what` that launched, or say why it didn't. Returns
+    /// whether it launched.
+    fn record(&self, ran: Result<()>, what: &str) -> bool {
+        match ran {
+            Ok(()) => {
+                record_run(self.stats);
+                true
+            }
+            Err(err) => {
+                self.out
+                    .eprintln(format_args!("flash-watcher: {what} failed: {err:#}"));
+                false
+            }
+        }
+    }
+
+    /// The exit code, if the run just made ends the session: it failed
+    /// under `--fail-fast`, or it was the last `--max-runs` allows. `ok` is
+    /// what [`record`](Self::record) returned; only `counted` runs use up
+    /// `--max-runs`.
+    fn end_after(&mut self, ok: bool, counted: bool) -> Option<ExitCode> {
+        if self.settings.fail_fast && !succeeded(ok, &self.runner) {
+            return Some(self.fail_fast());
+        }
+        if counted && use_up_run(&mut self.runs_left) {
+            return Some(exit_code(self.shut_down()));
+        }
+        None
+    }
+
+    /// Shut down after a failed run under `--fail-fast`.
+    fn fail_fast(&mut self) -> ExitCode {
+        self.out
+            .eprintln("flash-watcher: the command failed, stopping (--fail-fast)");
+        self.exit_failed()
+    }
+
+    /// Shut down after a failed run. The exit code is the command's, or 1 if
+    /// it never got to exit.
+    fn exit_failed(&mut self) -> ExitCode {
+        match self.shut_down() {
+            Some(status) if !status.success() => exit_code(Some(status)),
+            _ => ExitCode::FAILURE,
+        }
+    }
+}
+
+/// The `--watch-stdin` paths: one per line, trimmed, blank lines skipped,
+/// and without repeats of each other or of `existing`. Reading nothing is an
+/// error unless `existing` already names something to watch.
+fn read_watch_list(input: impl BufRead, existing: &[String]) -> Result<Vec<String>> {
+    let mut paths: Vec<String> = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let path = line.trim();
+        if path.is_empty() || existing.iter().chain(&paths).any(|p| p == path) {
+            continue;
+        }
+        paths.push(path.to_string());
+    }
+    if paths.is_empty() && existing.is_empty() {
+        bail!("--watch-stdin got no paths; pipe at least one, or add --watch");
+    }
+    Ok(paths)
+}
+
+/// Whether a run went well: it launched and, if it was one-shot, exited
+/// successfully. Restart-mode runs are still going and have no status yet.
+fn succeeded(launched: bool, runner: &Runner) -> bool {
+    launched && runner.last_status().is_none_or(|status| status.success())
+}
+
+/// Tell whoever launched us that every watch is registered (and any
+/// `--initial` run has finished), so changes made from now on will be seen.
+#[cfg(unix)]
+fn signal_ready(fd: u32) -> Result<()> {
+    use std::io::Write;
+    // Going through /dev/fd borrows the inherited descriptor without taking
+    // ownership of it, and needs no unsafe.
+    let path = format!("/dev/fd/{fd}");
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(b"\n"))
+        .with_context(|| format!("writing ready signal to file descriptor {fd}"))
+}
+
+#[cfg(not(unix))]
+fn signal_ready(_fd: u32) -> Result<()> {
+    bail!("--ready-fd is only supported on Unix")
+}
+
+/// Re-read the config files for `--reload-config` and swap in their filters.
+/// If any no longer parses, the previous filters stay.
+fn reload_filters(
+    cli: &Cli,
+    running: &Settings,
+    filters: &mut Filters,
+    fast: bool,
+    out: &LinePrefix,
+) {
+    let rebuilt = Settings::build(cli.clone())
+        .map_err(anyhow::Error::from)
+        .and_then(|settings| Ok((Filters::build(&settings)?, settings)));
+    match rebuilt {
+        Ok((rebuilt, settings)) => {
+            *filters = rebuilt;
+            if !fast {
+                out.println("config reloaded".bright_green());
+            }
+            let stale = keys_needing_restart(running, &settings);
+            if !stale.is_empty() {
+                out.eprintln(format_args!(
+                    "flash-watcher: restart to apply the new {}",
+                    stale.join(", ")
+                ));
+            }
+        }
+        Err(err) => out.eprintln(format_args!(
+            "flash-watcher: config reload failed, keeping the previous filters: {err:#}"
+        )),
+    }
+}
+
+/// Config keys [`reload_filters`] re-applies in a running session.
+const RELOADED_KEYS: &[&str] = &[
+    "ext",
+    "pattern",
+    "ignore",
+    "ignore_file",
+    "pattern_file",
+    "exclude_output",
+    "ignore_editor_temp",
+    "include_hidden",
+    "ignore_case_paths",
+    "entries",
+    "git_tracked_only",
+];
+
+/// The config keys that differ between `running` and `reloaded` but only
+/// take effect on a restart. The filters of a `watch:` entry reload; its
+/// path doesn't.
+fn keys_needing_restart(running: &Settings, reloaded: &Settings) -> Vec<String> {
+    let as_map = |settings: &Settings| match serde_json::to_value(Config::from(settings)) {
+        Ok(serde_json::Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    let (before, after) = (as_map(running), as_map(reloaded));
+    let mut stale: Vec<String> = before
+        .keys()
+        .chain(after.keys().filter(|key| !before.contains_key(*key)))
+        .filter(|key| *key != "watch" && !RELOADED_KEYS.contains(&key.as_str()))
+        .filter(|key| before.get(*key) != after.get(*key))
+        .cloned()
+        .collect();
+    if running.watch != reloaded.watch {
+        stale.push("watch".to_string());
+    }
+    stale.sort();
+    stale
+}
+
+/// What `--list-watchers` prints: the notify backend behind
+/// [`RecommendedWatcher`] on this platform and how it covers a tree, as
+/// `key: value` lines.
+fn backend_report() -> String {
+    let (backend, recursive) = match RecommendedWatcher::kind() {
+        WatcherKind::Inotify => (
+            "inotify",
+            "one OS watch per directory (see --max-dirs and fs.inotify.max_user_watches)",
+        ),
+        WatcherKind::Fsevent => ("fsevents", "native, one stream per root"),
+        WatcherKind::Kqueue => ("kqueue", "one open file descriptor per file and directory"),
+        WatcherKind::ReadDirectoryChangesWatcher => {
+            ("ReadDirectoryChangesW", "native, one handle per root")
+        }
+        WatcherKind::PollWatcher => ("poll", "rescans every watched tree periodically"),
+        _ => ("other", "unknown"),
+    };
+    let polling = matches!(RecommendedWatcher::kind(), WatcherKind::PollWatcher);
+    format!(
+        "backend: {backend}\nrecursive: {recursive}\npolling: {}\n",
+        if polling { "yes" } else { "no" }
+    )
+}
+
+/// How often `--rewatch` checks whether a deleted watch root is back.
+const REWATCH_POLL: Duration = Duration::from_millis(250);
+
+/// How often `--watch-extensions-only` looks for changes in the directories
+/// it left unwatched.
+const SKIPPED_POLL: Duration = Duration::from_secs(1);
+
+/// How often the loop checks for finished `--parallel` jobs to report.
+const JOB_POLL: Duration = Duration::from_millis(100);
+
+/// How often `--max-memory` samples Flash's own memory use.
+const MEMORY_POLL: Duration = Duration::from_secs(1);
+
+/// First wait before retrying a watch the OS had no room for.
+const WATCH_RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Longest wait between two watch retries.
+const WATCH_RETRY_BACKOFF_MAX: Duration = Duration::from_secs(1);
+
+/// A watch root's device, inode and birth time, which tell a directory
+/// deleted and recreated under the same path from the one watched. The inode
+/// alone won't do: a new directory often reuses the old one's number.
+type RootId = (u64, u64, Option<SystemTime>);
+
+#[cfg(unix)]
+fn root_id(root: &Path) -> Option<RootId> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = std::fs::metadata(root).ok()?;
+    Some((meta.dev(), meta.ino(), meta.created().ok()))
+}
+
+/// Elsewhere only the birth time, where the platform keeps one.
+#[cfg(not(unix))]
+fn root_id(root: &Path) -> Option<RootId> {
+    let meta = std::fs::metadata(root).ok()?;
+    Some((0, 0, meta.created().ok()))
+}
+
+/// Warn about watch roots that are gone, or were replaced by a new directory
+/// of the same name, and add them to `missing`. Called when an event names a
+/// path that is gone or a root itself, which is what deleting a root looks
+/// like from here.
+fn note_removed_roots(
+    identities: &[(PathBuf, Option<RootId>)],
+    missing: &mut Vec<PathBuf>,
+    rewatch: bool,
+    out: &LinePrefix,
+) {
+    for (root, id) in identities {
+        let now = root_id(root);
+        if (now.is_some() && now == *id) || missing.contains(root) {
+            continue;
+        }
+        if rewatch {
+            out.eprintln(format_args!(
+                "flash-watcher: watch root '{}' was removed; watching again once it is recreated",
+                display_path(root)
+            ));
+        } else {
+            out.eprintln(format_args!(
+                "flash-watcher: watch root '{}' was removed; changes under it are no longer \
+                 seen (see --rewatch)",
+                display_path(root)
+            ));
+        }
+        missing.push(root.clone());
+    }
+}
+
+/// Re-register the watch on every root in `missing` that exists again, and
+/// remember which directory it now is.
+fn restore_roots(
+    source: &mut EventSource,
+    missing: &mut Vec<PathBuf>,
+    identities: &mut [(PathBuf, Option<RootId>)],
+    filters: &Filters,
+    fast: bool,
+    out: &LinePrefix,
+) {
+    missing.retain(|root| {
+        let Some(id) = root_id(root) else {
+            return true;
+        };
+        // A root replaced before notify noticed may still hold the old watch.
+        let _ = source.watcher().unwatch(root);
+        match source.watcher().watch(root, filters.recursive_mode(root)) {
+            Ok(()) => {
+                for (known, was) in identities.iter_mut() {
+                    if known == root {
+                        *was = Some(id);
+                    }
+                }
+                if !fast {
+                    out.println(format!("watching '{}' again", display_path(root)).bright_green());
+                }
+                false
+            }
+            // Most likely removed again before we got to it; try next time.
+            Err(_) => true,
+        }
+    });
+}
+
+/// `--output`: whether `output` is newer than every one of `inputs`, as make
+/// would judge it. A missing output, or an input that is gone, is not.
+fn up_to_date(output: &Path, inputs: &[PathBuf]) -> bool {
+    let Some(built) = modified(output) else {
+        return false;
+    };
+    !inputs.is_empty()
+        && inputs
+            .iter()
+            .all(|input| modified(input).is_some_and(|changed| changed < built))
+}
+
+/// A batch of changes split up for `--command-from-matched-file`: each
+/// script found, in the order first needed, with the kinds of the changes it
+/// claimed, and then the kinds of the changes no script claimed.
+type ScriptBatch = (Vec<(PathBuf, Vec<ChangeKind>)>, Vec<ChangeKind>);
+
+/// Split a batch by the script each change runs.
+fn matched_scripts(
+    name: &str,
+    roots: &[PathBuf],
+    changes: &[(PathBuf, ChangeKind)],
+) -> ScriptBatch {
+    let mut scripts: Vec<(PathBuf, Vec<ChangeKind>)> = Vec::new();
+    let mut rest = Vec::new();
+    for (path, kind) in changes {
+        let Some(script) = nearest_script(name, roots, path) else {
+            rest.push(*kind);
+            continue;
+        };
+        match scripts.iter_mut().find(|(found, _)| *found == script) {
+            Some((_, kinds)) => kinds.push(*kind),
+            None => scripts.push((script, vec![*kind])),
+        }
+    }
+    (scripts, rest)
+}
+
+/// The script named `name` a change to `path` runs: the one in the changed
+/// entry's own directory, or failing that in the nearest parent, looking no
+/// higher than the watch root (the directory of a watched file).
+fn nearest_script(name: &str, roots: &[PathBuf], path: &Path) -> Option<PathBuf> {
+    let root = root_of(roots, path)?;
+    let top = if root.is_dir() { root } else { root.parent()? };
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(top))
+        .map(|dir| dir.join(name))
+        .find(|script| script.is_file())
+}
+
+/// `--verbose`: how long each startup phase took, shown before the `ready`
+/// line.
+struct StartupTimes {
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl StartupTimes {
+    fn new(started: Instant) -> Self {
+        Self {
+            started,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Note that `phase` ran from `since` until now.
+    fn record(&mut self, phase: &'static str, since: Instant) {
+        self.phases.push((phase, since.elapsed()));
+    }
+}
+
+impl fmt::Display for StartupTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "startup {SEP} ")?;
+        for (phase, took) in &self.phases {
+            write!(f, "{phase} {}, ", format_millis(*took))?;
+        }
+        write!(f, "{} in all", format_millis(self.started.elapsed()))
+    }
+}
+
+/// A short duration in milliseconds, or seconds from one second up.
+fn format_millis(d: Duration) -> String {
+    let ms = d.as_secs_f64() * 1000.0;
+    if ms < 1000.0 {
+        format!("{ms:.1}ms")
+    } else {
+        format!("{:.2}s", ms / 1000.0)
+    }
+}
+
+/// `--await-stable`: holds a run back until the files it is for stop
+/// changing, then recognises the events their writes left queued.
+struct Settler {
+    window: Duration,
+    /// Files the next run waits on.
+    waiting: Vec<PathBuf>,
+    /// How each file looked once it settled.
+    settled: HashMap<PathBuf, FileState>,
+}
+
+/// Size and modification time: what a write in progress keeps moving.
+type FileState = (u64, Option<SystemTime>);
+
+/// How many `--await-stable` windows a run waits on files that keep changing
+/// before it goes ahead anyway.
+const SETTLE_LIMIT_WINDOWS: u32 = 10;
+
+impl Settler {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            waiting: Vec::new(),
+            settled: HashMap::new(),
+        }
+    }
+
+    /// Queue `path` for the next run to wait on. False if it still looks as
+    /// it did when it last settled: the event is a leftover from writes
+    /// already waited out. Once it has moved on, it is forgotten.
+    fn note(&mut self, path: &Path) -> bool {
+        if let Some(settled) = self.settled.get(path) {
+            if Some(*settled) == file_state(path) {
+                return false;
+            }
+            self.settled.remove(path);
+        }
+        if !self.waiting.iter().any(|p| p == path) {
+            self.waiting.push(path.to_path_buf());
+        }
+        true
+    }
+
+    /// Block until every waiting file has held still for the window, polling
+    /// them all together. Gives up after [`SETTLE_LIMIT_WINDOWS`] windows, or
+    /// at once on Ctrl-C, and returns the files still changing then. A file
+    /// that is gone doesn't hold anything up. Only this batch's files are
+    /// remembered as settled afterwards.
+    fn settle(&mut self) -> Vec<PathBuf> {
+        let poll = (self.window / 5).clamp(Duration::from_millis(5), Duration::from_millis(100));
+        let started = Instant::now();
+        let deadline = started + self.window * SETTLE_LIMIT_WINDOWS;
+        // Each file's last state and since when it has held it.
+        let mut moving: Vec<(PathBuf, FileState, Instant)> = std::mem::take(&mut self.waiting)
+            .into_iter()
+            .filter_map(|path| file_state(&path).map(|state| (path, state, started)))
+            .collect();
+        self.settled.clear();
+        while !moving.is_empty() && Instant::now() < deadline && !INTERRUPTED.load(Ordering::SeqCst)
+        {
+            thread::sleep(poll);
+            let now = Instant::now();
+            moving.retain_mut(|(path, last, still_since)| match file_state(path) {
+                None => false,
+                Some(state) if state != *last => {
+                    *last = state;
+                    *still_since = now;
+                    true
+                }
+                Some(state) if now.duration_since(*still_since) >= self.window => {
+                    self.settled.insert(path.clone(), state);
+                    false
+                }
+                Some(_) => true,
+            });
+        }
+        moving.into_iter().map(|(path, ..)| path).collect()
+    }
+}
+
+fn file_state(path: &Path) -> Option<FileState> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.len(), meta.modified().ok()))
+}
+
+/// When the next run may start under `--throttle`, or `None` if it may start
+/// now.
+fn next_slot(throttle: Option<Duration>, last_run: Option<Instant>) -> Option<Instant> {
+    let at = last_run? + throttle?;
+    (at > Instant::now()).then_some(at)
+}
+
+/// Count one run against `--max-runs`; true once the budget is spent.
+fn use_up_run(runs_left: &mut Option<u64>) -> bool {
+    match runs_left {
+        Some(left) => {
+            *left = left.saturating_sub(1);
+            *left == 0
+        }
+        None => false,
+    }
+}
+
+/// Mirror the command's exit status. Signals and launch failures have no
+/// code of their own, so they map to a generic failure.
+fn exit_code(status: Option<ExitStatus>) -> ExitCode {
+    match status.and_then(|s| s.code()) {
+        Some(0) => ExitCode::SUCCESS,
+        Some(code) => ExitCode::from(u8::try_from(code).unwrap_or(1)),
+        None => ExitCode::FAILURE,
+    }
+}
+
+/// The notify front-end feeding the event loop. Held for as long as the loop
+/// runs; dropping it stops the underlying watcher.
+///
+/// A zero debounce window skips `notify-debouncer-mini` entirely and forwards
+/// every raw event, so nothing is coalesced or deduplicated.
+enum EventSource {
+    Debounced(Debouncer<RecommendedWatcher>),
+    Filtered(Debouncer<FrontWatcher>),
+    Raw(RecommendedWatcher),
+}
+
+/// Hands a batch of changes to the event loop, from the debouncer's thread
+/// or, for changes that skip it, from notify's.
+type Forward = Arc<Mutex<dyn FnMut(Vec<(PathBuf, ChangeKind)>) + Send>>;
+
+fn send(forward: &Forward, changes: Vec<(PathBuf, ChangeKind)>) {
+    if let Ok(mut forward) = forward.lock() {
+        forward(changes);
+    }
+}
+
+impl EventSource {
+    /// With `keep_dirs`, a directory reported alongside something inside it
+    /// is kept rather than dropped, for `--dirs-only`. `front` applies to raw
+    /// events before they are debounced.
+    fn new(
+        debounce: Duration,
+        canonicalize: bool,
+        keep_dirs: bool,
+        front: FrontFilter,
+        queue: EventQueue,
+        stats: Option<Arc<Mutex<Stats>>>,
+        out: LinePrefix,
+    ) -> Result<Self> {
+        let report = move |err: notify::Error| {
+            out.eprintln(format_args!("flash-watcher: watcher error: {err}"));
+        };
+        let mut canonical = canonicalize.then(Canonicalizer::default);
+        let forward: Forward = Arc::new(Mutex::new(move |changes: Vec<(PathBuf, ChangeKind)>| {
+            let events = changes.len();
+            let changes = match canonical.as_mut() {
+                Some(canonical) => canonical.resolve_all(changes),
+                None => changes,
+            };
+            let kept = if keep_dirs {
+                Vec::new()
+            } else {
+                drop_parent_dirs(changes.iter().map(|(path, _)| path.clone()).collect())
+            };
+            let mut pushed = 0;
+            let mut dropped = 0;
+            for (path, kind) in changes {
+                if keep_dirs || kept.contains(&path) {
+                    if !queue.push(path, kind) {
+                        dropped += 1;
+                    }
+                    pushed += 1;
+                }
+            }
+            if let Some(stats) = stats.as_ref() {
+                if let Ok(mut s) = stats.lock() {
+                    for _ in 0..events {
+                        s.record_event();
+                    }
+                    s.record_coalesced((events - pushed) as u64);
+                    s.record_dropped(dropped);
+                }
+            }
+        }));
+
+        if debounce.is_zero() {
+            let watcher =
+                notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+                    match result {
+                        Ok(event) if front.content_only && !is_content_change(&event.kind) => {}
+                        Ok(event) => send(&forward, raw_changes(event)),
+                        Err(err) => report(err),
+                    }
+                })
+                .context("creating watcher")?;
+            return Ok(Self::Raw(watcher));
+        }
+
+        let debounced = forward.clone();
+        let handler = move |result: DebounceEventResult| match result {
+            Ok(events) => send(
+                &debounced,
+                events
+                    .into_iter()
+                    .map(|e| {
+                        let kind = infer_kind(&e.path, debounce);
+                        (e.path, kind)
+                    })
+                    .collect(),
+            ),
+            Err(err) => report(err),
+        };
+        let config = notify_debouncer_mini::Config::default().with_timeout(debounce);
+        if !front.content_only && front.urgent.is_none() {
+            return Ok(Self::Debounced(
+                new_debouncer_opt(config, handler).context("creating debounced watcher")?,
+            ));
+        }
+        NEXT_FRONT.with(|next| *next.borrow_mut() = Some((front, forward)));
+        let debouncer = new_debouncer_opt(config, handler);
+        NEXT_FRONT.with(|next| next.borrow_mut().take());
+        Ok(Self::Filtered(
+            debouncer.context("creating debounced watcher")?,
+        ))
+    }
+
+    fn watcher(&mut self) -> &mut dyn Watcher {
+        match self {
+            Self::Debounced(debouncer) => debouncer.watcher(),
+            Self::Filtered(debouncer) => debouncer.watcher(),
+            Self::Raw(watcher) => watcher,
+        }
+    }
+}
+
+/// What [`FrontWatcher`] does with raw events before the debouncer sees
+/// them.
+#[derive(Default)]
+struct FrontFilter {
+    /// Drop metadata-only events (`--content-only`).
+    content_only: bool,
+    /// Paths that skip the debounce (`--no-debounce-pattern`): a filter made
+    /// of those patterns, and the watch roots paths are matched under.
+    urgent: Option<(Filter, Vec<PathBuf>)>,
+}
+
+impl FrontFilter {
+    fn is_urgent(&self, path: &Path) -> bool {
+        self.urgent
+            .as_ref()
+            .is_some_and(|(filter, roots)| roots.iter().any(|root| filter.accepts_in(root, path)))
+    }
+}
+
+/// Whether a raw notify event could have changed what a file holds or what
+/// it is called. Permission, ownership, and access-time updates all arrive
+/// as `Modify(Metadata(_))`, whatever the platform backend.
+fn is_content_change(kind: &EventKind) -> bool {
+    !matches!(kind, EventKind::Modify(ModifyKind::Metadata(_)))
+}
+
+/// The platform watcher with a [`FrontFilter`] applied to its raw events.
+/// The debouncer only reports paths, so by the time a batch reaches
+/// [`EventSource`] it is too late to tell a chmod from a write, or to skip
+/// the wait; this sits between notify and the debouncer instead.
+struct FrontWatcher(RecommendedWatcher);
+
+thread_local! {
+    /// The filter, and where urgent changes go, for the next [`FrontWatcher`]
+    /// made on this thread. The debouncer builds its watcher itself through
+    /// [`Watcher::new`], so this is how they get handed over.
+    static NEXT_FRONT: RefCell<Option<(FrontFilter, Forward)>> = const { RefCell::new(None) };
+}
+
+impl Watcher for FrontWatcher {
+    fn new<F: notify::EventHandler>(
+        mut handler: F,
+        config: notify::Config,
+    ) -> notify::Result<Self> {
+        let (filter, forward) = NEXT_FRONT
+            .with(|next| next.borrow_mut().take())
+            .ok_or_else(|| notify::Error::generic("no filter for the watcher"))?;
+        let watcher = RecommendedWatcher::new(
+            move |mut result: notify::Result<notify::Event>| {
+                if let Ok(event) = result.as_mut() {
+                    if filter.content_only && !is_content_change(&event.kind) {
+                        return;
+                    }
+                    let (urgent, rest): (Vec<_>, Vec<_>) = raw_changes(event.clone())
+                        .into_iter()
+                        .partition(|(path, _)| filter.is_urgent(path));
+                    if !urgent.is_empty() {
+                        send(&forward, urgent);
+                        if rest.is_empty() {
+                            return;
+                        }
+                        event.paths.retain(|path| !filter.is_urgent(path));
+                    }
+                }
+                handler.handle_event(result);
+            },
+            config,
+        )?;
+        Ok(Self(watcher))
+    }
+
+    fn watch(&mut self, path: &Path, recursive_mode: RecursiveMode) -> notify::Result<()> {
+        self.0.watch(path, recursive_mode)
+    }
+
+    fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        self.0.unwatch(path)
+    }
+
+    fn configure(&mut self, option: notify::Config) -> notify::Result<bool> {
+        self.0.configure(option)
+    }
+
+    fn kind() -> WatcherKind {
+        RecommendedWatcher::kind()
+    }
+}
+
+/// The paths of one raw notify event with what happened to each. A rename
+/// reported as one event removes its first path and creates its second.
+fn raw_changes(event: notify::Event) -> Vec<(PathBuf, ChangeKind)> {
+    let kind = match event.kind {
+        EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            ChangeKind::Create
+        }
+        EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            ChangeKind::Remove
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let kinds = [ChangeKind::Remove, ChangeKind::Create];
+            return event.paths.into_iter().zip(kinds).collect();
+        }
+        _ => ChangeKind::Modify,
+    };
+    event.paths.into_iter().map(|path| (path, kind)).collect()
+}
+
+/// Guess what happened to a path the debouncer reported, which it does
+/// without saying how it changed. See [`ChangeKind`] for the rules.
+fn infer_kind(path: &Path, debounce: Duration) -> ChangeKind {
+    let Ok(meta) = path.symlink_metadata() else {
+        return ChangeKind::Remove;
+    };
+    let (Ok(born), Ok(written)) = (meta.created(), meta.modified()) else {
+        return ChangeKind::Modify;
+    };
+    match written.duration_since(born) {
+        Ok(age) if age > debounce => ChangeKind::Modify,
+        _ => ChangeKind::Create,
+    }
+}
+
+/// The sending half of the bounded channel between notify and the event
+/// loop. A full channel means a run is already pending, so rather than
+/// blocking notify's thread or growing without bound, extra changes are
+/// dropped and counted.
+#[derive(Clone)]
+struct EventQueue {
+    tx: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl EventQueue {
+    fn new(tx: SyncSender<Message>) -> Self {
+        Self {
+            tx,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Queue a change. Returns false if it was dropped.
+    fn push(&self, path: PathBuf, kind: ChangeKind) -> bool {
+        if let Err(TrySendError::Full(_)) = self.tx.try_send(Message::Change(path, kind)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        true
+    }
+
+    /// Whether a change was dropped since [`take_dropped`](Self::take_dropped)
+    /// last ran. Changes are queued before they are filtered, so a burst
+    /// the filters reject can crowd out one they would have let through.
+    fn overflowed(&self) -> bool {
+        self.dropped.load(Ordering::Relaxed) > 0
+    }
+
+    /// How many changes were dropped since the last call.
+    fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Rewrites event paths through their canonical parent directory, so a file
+/// reached via a symlinked directory and directly reads the same both ways.
+/// Only the parent is resolved: the file may be gone already, and a symlinked
+/// file keeps its own name. Parents are cached, since a burst of events tends
+/// to land in a handful of directories.
+#[derive(Debug, Default)]
+struct Canonicalizer {
+    parents: HashMap<PathBuf, PathBuf>,
+}
+
+impl Canonicalizer {
+    /// Past this many cached parents, start over rather than grow forever.
+    const CAPACITY: usize = 4096;
+
+    fn resolve(&mut self, path: PathBuf) -> PathBuf {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return path;
+        };
+        if let Some(resolved) = self.parents.get(parent) {
+            return resolved.join(name);
+        }
+        // A parent that is gone too keeps the raw path.
+        let Ok(resolved) = parent.canonicalize() else {
+            return path;
+        };
+        if self.parents.len() >= Self::CAPACITY {
+            self.parents.clear();
+        }
+        self.parents.insert(parent.to_path_buf(), resolved.clone());
+        resolved.join(name)
+    }
+
+    /// Resolve every path in a batch, dropping the duplicates that two
+    /// spellings of one file turn into. The first spelling's kind is kept.
+    fn resolve_all(&mut self, changes: Vec<(PathBuf, ChangeKind)>) -> Vec<(PathBuf, ChangeKind)> {
+        let mut resolved: Vec<(PathBuf, ChangeKind)> = Vec::with_capacity(changes.len());
+        for (path, kind) in changes {
+            let path = self.resolve(path);
+            if !resolved.iter().any(|(seen, _)| *seen == path) {
+                resolved.push((path, kind));
+            }
+        }
+        resolved
+    }
+}
+
+/// Drop directories that arrive alongside an event for something inside them.
+/// Editing a file often bumps its parent's mtime too, and the two paths would
+/// otherwise coalesce into two runs for one change. A directory event on its
+/// own is kept.
+pub(crate) fn drop_parent_dirs(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    if paths.len() < 2 {
+        return paths;
+    }
+    let parents: HashSet<PathBuf> = paths
+        .iter()
+        .flat_map(|path| path.ancestors().skip(1))
+        .map(Path::to_path_buf)
+        .collect();
+    paths.retain(|path| !parents.contains(path));
+    paths
+}
+
+/// Translate the user's `--watch` arguments into actual paths to hand to
+/// `notify`, along with each glob among them. Globs are expanded by
+/// [`expand_watch_glob`], skipping what `prune` ignores, and the pattern
+/// itself is enforced by [`Filters`]. Literal paths that do not exist are an
+/// error — issue #1.
+fn resolve_watch_roots(
+    specs: &[String],
+    recursive: bool,
+    ignore_case: bool,
+    prune: &Filter,
+) -> Result<(Vec<PathBuf>, Vec<WatchGlob>)> {
+    if specs.is_empty() {
+        return Ok((vec![PathBuf::from(".")], Vec::new()));
+    }
+
+    let mut roots: Vec<PathBuf> = Vec::with_capacity(specs.len());
+    let mut globs = Vec::new();
+    for spec in specs {
+        let resolved = if looks_like_glob(spec) {
+            let glob = expand_watch_glob(spec, recursive, ignore_case, prune)?;
+            let resolved = glob.roots.clone();
+            globs.push(glob);
+            resolved
+        } else {
+            vec![resolve_watch_root(spec)?]
+        };
+        for canonical in resolved {
+            if !roots.iter().any(|p| p == &canonical) {
+                roots.push(canonical);
+            }
+        }
+    }
+    Ok((roots, globs))
+}
+
+/// A `--watch` glob resolved against the file system.
+struct WatchGlob {
+    /// Directories to watch: each matched directory, and the parent of each
+    /// matched file. If nothing matches yet, the glob's fixed prefix.
+    roots: Vec<PathBuf>,
+    /// The glob made absolute, so it can act as the include pattern for
+    /// events under `roots`.
+    pattern: String,
+    /// The glob has no `**`, so it reaches a fixed depth: each `*` stays
+    /// within one path component, and `roots` are watched non-recursively.
+    shallow: bool,
+}
+
+/// Resolve a `--watch` glob. Roots nested inside another root are dropped when
+/// `recursive`, since the outer watch already covers them. Symlinks are not
+/// followed, nor are directories `prune` ignores as a whole.
+///
+/// The glob is matched once, at startup: a directory created later that
+/// would match is not watched until Flash restarts.
+///
+/// A glob without `**` is taken literally: `src/*` matches the direct
+/// children of `src` and nothing below them, so only as many levels are
+/// walked as the glob has components. With `ignore_case`, the part after
+/// the fixed prefix matches names regardless of case.
+fn expand_watch_glob(
+    spec: &str,
+    recursive: bool,
+    ignore_case: bool,
+    prune: &Filter,
+) -> Result<WatchGlob> {
+    let base = resolve_watch_root(spec)?;
+    let rest = split_glob(spec).1;
+    let shallow = !rest.split('/').any(|component| component.contains("**"));
+    let depth = rest.split('/').count();
+    let pattern = format!("{}/{rest}", globset::escape(&base.to_string_lossy()));
+    let matcher = GlobBuilder::new(&pattern)
+        .literal_separator(shallow)
+        .case_insensitive(ignore_case)
+        .build()
+        .with_context(|| format!("invalid watch pattern '{spec}'"))?
+        .compile_matcher();
+
+    let mut roots = Vec::new();
+    let mut pending = vec![(base.clone(), 1)];
+    while let Some((dir, level)) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() && prune.ignores_subtree(&path, Some(&base)) {
+                continue;
+            }
+            let target = if !matcher.is_match(&path) {
+                None
+            } else if file_type.is_dir() {
+                Some(path.clone())
+            } else {
+                Some(dir.clone())
+            };
+            if let Some(target) = target.filter(|t| !roots.contains(t)) {
+                roots.push(target);
+            }
+            if file_type.is_dir() && !(shallow && level >= depth) {
+                pending.push((path, level + 1));
+            }
+        }
+    }
+
+    if roots.is_empty() {
+        roots.push(base);
+    } else if recursive && !shallow {
+        let all = roots.clone();
+        roots.retain(|root| {
+            !all.iter()
+                .any(|other| other != root && root.starts_with(other))
+        });
+    }
+    roots.sort();
+    Ok(WatchGlob {
+        roots,
+        pattern,
+        shallow,
+    })
+}
+
+fn resolve_watch_root(spec: &str) -> Result<PathBuf> {
+    let root = if looks_like_glob(spec) {
+        split_glob(spec).0
+    } else {
+        PathBuf::from(spec)
+    };
+
+    if !root.exists() {
+        if looks_like_glob(spec) {
+            bail!(
+                "watch pattern '{spec}' has no existing root directory (tried '{}')",
+                root.display()
+            );
+        }
+        bail!("watch path '{spec}' does not exist");
+    }
+
+    Ok(root.canonicalize().unwrap_or(root))
+}
+
+/// The global [`Filter`] plus one per config-file watch entry that declared
+/// its own. Events are judged by the filter of the root they arrived under.
+struct Filters {
+    global: Filter,
+    scoped: Vec<(PathBuf, Filter)>,
+    /// `--no-recursive`: only direct children of a root count.
+    shallow: bool,
+    /// Roots of `--watch` globs without `**`, where the same holds, each
+    /// with the glob it came from.
+    shallow_roots: Vec<(PathBuf, String)>,
+    entries: EntryKind,
+    /// `--git-tracked-only`: what git tracks under the watch roots.
+    tracked: Option<Tracked>,
+    /// What to watch: every `--watch` path, with globs expanded once here.
+    roots: Vec<PathBuf>,
+}
+
+impl Filters {
+    fn build(settings: &Settings) -> Result<Self> {
+        let mut from_file = match &settings.ignore_file {
+            Some(path) => read_ignore_file(path)?,
+            None => Vec::new(),
+        };
+        from_file.extend(output_dir_patterns(&settings.exclude_output)?);
+        // Otherwise every line logged, or every build, would report its own
+        // write.
+        from_file.extend(output_dir_patterns(settings.log_file.as_slice())?);
+        from_file.extend(output_dir_patterns(settings.output.as_slice())?);
+        let mut include = settings.include.clone();
+        if let Some(path) = &settings.pattern_file {
+            include.extend(read_pattern_file(path)?);
+        }
+        let compile = |extensions: &[String], include: &[String], ignore: &[String]| {
+            let mut ignore = ignore.to_vec();
+            ignore.extend(from_file.iter().cloned());
+            if settings.ignore_editor_temp {
+                ignore.extend(EDITOR_TEMP_PATTERNS.iter().map(|p| p.to_string()));
+            }
+            Filter::new(extensions, include, &ignore).map(|filter| {
+                filter
+                    .include_hidden(settings.include_hidden)
+                    .ignore_case(settings.ignore_case_paths)
+            })
+        };
+
+        let global = compile(&settings.extensions, &include, &settings.ignore)?;
+        let mut scoped = Vec::with_capacity(settings.scoped.len());
+        for entry in &settings.scoped {
+            let extensions = match &entry.ext {
+                Some(raw) => parse_extensions(raw),
+                None => settings.extensions.clone(),
+            };
+            let filter = compile(
+                &extensions,
+                entry.pattern.as_ref().unwrap_or(&include),
+                entry.ignore.as_ref().unwrap_or(&settings.ignore),
+            )?;
+            scoped.push((resolve_watch_root(&entry.path)?, filter));
+        }
+        let (roots, globs) = resolve_watch_roots(
+            &settings.watch,
+            settings.recursive,
+            settings.ignore_case_paths,
+            &global,
+        )?;
+        // Under the roots a `--watch` glob expands to, a path must match the
+        // glob as well as the include patterns.
+        let mut shallow_roots = Vec::new();
+        for glob in globs {
+            if glob.shallow {
+                shallow_roots.extend(glob.roots.iter().map(|r| (r.clone(), glob.pattern.clone())));
+            }
+            // Every root a glob expands to shares the one filter.
+            let filter = compile(&settings.extensions, &include, &settings.ignore)?
+                .watch_glob(&glob.pattern)?;
+            for root in glob.roots {
+                scoped.push((root, filter.clone()));
+            }
+        }
+        let tracked = if settings.git_tracked_only {
+            Some(Tracked::load(&roots)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            global,
+            scoped,
+            shallow: !settings.recursive,
+            shallow_roots,
+            entries: settings.entries,
+            tracked,
+            roots,
+        })
+    }
+
+    /// How to watch `root`: non-recursively under `--no-recursive` or when
+    /// it came from a glob without `**`.
+    fn recursive_mode(&self, root: &Path) -> RecursiveMode {
+        if self.is_shallow(root) {
+            RecursiveMode::NonRecursive
+        } else {
+            RecursiveMode::Recursive
+        }
+    }
+
+    fn is_shallow(&self, root: &Path) -> bool {
+        self.shallow || self.shallow_roots.iter().any(|(r, _)| r == root)
+    }
+
+    fn accepts(&self, roots: &[PathBuf], path: &Path) -> bool {
+        self.explain(roots, path).is_accepted()
+    }
+
+    /// Whether the ignore patterns reject everything under the directory
+    /// `dir`, so it needn't be watched.
+    fn prunes(&self, roots: &[PathBuf], dir: &Path) -> bool {
+        match root_of(roots, dir) {
+            Some(root) => self.filter_for(root).ignores_subtree(dir, Some(root)),
+            None => self.global.ignores_subtree(dir, None),
+        }
+    }
+
+    fn prunes_any(&self) -> bool {
+        self.global.ignores_any_subtree()
+            || self.scoped.iter().any(|(_, f)| f.ignores_any_subtree())
+    }
+
+    /// The filter for events under `root`: its own, or the global one.
+    fn filter_for(&self, root: &Path) -> &Filter {
+        self.scoped
+            .iter()
+            .find(|(scoped_root, _)| scoped_root == root)
+            .map_or(&self.global, |(_, filter)| filter)
+    }
+
+    fn explain(&self, roots: &[PathBuf], path: &Path) -> PathDecision {
+        let decision = self.explain_path(roots, path);
+        if decision.is_accepted() && self.tracked.as_ref().is_some_and(|t| !t.contains(path)) {
+            return PathDecision::Rejected {
+                reason: RejectReason::Untracked,
+            };
+        }
+        // Last, as the only check that touches the file system.
+        if decision.is_accepted() {
+            if let Some(reason) = self.entries.check(path) {
+                return PathDecision::Rejected { reason };
+            }
+        }
+        decision
+    }
+
+    fn explain_path(&self, roots: &[PathBuf], path: &Path) -> PathDecision {
+        let Some(root) = root_of(roots, path) else {
+            return self.global.explain(path, None);
+        };
+        // Some backends still report nested events for a non-recursive watch.
+        if self.is_shallow(root) && path.parent() != Some(root) {
+            let glob = self
+                .shallow_roots
+                .iter()
+                .find(|(r, _)| r == root)
+                .map(|(_, glob)| glob.clone());
+            return PathDecision::Rejected {
+                reason: RejectReason::NotDirectChild(glob.filter(|_| !self.shallow)),
+            };
+        }
+        self.filter_for(root).explain(path, Some(root))
+    }
+}
+
+/// Ignore globs for `--exclude-output`: each directory and everything under
+/// it, spelled the way events arrive. The directory may not exist yet, as
+/// before a first build.
+fn output_dir_patterns(dirs: &[PathBuf]) -> Result<Vec<String>> {
+    let mut patterns = Vec::with_capacity(dirs.len() * 2);
+    for dir in dirs {
+        let dir =
+            std::path::absolute(dir).with_context(|| format!("resolving '{}'", dir.display()))?;
+        let dir = dir.canonicalize().unwrap_or_else(|_| {
+            match (
+                dir.parent().and_then(|p| p.canonicalize().ok()),
+                dir.file_name(),
+            ) {
+                (Some(parent), Some(name)) => parent.join(name),
+                _ => dir.clone(),
+            }
+        });
+        let dir = globset::escape(&dir.to_string_lossy());
+        patterns.push(format!("{dir}/**"));
+        patterns.push(dir);
+    }
+    Ok(patterns)
+}
+
+/// The watch root an event path lives under. Roots can nest, so the longest
+/// match wins — patterns should be relative to the most specific root.
+pub(crate) fn root_of<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(PathBuf::as_path)
+}
+
+/// Fail before handing anything to notify if the roots hold more than `limit`
+/// directories. Recursive watches cost one OS watch per directory, and running
+/// out halfway through produces an error that names neither cause nor cure.
+fn check_dir_budget(roots: &[PathBuf], limit: usize) -> Result<()> {
+    let mut seen = 0;
+    for root in roots {
+        seen += count_dirs(root, limit - seen.min(limit));
+        if seen > limit {
+            bail!(
+                "'{}' contains more than {limit} directories (the --max-dirs limit); \
+                 point --watch at the directories you need, or raise --max-dirs",
+                root.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Count `root` and the directories beneath it, stopping as soon as the count
+/// passes `limit`. Symlinks are not followed.
+fn count_dirs(root: &Path, limit: usize) -> usize {
+    let mut count = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        count += 1;
+        if count > limit {
+            break;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+    count
+}
+
+/// Directories watched one by one rather than each root recursively,
+/// leaving out subtrees the ignore patterns reject wholesale and, with
+/// `--watch-extensions-only`, those without a file the filters accept.
+struct PrunedWatch {
+    watched: HashSet<PathBuf>,
+    /// Directories the OS had no watches left for, even after retrying.
+    unwatched: Vec<(PathBuf, notify::Error)>,
+    /// Directories seen by the startup walk, for the banner.
+    scanned: usize,
+    /// Directories left out for holding no file the filters accept, with
+    /// their modification time when last looked at. Adding a file changes
+    /// it, though no watch reports that.
+    skipped: HashMap<PathBuf, Option<SystemTime>>,
+    /// When [`rescan`](Self::rescan) last looked at `skipped`.
+    rescanned: Instant,
+}
+
+impl PrunedWatch {
+    /// Walk `roots` and `watch` each directory worth it. Directories the OS
+    /// is out of watches for are left out, as long as one is watched; any
+    /// other failure stops startup.
+    fn start(
+        roots: &[PathBuf],
+        filters: &Filters,
+        extensions_only: bool,
+        retries: u32,
+        mut watch: impl FnMut(&Path) -> notify::Result<()>,
+    ) -> Result<Self> {
+        let mut watched = HashSet::new();
+        let mut unwatched = Vec::new();
+        let mut scanned = 0;
+        let mut skipped = Vec::new();
+        for root in roots {
+            let mut dirs = Vec::new();
+            if filters.is_shallow(root) {
+                scanned += 1;
+            } else {
+                scanned += dirs_worth_watching(
+                    root,
+                    roots,
+                    filters,
+                    extensions_only,
+                    &mut dirs,
+                    &mut skipped,
+                );
+            }
+            // The root itself, even if empty, so new entries in it are seen.
+            if dirs.last() != Some(root) {
+                dirs.push(root.clone());
+            }
+            for dir in dirs {
+                match watch_with_retry(retries, || watch(&dir)) {
+                    Ok(()) => {
+                        watched.insert(dir);
+                    }
+                    Err(err) if out_of_watches(&err) => unwatched.push((dir, err)),
+                    Err(err) => {
+                        return Err(explain_watch_error(err))
+                            .with_context(|| format!("watching '{}'", dir.display()));
+                    }
+                }
+            }
+        }
+        if watched.is_empty() && !unwatched.is_empty() {
+            let (dir, err) = unwatched.swap_remove(0);
+            return Err(explain_watch_error(err))
+                .with_context(|| format!("watching '{}'", dir.display()));
+        }
+        let skipped = skipped
+            .into_iter()
+            .filter(|dir| !watched.contains(dir))
+            .map(|dir| {
+                let modified = modified(&dir);
+                (dir, modified)
+            })
+            .collect();
+        Ok(Self {
+            watched,
+            unwatched,
+            scanned,
+            skipped,
+            rescanned: Instant::now(),
+        })
+    }
+
+    /// Every [`SKIPPED_POLL`], look again at skipped directories whose
+    /// contents changed. One that now holds a file the filters accept is
+    /// watched, along with the skipped directories above it, and the files
+    /// found in them are queued as created: they appeared while unwatched.
+    fn rescan(
+        &mut self,
+        source: &mut EventSource,
+        roots: &[PathBuf],
+        filters: &Filters,
+        queue: &EventQueue,
+    ) {
+        if self.rescanned.elapsed() < SKIPPED_POLL {
+            return;
+        }
+        self.rescanned = Instant::now();
+        let changed: Vec<PathBuf> = self
+            .skipped
+            .iter()
+            .filter(|(dir, was)| modified(dir) != **was)
+            .map(|(dir, _)| dir.clone())
+            .collect();
+        for dir in changed {
+            if self.skipped.remove(&dir).is_none() || !dir.is_dir() {
+                continue;
+            }
+            let mut worth = Vec::new();
+            let mut skipped = Vec::new();
+            dirs_worth_watching(&dir, roots, filters, true, &mut worth, &mut skipped);
+            for below in skipped {
+                if !worth.contains(&below) {
+                    let modified = modified(&below);
+                    self.skipped.entry(below).or_insert(modified);
+                }
+            }
+            if worth.is_empty() {
+                continue;
+            }
+            let mut above = dir.parent();
+            while let Some(parent) = above {
+                if self.skipped.remove(parent).is_none() {
+                    break;
+                }
+                worth.push(parent.to_path_buf());
+                above = parent.parent();
+            }
+            for dir in worth {
+                self.skipped.remove(&dir);
+                if self.watched.contains(&dir)
+                    || source
+                        .watcher()
+                        .watch(&dir, RecursiveMode::NonRecursive)
+                        .is_err()
+                {
+                    continue;
+                }
+                for entry in std::fs::read_dir(&dir).into_iter().flatten().flatten() {
+                    let path = entry.path();
+                    if entry.file_type().is_ok_and(|t| t.is_file()) && filters.accepts(roots, &path)
+                    {
+                        queue.push(path, ChangeKind::Create);
+                    }
+                }
+                self.watched.insert(dir);
+            }
+        }
+    }
+
+    /// Watch a directory created since the startup walk, with everything in
+    /// it that isn't ignored: it is new, so whatever lands in it next is
+    /// worth seeing.
+    fn adopt(
+        &mut self,
+        source: &mut EventSource,
+        roots: &[PathBuf],
+        filters: &Filters,
+        path: &Path,
+    ) {
+        if !path.is_dir() || self.watched.contains(path) || filters.prunes(roots, path) {
+            return;
+        }
+        let mut pending = vec![path.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            if source
+                .watcher()
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .is_err()
+            {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                self.watched.insert(dir);
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if entry.file_type().is_ok_and(|t| t.is_dir()) && !filters.prunes(roots, &path) {
+                    pending.push(path);
+                }
+            }
+            self.watched.insert(dir);
+        }
+    }
+}
+
+/// Collect into `out` the directories under `dir` (itself included) outside
+/// wholly ignored subtrees, children before parents. With `extensions_only`,
+/// only those that hold, at any depth, a file the filters accept; the rest go
+/// into `skipped`. Returns how many directories were seen; an ignored one
+/// counts, but not what is in it. Symlinks are not followed.
+fn dirs_worth_watching(
+    dir: &Path,
+    roots: &[PathBuf],
+    filters: &Filters,
+    extensions_only: bool,
+    out: &mut Vec<PathBuf>,
+    skipped: &mut Vec<PathBuf>,
+) -> usize {
+    let mut walked = 1;
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return walked;
+    };
+    let mut worth = !extensions_only;
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() && filters.prunes(roots, &path) {
+            walked += 1;
+        } else if file_type.is_dir() {
+            let before = out.len();
+            walked += dirs_worth_watching(&path, roots, filters, extensions_only, out, skipped);
+            worth |= out.len() > before;
+        } else if file_type.is_file() && !worth {
+            worth = filters.accepts(roots, &path);
+        }
+    }
+    if worth {
+        out.push(dir.to_path_buf());
+    } else {
+        skipped.push(dir.to_path_buf());
+    }
+    walked
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Files under `roots` (or, without `recursive`, directly in them) modified
+/// within the last `window` that pass the filters, sorted. Symlinks are not
+/// followed.
+fn changed_within(
+    roots: &[PathBuf],
+    window: Duration,
+    recursive: bool,
+    filters: &Filters,
+) -> Vec<PathBuf> {
+    let cutoff = SystemTime::now()
+        .checked_sub(window)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut found = Vec::new();
+    let mut pending = roots.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                if recursive {
+                    pending.push(path);
+                }
+            } else if file_type.is_file()
+                && entry
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| modified >= cutoff)
+                && filters.accepts(roots, &path)
+            {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    found
+}
+
+/// Whether `err` means the OS is out of watches or descriptors, which may
+/// pass as other processes let go of theirs.
+fn out_of_watches(err: &notify::Error) -> bool {
+    match &err.kind {
+        notify::ErrorKind::MaxFilesWatch => true,
+        // EMFILE (too many open files) and ENOSPC (inotify watch limit).
+        notify::ErrorKind::Io(io) => cfg!(unix) && matches!(io.raw_os_error(), Some(24 | 28)),
+        _ => false,
+    }
+}
+
+/// Call `watch` until it succeeds, retrying up to `retries` times while it
+/// fails with [`out_of_watches`]. The wait doubles after each attempt,
+/// starting at [`WATCH_RETRY_BACKOFF`] and stopping at [`WATCH_RETRY_BACKOFF_MAX`].
+fn watch_with_retry(
+    retries: u32,
+    mut watch: impl FnMut() -> notify::Result<()>,
+) -> notify::Result<()> {
+    let mut backoff = WATCH_RETRY_BACKOFF;
+    let mut attempt = 0;
+    loop {
+        match watch() {
+            Err(err) if attempt < retries && out_of_watches(&err) => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(WATCH_RETRY_BACKOFF_MAX);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Turn OS watch-limit failures into an error that says what to do about them.
+fn explain_watch_error(err: notify::Error) -> anyhow::Error {
+    if out_of_watches(&err) {
+        anyhow::anyhow!(
+            "{err}: the OS ran out of file watches; narrow --watch to fewer directories \
+             or raise the limit (fs.inotify.max_user_watches / ulimit -n)"
+        )
+    } else {
+        err.into()
+    }
+}
+
+fn looks_like_glob(spec: &str) -> bool {
+    spec.chars().any(|c| matches!(c, '*' | '?' | '[' | '{'))
+}
+
+/// Split a glob at its first wildcard component: the longest leading prefix
+/// with no wildcard characters, and the rest. `src/**/*.rs` -> `src` and
+/// `**/*.rs`; `**/foo` -> `.` and `**/foo`; `a/b/*.c` -> `a/b` and `*.c`.
+fn split_glob(pattern: &str) -> (PathBuf, String) {
+    let mut root = PathBuf::new();
+    let mut rest: Vec<String> = Vec::new();
+    for component in Path::new(pattern).components() {
+        let s = component.as_os_str().to_string_lossy();
+        if rest.is_empty() && !looks_like_glob(&s) {
+            root.push(component);
+        } else {
+            rest.push(s.into_owned());
+        }
+    }
+    if root.as_os_str().is_empty() {
+        root = PathBuf::from(".");
+    }
+    (root, rest.join("/"))
+}
+
+fn print_banner(settings: &Settings, roots: &[PathBuf], out: &LinePrefix) {
+    if settings.fast || settings.stats {
+        return;
+    }
+    out.println(
+        format!("flash {SEP} watching for changes")
+            .bright_green()
+            .bold(),
+    );
+    let (shown, more) = banner_roots(roots, settings.verbose);
+    for root in &shown {
+        out.println(format_args!("  {} {root}", BULLET.bright_blue()));
+    }
+    if more > 0 {
+        out.println(format_args!(
+            "  {} {more} more, {} in all (--verbose lists them)",
+            BULLET.bright_blue(),
+            shown.len() + more
+        ));
+    }
+    if !settings.extensions.is_empty() {
+        out.println(format_args!(
+            "  {} {}",
+            "ext".bright_blue(),
+            settings.extensions.join(",")
+        ));
+    }
+    if !settings.include.is_empty() {
+        out.println(format_args!(
+            "  {} {}",
+            "include".bright_blue(),
+            settings.include.join(", ")
+        ));
+    }
+    if !settings.ignore.is_empty() {
+        out.println(format_args!(
+            "  {} {}",
+            "ignore".bright_blue(),
+            settings.ignore.join(", ")
+        ));
+    }
+    if settings.count_only {
+        out.println(format_args!(
+            "  {} {}",
+            "run".bright_blue(),
+            "nothing, counting changes".bright_yellow()
+        ));
+        return;
+    }
+    out.println(format_args!(
+        "  {} {}",
+        "run".bright_blue(),
+        settings.command.join(" ").bright_yellow()
+    ));
+}
+
+/// Watch roots the banner lists before summing up the rest.
+const BANNER_ROOTS: usize = 5;
+
+/// The watch roots for the banner, sorted and without repeats, and how many
+/// more are left out. A glob can expand to hundreds of directories; unless
+/// `verbose`, only the first [`BANNER_ROOTS`] are named.
+fn banner_roots(roots: &[PathBuf], verbose: bool) -> (Vec<String>, usize) {
+    let mut shown: Vec<String> = roots.iter().map(|root| display_path(root)).collect();
+    shown.sort();
+    shown.dedup();
+    let more = if verbose {
+        0
+    } else {
+        shown.len().saturating_sub(BANNER_ROOTS)
+    };
+    shown.truncate(shown.len() - more);
+    (shown, more)
+}
+
+/// Empty the channel without blocking, offering each path to `accept`.
+/// True if anything was accepted. An interrupt is left to the caller, which
+/// checks [`INTERRUPTED`] after every run.
+fn drain_pending(
+    rx: &Receiver<Message>,
+    mut accept: impl FnMut(&Path, ChangeKind) -> bool,
+) -> bool {
+    let mut any = false;
+    while let Ok(message) = rx.try_recv() {
+        if let Message::Change(path, kind) = message {
+            any |= accept(&path, kind);
+        }
+    }
+    any
+}
+
+/// Add a change to the next run's batch. A path already in it keeps its
+/// place and takes the kind both changes add up to ([`ChangeKind::then`]),
+/// so a run reports each path once.
+fn add_change(batch: &mut Vec<(PathBuf, ChangeKind)>, path: PathBuf, kind: ChangeKind) {
+    match batch.iter_mut().find(|(pending, _)| *pending == path) {
+        Some((_, earlier)) => *earlier = earlier.then(kind),
+        None => batch.push((path, kind)),
+    }
+}
+
+/// Count an accepted change for `--stats` and hand it to the sink.
+fn report_change(
+    sink: &dyn EventSink,
+    stats: Option<&Arc<Mutex<Stats>>>,
+    roots: &[PathBuf],
+    path: &Path,
+    kind: ChangeKind,
+) {
+    if let Some(stats) = stats {
+        if let Ok(mut s) = stats.lock() {
+            s.record_change(path);
+        }
+    }
+    let root = root_of(roots, path).map(Path::to_path_buf);
+    sink.on_change(&ChangeEvent::new(path).with_root(root).with_kind(kind));
+}
+
+/// Whether the filters let `path` through. A rejection is counted, by
+/// reason, for `--stats`.
+fn admit(
+    filters: &Filters,
+    roots: &[PathBuf],
+    path: &Path,
+    stats: Option<&Arc<Mutex<Stats>>>,
+) -> bool {
+    let decision = filters.explain(roots, path);
+    if let (PathDecision::Rejected { reason }, Some(stats)) = (&decision, stats) {
+        if let Ok(mut s) = stats.lock() {
+            s.record_rejected(reason);
+        }
+    }
+    decision.is_accepted()
+}
+
+/// The `--count-only` tally after each change, or with `closing`, the
+/// summary printed on the way out.
+fn print_counts(stats: Option<&Arc<Mutex<Stats>>>, closing: bool, out: &LinePrefix) {
+    let Some(Ok(s)) = stats.map(|stats| stats.lock()) else {
+        return;
+    };
+    if closing {
+        out.println(format_args!(
+            "{}  counted {} in {}",
+            SUMMARY.bright_blue(),
+            s.render_counts(),
+            format_duration(s.uptime())
+        ));
+    } else {
+        out.println(format_args!(
+            "{}  {}",
+            SUMMARY.bright_blue(),
+            s.render_counts()
+        ));
+    }
+}
+
+/// Passes everything on to `inner`, counting failed runs on the way for the
+/// exit summary. Every exit goes through the sink, including those of
+/// `--parallel` jobs reaped long after they started.
+struct Tally<'a> {
+    inner: &'a dyn EventSink,
+    stats: Arc<Mutex<Stats>>,
+}
+
+impl EventSink for Tally<'_> {
+    fn on_change(&self, event: &ChangeEvent) {
+        self.inner.on_change(event);
+    }
+
+    fn on_run(&self, command: &[String]) {
+        self.inner.on_run(command);
+    }
+
+    fn on_exit(&self, status: ExitStatus) {
+        if !status.success() {
+            if let Ok(mut s) = self.stats.lock() {
+                s.record_failure();
+            }
+        }
+        self.inner.on_exit(status);
+    }
+
+    fn on_output(&self, command: &[String], output: &std::process::Output) {
+        self.inner.on_output(command, output);
+    }
+}
+
+fn record_run(stats: Option<&Arc<Mutex<Stats>>>) {
+    if let Some(stats) = stats {
+        if let Ok(mut s) = stats.lock() {
+            s.record_run();
+        }
+    }
+}
+
+/// Shared shutdown flag for the periodic `--stats` and `--summary-interval`
+/// printers. Waiting on it doubles as their sleep, so a stop wakes them at
+/// once instead of after the rest of the interval.
+#[derive(Debug, Clone, Default)]
+struct StopSignal(Arc<(Mutex<bool>, Condvar)>);
+
+impl StopSignal {
+    fn stop(&self) {
+        let (stopped, wake) = &*self.0;
+        if let Ok(mut stopped) = stopped.lock() {
+            *stopped = true;
+        }
+        wake.notify_all();
+    }
+
+    /// Sleep for `interval` or until [`stop`](Self::stop) is called. Returns
+    /// whether the signal was raised.
+    fn wait(&self, interval: Duration) -> bool {
+        let (stopped, wake) = &*self.0;
+        let Ok(guard) = stopped.lock() else {
+            return true;
+        };
+        match wake.wait_timeout_while(guard, interval, |stopped| !*stopped) {
+            Ok((stopped, _)) => *stopped,
+            Err(_) => true,
+        }
+    }
+}
+
+/// The periodic printer threads of one session, and its `--max-memory`
+/// guard. Dropping it raises the stop signal and waits for each of them to
+/// print its final block and exit.
+#[derive(Default)]
+struct Background {
+    stop: StopSignal,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        self.stop.stop();
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn spawn_summary_thread(
+    stats: Arc<Mutex<Stats>>,
+    interval: Duration,
+    out: LinePrefix,
+    stop: StopSignal,
+) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let stopping = stop.wait(interval);
+        let summary = match stats.lock() {
+            Ok(mut s) => s.take_summary(),
+            Err(_) => break,
+        };
+        if !summary.is_empty() {
+            out.println(format_args!(
+                "{}  {}",
+                SUMMARY.bright_blue(),
+                summary.render(interval)
+            ));
+        }
+        if stopping {
+            break;
+        }
+    })
+}
+
+fn spawn_stats_thread(
+    stats: Arc<Mutex<Stats>>,
+    interval: Duration,
+    out: LinePrefix,
+    stop: StopSignal,
+) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        let stopping = stop.wait(interval);
+        let mut s = match stats.lock() {
+            Ok(s) => s,
+            Err(_) => break,
+        };
+        s.refresh();
+        out.println(s.render());
+        if stopping {
+            break;
+        }
+    })
+}
+
+/// Check Flash's own memory use for `--max-memory`, now and then every
+/// `interval`. `sample` reads it in bytes, or `None` once it can't. The
+/// loop hears about every reading over `limit`, not just the first: draining
+/// the channel after a run discards anything that isn't a change.
+fn spawn_memory_guard(
+    mut sample: impl FnMut() -> Option<u64> + Send + 'static,
+    limit: u64,
+    interval: Duration,
+    tx: SyncSender<Message>,
+    stop: StopSignal,
+) -> JoinHandle<()> {
+    thread::spawn(move || loop {
+        match sample() {
+            Some(bytes) if bytes > limit => {
+                let _ = tx.try_send(Message::OverMemory(bytes));
+            }
+            Some(_) => {}
+            None => break,
+        }
+        if stop.wait(interval) {
+            break;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScopedWatch;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    fn no_filter() -> Filter {
+        Filter::new(&[], &[], &[]).unwrap()
+    }
+
+    fn roots_for(specs: &[String], recursive: bool, ignore_case: bool) -> Result<Vec<PathBuf>> {
+        resolve_watch_roots(specs, recursive, ignore_case, &no_filter()).map(|(roots, _)| roots)
+    }
+
+    #[test]
+    fn memory_guard_reports_readings_over_the_limit() {
+        const MIB: u64 = 1024 * 1024;
+        let (tx, rx) = sync_channel(16);
+        let mut readings = vec![2 * MIB, 10 * MIB, 3 * MIB].into_iter();
+        let sample = move || readings.next();
+        spawn_memory_guard(sample, 4 * MIB, Duration::ZERO, tx, StopSignal::default())
+            .join()
+            .unwrap();
+
+        let over: Vec<u64> = rx
+            .try_iter()
+            .map(|message| match message {
+                Message::OverMemory(bytes) => bytes,
+                _ => panic!("the guard only ever reports memory"),
+            })
+            .collect();
+        assert_eq!(over, [10 * MIB], "only the reading over the limit counts");
+    }
+
+    #[test]
+    fn drain_pending_offers_every_queued_change_once() {
+        let (tx, rx) = sync_channel(16);
+        for name in ["a.rs", "b.rs", "c.txt"] {
+            tx.send(Message::Change(PathBuf::from(name), ChangeKind::Modify))
+                .unwrap();
+        }
+        let mut accepted = Vec::new();
+        let any = drain_pending(&rx, |p, _| {
+            let keep = p.extension().is_some_and(|e| e == "rs");
+            if keep {
+                accepted.push(p.to_path_buf());
+            }
+            keep
+        });
+        assert!(any);
+        assert_eq!(accepted, [PathBuf::from("a.rs"), PathBuf::from("b.rs")]);
+        assert!(rx.try_recv().is_err(), "channel is left empty");
+        assert!(!drain_pending(&rx, |_, _| true));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonicalizer_merges_spellings_through_a_symlinked_dir() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("link")).unwrap();
+        std::fs::write(root.join("real/a.rs"), "").unwrap();
+
+        let mut canonical = Canonicalizer::default();
+        let batch = vec![
+            (root.join("link/a.rs"), ChangeKind::Modify),
+            (root.join("real/a.rs"), ChangeKind::Modify),
+        ];
+        assert_eq!(
+            canonical.resolve_all(batch),
+            [(root.join("real/a.rs"), ChangeKind::Modify)]
+        );
+        assert_eq!(
+            canonical.resolve(root.join("link/deleted.rs")),
+            root.join("real/deleted.rs"),
+            "a deleted file still resolves through its parent"
+        );
+        assert_eq!(
+            canonical.resolve(root.join("gone/x.rs")),
+            root.join("gone/x.rs"),
+            "an unresolvable parent keeps the raw path"
+        );
+    }
+
+    #[test]
+    fn only_metadata_modifications_are_not_content_changes() {
+        use notify::event::{AccessKind, CreateKind, DataChange, MetadataKind, RemoveKind};
+        let metadata = [
+            MetadataKind::Permissions,
+            MetadataKind::Ownership,
+            MetadataKind::AccessTime,
+            MetadataKind::WriteTime,
+            MetadataKind::Any,
+        ];
+        for kind in metadata {
+            let kind = EventKind::Modify(ModifyKind::Metadata(kind));
+            assert!(!is_content_change(&kind), "{kind:?}");
+        }
+        let content = [
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            EventKind::Modify(ModifyKind::Data(DataChange::Size)),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            EventKind::Modify(ModifyKind::Any),
+            EventKind::Create(CreateKind::File),
+            EventKind::Remove(RemoveKind::File),
+            EventKind::Access(AccessKind::Any),
+        ];
+        for kind in content {
+            assert!(is_content_change(&kind), "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn raw_events_map_to_change_kinds() {
+        use notify::event::{CreateKind, DataChange, RemoveKind};
+        let event = |kind, paths: &[&str]| notify::Event {
+            kind,
+            paths: paths.iter().map(PathBuf::from).collect(),
+            attrs: Default::default(),
+        };
+        let cases = [
+            (EventKind::Create(CreateKind::File), ChangeKind::Create),
+            (
+                EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+                ChangeKind::Modify,
+            ),
+            (EventKind::Remove(RemoveKind::File), ChangeKind::Remove),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)),
+                ChangeKind::Remove,
+            ),
+            (
+                EventKind::Modify(ModifyKind::Name(RenameMode::To)),
+                ChangeKind::Create,
+            ),
+        ];
+        for (kind, expected) in cases {
+            assert_eq!(
+                raw_changes(event(kind, &["/w/a.rs"])),
+                [(PathBuf::from("/w/a.rs"), expected)],
+                "{kind:?}"
+            );
+        }
+        assert_eq!(
+            raw_changes(event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &["/w/old.rs", "/w/new.rs"]
+            )),
+            [
+                (PathBuf::from("/w/old.rs"), ChangeKind::Remove),
+                (PathBuf::from("/w/new.rs"), ChangeKind::Create)
+            ]
+        );
+    }
+
+    #[test]
+    fn debounced_kinds_are_read_off_the_file() {
+        let tmp = TempDir::new().unwrap();
+        let window = Duration::from_millis(50);
+        let path = tmp.path().join("a.rs");
+        assert_eq!(infer_kind(&path, window), ChangeKind::Remove);
+
+        std::fs::write(&path, "").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        let born = file.metadata().unwrap().created();
+        if born.is_ok() {
+            assert_eq!(infer_kind(&path, window), ChangeKind::Create);
+        }
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(infer_kind(&path, window), ChangeKind::Modify);
+    }
+
+    #[test]
+    fn directory_events_paired_with_a_child_event_are_dropped() {
+        let batch = vec![
+            PathBuf::from("/w"),
+            PathBuf::from("/w/src"),
+            PathBuf::from("/w/src/net"),
+            PathBuf::from("/w/src/net/tcp.rs"),
+            PathBuf::from("/w/src/lib.rs"),
+            PathBuf::from("/w/docs"),
+            PathBuf::from("/w/src-old"),
+        ];
+        assert_eq!(
+            drop_parent_dirs(batch),
+            [
+                PathBuf::from("/w/src/net/tcp.rs"),
+                PathBuf::from("/w/src/lib.rs"),
+                PathBuf::from("/w/docs"),
+                PathBuf::from("/w/src-old"),
+            ]
+        );
+        let lone_dir = vec![PathBuf::from("/w/src")];
+        assert_eq!(drop_parent_dirs(lone_dir.clone()), lone_dir);
+    }
+
+    #[test]
+    fn a_flooded_queue_stays_bounded_and_counts_what_it_dropped() {
+        let (tx, rx) = sync_channel(8);
+        let queue = EventQueue::new(tx);
+        for i in 0..10_000 {
+            queue.push(PathBuf::from(format!("/w/file-{i}")), ChangeKind::Modify);
+        }
+        assert!(queue.overflowed(), "dropped changes still call for a run");
+        assert_eq!(queue.take_dropped(), 10_000 - 8);
+        assert_eq!(queue.take_dropped(), 0, "the counter resets once read");
+        assert!(!queue.overflowed());
+
+        let mut seen = 0;
+        assert!(drain_pending(&rx, |_, _| {
+            seen += 1;
+            true
+        }));
+        assert_eq!(seen, 8, "only the first `buffer_size` changes were queued");
+        queue.push(PathBuf::from("/w/after-drain"), ChangeKind::Modify);
+        assert_eq!(queue.take_dropped(), 0, "room again once drained");
+    }
+
+    #[test]
+    fn next_slot_waits_out_the_throttle_window() {
+        let throttle = Some(Duration::from_secs(60));
+        assert_eq!(next_slot(None, Some(Instant::now())), None);
+        assert_eq!(next_slot(throttle, None), None, "first run is never held");
+
+        let last = Instant::now();
+        assert_eq!(
+            next_slot(throttle, Some(last)),
+            Some(last + Duration::from_secs(60))
+        );
+        let long_ago = Instant::now() - Duration::from_secs(120);
+        assert_eq!(next_slot(throttle, Some(long_ago)), None);
+    }
+
+    #[test]
+    fn stats_thread_stops_within_the_interval() {
+        let interval = Duration::from_secs(5);
+        let stop = StopSignal::default();
+        let stats = Arc::new(Mutex::new(Stats::new()));
+        let thread = spawn_stats_thread(stats, interval, LinePrefix::default(), stop.clone());
+
+        let asked = Instant::now();
+        drop(Background {
+            stop,
+            threads: vec![thread],
+        });
+        assert!(
+            asked.elapsed() < interval,
+            "stats thread outlived its interval: {:?}",
+            asked.elapsed()
+        );
+    }
+
+    #[test]
+    fn stop_signal_wakes_a_waiter_and_stays_raised() {
+        let stop = StopSignal::default();
+        assert!(!stop.wait(Duration::from_millis(1)));
+        stop.stop();
+        assert!(stop.wait(Duration::from_secs(60)));
     }
 
-    let settings = Settings::build(cli)?;
-    if settings.command.is_empty() {
-        bail!("no command specified — pass one after the flags, or set `command:` in your config");
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl EventSink for Recorder {
+        fn on_change(&self, event: &ChangeEvent) {
+            self.0
+                .borrow_mut()
+                .push(format!("change {}", event.path.display()));
+        }
+        fn on_run(&self, command: &[String]) {
+            self.0
+                .borrow_mut()
+                .push(format!("run {}", command.join(" ")));
+        }
+        fn on_exit(&self, status: ExitStatus) {
+            self.0
+                .borrow_mut()
+                .push(format!("exit {:?}", status.code()));
+        }
     }
 
-    let filter = Filter::new(&settings.extensions, &settings.include, &settings.ignore)?;
-    let watch_roots = resolve_watch_roots(&settings.watch)?;
+    #[test]
+    fn a_single_change_reaches_the_sink_as_change_run_exit() {
+        let tmp = TempDir::new().unwrap();
+        let watched = tmp.path().canonicalize().unwrap();
+        let touched = watched.join("lib.rs");
+        let cli = Cli::parse_args([
+            "flash-watcher".as_ref(),
+            "--fast".as_ref(),
+            "--max-runs".as_ref(),
+            "1".as_ref(),
+            "-w".as_ref(),
+            watched.as_os_str(),
+            "--".as_ref(),
+            "sh".as_ref(),
+            "-c".as_ref(),
+            "exit 2".as_ref(),
+        ]);
+        // Keep writing until the session ends, so a write made before the
+        // watch is in place doesn't leave the test waiting.
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = thread::spawn({
+            let done = done.clone();
+            let touched = touched.clone();
+            move || {
+                while !done.load(Ordering::SeqCst) {
+                    std::fs::write(&touched, "fn main() {}").unwrap();
+                    thread::sleep(Duration::from_millis(200));
+                }
+            }
+        });
+
+        let sink = Recorder::default();
+        let code = run_with_sink(cli, &sink);
+        done.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
 
-    let stats = settings.stats.then(|| Arc::new(Mutex::new(Stats::new())));
-    if let Some(stats) = stats.clone() {
-        spawn_stats_thread(stats, settings.stats_interval);
+        assert_eq!(code.unwrap(), ExitCode::from(2));
+        let calls = sink.0.borrow();
+        let changes = calls
+            .iter()
+            .take_while(|c| c.starts_with("change "))
+            .count();
+        assert!(changes >= 1, "{calls:?}");
+        assert!(
+            calls[..changes]
+                .iter()
+                .all(|c| *c == format!("change {}", touched.display())),
+            "{calls:?}"
+        );
+        assert_eq!(calls[changes..], ["run sh -c exit 2", "exit Some(2)"]);
     }
 
-    print_banner(&settings, &watch_roots);
+    #[test]
+    fn only_keys_a_reload_cannot_apply_need_a_restart() {
+        let running = Settings {
+            command: vec!["make".to_string()],
+            ..Settings::default()
+        };
+        let reloaded = Settings {
+            command: vec!["make".to_string(), "test".to_string()],
+            extensions: vec!["rs".to_string()],
+            ignore: vec!["**/dist/**".to_string()],
+            debounce: Duration::from_millis(500),
+            ..running.clone()
+        };
+        assert_eq!(
+            keys_needing_restart(&running, &reloaded),
+            ["command", "debounce"]
+        );
 
-    let mut runner = Runner::new(settings.command.clone(), settings.restart, settings.clear);
-    if settings.initial {
-        if let Err(err) = runner.run() {
-            eprintln!("flash-watcher: initial run failed: {err:#}");
-        }
+        let moved = Settings {
+            watch: vec!["src".to_string()],
+            ..running.clone()
+        };
+        assert_eq!(keys_needing_restart(&running, &moved), ["watch"]);
+        assert!(keys_needing_restart(&running, &running).is_empty());
     }
 
-    let (tx, rx) = channel::<PathBuf>();
-    let stats_for_events = stats.clone();
-    let mut debouncer =
-        new_debouncer(
-            settings.debounce,
-            move |result: DebounceEventResult| match result {
-                Ok(events) => {
-                    if let Some(stats) = stats_for_events.as_ref() {
-                        if let Ok(mut s) = stats.lock() {
-                            for _ in &events {
-                                s.record_event();
-                            }
-                        }
-                    }
-                    for event in events {
-                        let _ = tx.send(event.path);
-                    }
-                }
-                Err(err) => eprintln!("flash-watcher: watcher error: {err}"),
-            },
-        )
-        .context("creating debounced watcher")?;
+    #[test]
+    fn backend_report_names_a_backend() {
+        let report = backend_report();
+        let backend = report
+            .lines()
+            .find_map(|l| l.strip_prefix("backend: "))
+            .expect("a backend line");
+        assert!(!backend.is_empty());
+        #[cfg(target_os = "linux")]
+        assert_eq!(backend, "inotify");
+        assert!(report.contains("\npolling: "), "{report}");
+    }
 
-    for root in &watch_roots {
-        debouncer
-            .watcher()
-            .watch(root, RecursiveMode::Recursive)
-            .with_context(|| format!("watching '{}'", root.display()))?;
+    #[test]
+    fn looks_like_glob_detects_wildcards() {
+        assert!(looks_like_glob("src/**/*.rs"));
+        assert!(looks_like_glob("a?b"));
+        assert!(looks_like_glob("a[bc]"));
+        assert!(looks_like_glob("a/{b,c}.txt"));
+        assert!(!looks_like_glob("plain/path"));
     }
 
-    if !settings.fast {
-        println!("{}", "ready · waiting for changes".bright_green());
+    #[test]
+    fn split_glob_takes_fixed_prefix() {
+        let root = |pattern| split_glob(pattern).0;
+        assert_eq!(root("src/**/*.rs"), PathBuf::from("src"));
+        assert_eq!(root("a/b/c/*.x"), PathBuf::from("a/b/c"));
+        assert_eq!(root("**/*.rs"), PathBuf::from("."));
+        assert_eq!(root("*.rs"), PathBuf::from("."));
+        assert_eq!(split_glob("src/*/mod.rs").1, "*/mod.rs");
     }
 
-    loop {
-        match rx.recv_timeout(Duration::from_secs(60)) {
-            Ok(path) => {
-                if !filter.accepts(&path) {
-                    continue;
-                }
-                if let Some(stats) = stats.as_ref() {
-                    if let Ok(mut s) = stats.lock() {
-                        s.record_change();
-                    }
-                }
-                if !settings.fast && !settings.stats {
-                    println!(
-                        "{}  {}",
-                        "↻".bright_blue(),
-                        display_path(&path).bright_white()
-                    );
-                }
-                if let Err(err) = runner.run() {
-                    eprintln!("flash-watcher: command failed: {err:#}");
-                }
-            }
-            Err(RecvTimeoutError::Timeout) => continue,
-            Err(RecvTimeoutError::Disconnected) => break,
-        }
+    #[test]
+    fn root_of_prefers_the_most_specific_root() {
+        let roots = vec![PathBuf::from("/repo"), PathBuf::from("/repo/web")];
+        assert_eq!(
+            root_of(&roots, Path::new("/repo/web/app.ts")),
+            Some(Path::new("/repo/web"))
+        );
+        assert_eq!(
+            root_of(&roots, Path::new("/repo/src/lib.rs")),
+            Some(Path::new("/repo"))
+        );
+        assert_eq!(root_of(&roots, Path::new("/elsewhere/x")), None);
     }
 
-    Ok(())
-}
+    #[test]
+    fn dir_budget_rejects_trees_over_the_limit() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::create_dir_all(tmp.path().join("a/b")).unwrap();
+        std::fs::create_dir(tmp.path().join("c")).unwrap();
+        let roots = vec![tmp.path().to_path_buf()];
 
-/// Translate the user's `--watch` arguments into actual paths to hand to
-/// `notify`. Globs are accepted: their fixed prefix becomes the root, and the
-/// pattern itself is enforced by the [`Filter`]. Literal paths that do not
-/// exist are an error — issue #1.
-fn resolve_watch_roots(specs: &[String]) -> Result<Vec<PathBuf>> {
-    if specs.is_empty() {
-        return Ok(vec![PathBuf::from(".")]);
+        assert!(check_dir_budget(&roots, 4).is_ok());
+        let err = check_dir_budget(&roots, 3).expect_err("4 dirs > limit of 3");
+        assert!(err.to_string().contains("--max-dirs"));
     }
 
-    let mut roots: Vec<PathBuf> = Vec::with_capacity(specs.len());
-    for spec in specs {
-        let root = if looks_like_glob(spec) {
-            glob_root(spec)
-        } else {
-            PathBuf::from(spec)
+    #[test]
+    fn watch_limit_errors_get_a_hint() {
+        let err = explain_watch_error(notify::Error::new(notify::ErrorKind::MaxFilesWatch));
+        assert!(err.to_string().contains("ran out of file watches"));
+
+        let err = explain_watch_error(notify::Error::path_not_found());
+        assert!(!err.to_string().contains("ran out of file watches"));
+    }
+
+    #[test]
+    fn scoped_filters_apply_only_under_their_root() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().join("src");
+        let assets = tmp.path().join("assets");
+        std::fs::create_dir(&src).unwrap();
+        std::fs::create_dir(&assets).unwrap();
+
+        let settings = Settings {
+            watch: vec![src.display().to_string(), assets.display().to_string()],
+            scoped: vec![
+                ScopedWatch {
+                    path: src.display().to_string(),
+                    ext: Some("rs".into()),
+                    pattern: None,
+                    ignore: None,
+                },
+                ScopedWatch {
+                    path: assets.display().to_string(),
+                    ext: Some("png".into()),
+                    pattern: None,
+                    ignore: None,
+                },
+            ],
+            ..Settings::default()
         };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = filters.roots.clone();
+        let src = &roots[0];
+        let assets = &roots[1];
 
-        if !root.exists() {
-            if looks_like_glob(spec) {
-                bail!(
-                    "watch pattern '{spec}' has no existing root directory (tried '{}')",
-                    root.display()
-                );
-            }
-            bail!("watch path '{spec}' does not exist");
-        }
+        assert!(filters.accepts(&roots, &src.join("lib.rs")));
+        assert!(!filters.accepts(&roots, &src.join("logo.png")));
+        assert!(filters.accepts(&roots, &assets.join("logo.png")));
+        assert!(!filters.accepts(&roots, &assets.join("lib.rs")));
+    }
 
-        let canonical = root.canonicalize().unwrap_or(root);
-        if !roots.iter().any(|p| p == &canonical) {
-            roots.push(canonical);
-        }
+    #[test]
+    fn unset_scoped_filters_fall_back_to_global_ones() {
+        let tmp = TempDir::new().unwrap();
+        let docs = tmp.path().join("docs");
+        let src = tmp.path().join("src");
+        std::fs::create_dir(&docs).unwrap();
+        std::fs::create_dir(&src).unwrap();
+
+        let settings = Settings {
+            watch: vec![docs.display().to_string(), src.display().to_string()],
+            scoped: vec![ScopedWatch {
+                path: src.display().to_string(),
+                ext: None,
+                pattern: Some(vec!["**/*.rs".into()]),
+                ignore: None,
+            }],
+            ignore: vec!["**/gen/**".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = filters.roots.clone();
+        let (docs, src) = (&roots[0], &roots[1]);
+
+        // `docs` has no entry of its own: global filters only.
+        assert!(filters.accepts(&roots, &docs.join("guide.md")));
+        assert!(!filters.accepts(&roots, &docs.join("gen/guide.md")));
+        // `src` has its own pattern but inherits the global ignore.
+        assert!(filters.accepts(&roots, &src.join("lib.rs")));
+        assert!(!filters.accepts(&roots, &src.join("notes.md")));
+        assert!(!filters.accepts(&roots, &src.join("gen/out.rs")));
     }
-    Ok(roots)
-}
 
-fn looks_like_glob(spec: &str) -> bool {
-    spec.chars().any(|c| matches!(c, '*' | '?' | '[' | '{'))
-}
+    #[test]
+    fn changed_within_finds_recent_matching_files_only() {
+        let tmp = TempDir::new().unwrap();
+        let nested = tmp.path().join("nested");
+        std::fs::create_dir(&nested).unwrap();
+        for name in ["fresh.rs", "fresh.md", "nested/deep.rs", "stale.rs"] {
+            std::fs::write(tmp.path().join(name), "x").unwrap();
+        }
+        let hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(tmp.path().join("stale.rs"))
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
 
-/// Take the longest leading prefix of the glob that contains no wildcard
-/// characters. `src/**/*.rs` -> `src`; `**/foo` -> `.`; `a/b/*.c` -> `a/b`.
-fn glob_root(pattern: &str) -> PathBuf {
-    let mut root = PathBuf::new();
-    for component in Path::new(pattern).components() {
-        let s = component.as_os_str().to_string_lossy();
-        if looks_like_glob(&s) {
-            break;
+        let settings = Settings {
+            extensions: vec!["rs".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = roots_for(&[tmp.path().display().to_string()], true, false).unwrap();
+        let found = changed_within(&roots, Duration::from_secs(60), true, &filters);
+        assert_eq!(
+            found,
+            vec![roots[0].join("fresh.rs"), roots[0].join("nested/deep.rs")]
+        );
+        let shallow = changed_within(&roots, Duration::from_secs(60), false, &filters);
+        assert_eq!(shallow, vec![roots[0].join("fresh.rs")]);
+    }
+
+    #[test]
+    fn settling_waits_on_all_files_at_once_and_gives_up_on_a_growing_one() {
+        let tmp = TempDir::new().unwrap();
+        let window = Duration::from_millis(50);
+        let mut settler = Settler::new(window);
+        let files: Vec<PathBuf> = (0..5)
+            .map(|i| tmp.path().join(format!("{i}.bin")))
+            .collect();
+        for file in &files {
+            std::fs::write(file, "done").unwrap();
+            assert!(settler.note(file));
         }
-        root.push(component);
+        let started = Instant::now();
+        assert!(settler.settle().is_empty());
+        assert!(
+            started.elapsed() < window * 4,
+            "five still files settle together, not one window each: {:?}",
+            started.elapsed()
+        );
+        // Leftover events for a settled file are recognised until it changes.
+        assert!(!settler.note(&files[0]));
+        assert!(!settler.note(&files[0]));
+        std::fs::write(&files[0], "again").unwrap();
+        assert!(settler.note(&files[0]));
+        assert!(!settler.settled.contains_key(&files[0]));
+        settler.waiting.clear();
+
+        let log = tmp.path().join("growing.log");
+        std::fs::write(&log, "").unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (log, stop) = (log.clone(), stop.clone());
+            thread::spawn(move || {
+                let mut size = 0;
+                while !stop.load(Ordering::SeqCst) {
+                    size += 1;
+                    std::fs::write(&log, "x".repeat(size)).unwrap();
+                    thread::sleep(Duration::from_millis(5));
+                }
+            })
+        };
+        assert!(settler.note(&log));
+        let started = Instant::now();
+        let unsettled = settler.settle();
+        stop.store(true, Ordering::SeqCst);
+        writer.join().unwrap();
+        assert_eq!(unsettled, vec![log]);
+        assert!(started.elapsed() < window * (SETTLE_LIMIT_WINDOWS + 5));
+        assert!(
+            settler.settled.is_empty(),
+            "the last batch's files are dropped"
+        );
     }
-    if root.as_os_str().is_empty() {
-        PathBuf::from(".")
-    } else {
-        root
+
+    #[test]
+    fn pruning_skips_subtrees_without_a_matching_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        for i in 0..20 {
+            let dir = tmp.path().join(format!("fixtures/case-{i}"));
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("input.json"), "{}").unwrap();
+        }
+        std::fs::create_dir_all(tmp.path().join("src/parser")).unwrap();
+        std::fs::write(tmp.path().join("src/parser/mod.rs"), "").unwrap();
+        std::fs::create_dir_all(tmp.path().join("docs")).unwrap();
+
+        let settings = Settings {
+            extensions: vec!["rs".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = roots_for(&[tmp.path().display().to_string()], true, false).unwrap();
+        let mut dirs = Vec::new();
+        let mut skipped = Vec::new();
+        let walked =
+            dirs_worth_watching(&roots[0], &roots, &filters, true, &mut dirs, &mut skipped);
+        assert_eq!(
+            walked, 25,
+            "the root, fixtures, 20 cases, src, parser, docs"
+        );
+        assert_eq!(
+            dirs,
+            vec![
+                roots[0].join("src/parser"),
+                roots[0].join("src"),
+                roots[0].clone()
+            ],
+            "3 of 25 directories hold a .rs file"
+        );
+        assert_eq!(skipped.len(), 22, "fixtures, its 20 cases, and docs");
     }
-}
 
-fn display_path(path: &Path) -> String {
-    if let Ok(cwd) = std::env::current_dir() {
-        if let Ok(stripped) = path.strip_prefix(&cwd) {
-            return stripped.display().to_string();
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn an_ignored_subtree_sends_no_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tmp.path().join("target/debug")).unwrap();
+        std::fs::create_dir_all(tmp.path().join("src")).unwrap();
+        let settings = Settings {
+            ignore: vec!["target/**".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = roots_for(&[tmp.path().display().to_string()], true, false).unwrap();
+        let (tx, rx) = sync_channel(64);
+        let mut source = EventSource::new(
+            Duration::from_millis(10),
+            false,
+            false,
+            FrontFilter::default(),
+            EventQueue::new(tx),
+            None,
+            LinePrefix::default(),
+        )
+        .unwrap();
+        let watch = PrunedWatch::start(&roots, &filters, false, 0, |dir| {
+            source.watcher().watch(dir, RecursiveMode::NonRecursive)
+        })
+        .unwrap();
+        assert_eq!(watch.watched.len(), 2, "the root and src");
+        assert_eq!(watch.scanned, 3, "target is seen, but not walked");
+
+        std::fs::write(roots[0].join("target/debug/app.d"), "x").unwrap();
+        std::fs::write(roots[0].join("src/lib.rs"), "x").unwrap();
+        let mut seen = Vec::new();
+        while let Ok(Message::Change(path, _)) = rx.recv_timeout(Duration::from_secs(1)) {
+            seen.push(path);
         }
+        assert!(
+            seen.contains(&roots[0].join("src/lib.rs")),
+            "src is still watched: {seen:?}"
+        );
+        assert!(
+            !seen.iter().any(|p| p.starts_with(roots[0].join("target"))),
+            "nothing under target should reach the channel: {seen:?}"
+        );
     }
-    path.display().to_string()
-}
 
-fn print_banner(settings: &Settings, roots: &[PathBuf]) {
-    if settings.fast || settings.stats {
-        return;
+    #[test]
+    fn pruned_watch_goes_on_without_directories_the_os_cannot_watch() {
+        let tmp = tempfile::tempdir().unwrap();
+        for dir in ["src", "docs"] {
+            std::fs::create_dir_all(tmp.path().join(dir)).unwrap();
+        }
+        let settings = Settings {
+            ignore: vec!["target/**".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = roots_for(&[tmp.path().display().to_string()], true, false).unwrap();
+        let exhausted = || notify::Error::new(notify::ErrorKind::MaxFilesWatch);
+
+        let watch = PrunedWatch::start(&roots, &filters, false, 0, |dir| {
+            if dir.ends_with("docs") {
+                Err(exhausted())
+            } else {
+                Ok(())
+            }
+        })
+        .unwrap();
+        let mut watched: Vec<_> = watch.watched.into_iter().collect();
+        watched.sort();
+        assert_eq!(watched, vec![roots[0].clone(), roots[0].join("src")]);
+        let unwatched: Vec<_> = watch.unwatched.iter().map(|(dir, _)| dir).collect();
+        assert_eq!(unwatched, vec![&roots[0].join("docs")]);
+
+        // With nothing watched at all, startup fails.
+        let result = PrunedWatch::start(&roots, &filters, false, 0, |_| Err(exhausted()));
+        assert!(result.is_err());
     }
-    println!("{}", "flash · watching for changes".bright_green().bold());
-    for root in roots {
-        println!("  {} {}", "•".bright_blue(), display_path(root));
+
+    #[test]
+    fn watch_retries_only_while_the_os_is_out_of_watches() {
+        let exhausted = || notify::Error::new(notify::ErrorKind::MaxFilesWatch);
+
+        // Two transient failures, then room frees up.
+        let mut calls = 0;
+        let result = watch_with_retry(3, || {
+            calls += 1;
+            if calls <= 2 {
+                Err(exhausted())
+            } else {
+                Ok(())
+            }
+        });
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        // Still exhausted once the retries run out.
+        let mut calls = 0;
+        let result = watch_with_retry(2, || {
+            calls += 1;
+            Err(exhausted())
+        });
+        assert!(out_of_watches(&result.unwrap_err()));
+        assert_eq!(calls, 3);
+
+        // Any other failure is final at once.
+        let mut calls = 0;
+        let result = watch_with_retry(3, || {
+            calls += 1;
+            Err(notify::Error::path_not_found())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
     }
-    if !settings.extensions.is_empty() {
-        println!(
-            "  {} {}",
-            "ext".bright_blue(),
-            settings.extensions.join(",")
+
+    #[test]
+    fn a_batch_holds_each_path_once_with_the_coalesced_kind() {
+        let mut batch = Vec::new();
+        for (path, kind) in [
+            ("/w/new.rs", ChangeKind::Create),
+            ("/w/old.rs", ChangeKind::Modify),
+            ("/w/new.rs", ChangeKind::Modify),
+            ("/w/gone.rs", ChangeKind::Modify),
+            ("/w/new.rs", ChangeKind::Modify),
+            ("/w/gone.rs", ChangeKind::Remove),
+        ] {
+            add_change(&mut batch, PathBuf::from(path), kind);
+        }
+        assert_eq!(
+            batch,
+            [
+                (PathBuf::from("/w/new.rs"), ChangeKind::Create),
+                (PathBuf::from("/w/old.rs"), ChangeKind::Modify),
+                (PathBuf::from("/w/gone.rs"), ChangeKind::Remove),
+            ]
         );
     }
-    if !settings.include.is_empty() {
-        println!(
-            "  {} {}",
-            "include".bright_blue(),
-            settings.include.join(", ")
+
+    #[test]
+    fn changes_run_the_nearest_script_up_to_their_watch_root() {
+        let dir = TempDir::new().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        std::fs::create_dir_all(root.join("c")).unwrap();
+        // Above the watch root, so never used.
+        std::fs::write(dir.path().join("build.sh"), "").unwrap();
+        std::fs::write(root.join("a/build.sh"), "").unwrap();
+
+        let changes = [
+            (root.join("a/b/deep.rs"), ChangeKind::Modify),
+            (root.join("c/other.rs"), ChangeKind::Remove),
+            (root.join("a/near.rs"), ChangeKind::Create),
+        ];
+        let (scripts, rest) = matched_scripts("build.sh", std::slice::from_ref(&root), &changes);
+        assert_eq!(
+            scripts,
+            [(
+                root.join("a/build.sh"),
+                vec![ChangeKind::Modify, ChangeKind::Create]
+            )]
         );
+        assert_eq!(rest, [ChangeKind::Remove]);
     }
-    if !settings.ignore.is_empty() {
-        println!(
-            "  {} {}",
-            "ignore".bright_blue(),
-            settings.ignore.join(", ")
+
+    #[test]
+    fn output_is_up_to_date_only_when_newer_than_every_input() {
+        let dir = TempDir::new().unwrap();
+        let at = |name: &str, age: u64| {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+            path
+        };
+        let output = at("app", 60);
+        let old = at("old.rs", 120);
+        let new = at("new.rs", 0);
+
+        assert!(up_to_date(&output, std::slice::from_ref(&old)));
+        assert!(!up_to_date(&output, &[old.clone(), new]));
+        assert!(!up_to_date(&output, &[dir.path().join("deleted.rs")]));
+        assert!(!up_to_date(&dir.path().join("missing"), &[old]));
+    }
+
+    #[test]
+    fn banner_roots_are_sorted_deduplicated_and_capped() {
+        let roots: Vec<PathBuf> = ["/w/c", "/w/a", "/w/b", "/w/a"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(
+            banner_roots(&roots, false),
+            (vec!["/w/a".into(), "/w/b".into(), "/w/c".into()], 0)
         );
+
+        let many: Vec<PathBuf> = (0..12)
+            .rev()
+            .map(|i| PathBuf::from(format!("/w/dir-{i:02}")))
+            .collect();
+        let (shown, more) = banner_roots(&many, false);
+        assert_eq!(
+            shown,
+            [
+                "/w/dir-00",
+                "/w/dir-01",
+                "/w/dir-02",
+                "/w/dir-03",
+                "/w/dir-04"
+            ]
+        );
+        assert_eq!(more, 7);
+        assert_eq!(
+            banner_roots(&many, false),
+            (shown, more),
+            "stable across calls"
+        );
+        let (all, more) = banner_roots(&many, true);
+        assert_eq!((all.len(), more), (12, 0));
     }
-    println!(
-        "  {} {}",
-        "run".bright_blue(),
-        settings.command.join(" ").bright_yellow()
-    );
-}
 
-fn spawn_stats_thread(stats: Arc<Mutex<Stats>>, interval: Duration) {
-    thread::spawn(move || loop {
-        thread::sleep(interval);
-        let mut s = match stats.lock() {
-            Ok(s) => s,
-            Err(_) => break,
+    #[test]
+    fn admit_counts_each_rejection_by_reason() {
+        let settings = Settings {
+            extensions: vec!["rs".into(), "!gen.rs".into()],
+            include: vec!["src/**".into()],
+            ignore: vec!["**/target/**".into()],
+            ..Settings::default()
         };
-        s.refresh();
-        println!("{}", s.render());
-    });
-}
+        let filters = Filters::build(&settings).unwrap();
+        let roots = [PathBuf::from("/repo")];
+        let stats = Arc::new(Mutex::new(Stats::new()));
+        let admitted: Vec<bool> = [
+            "/repo/src/lib.rs",
+            "/repo/src/notes.md",
+            "/repo/src/schema.gen.rs",
+            "/repo/README.md",
+            "/repo/target/debug/build.rs",
+            "/repo/benches/speed.rs",
+        ]
+        .iter()
+        .map(|p| admit(&filters, &roots, Path::new(p), Some(&stats)))
+        .collect();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+        assert_eq!(admitted, [true, false, false, false, false, false]);
+        let s = stats.lock().unwrap();
+        assert_eq!(s.rejected(), 5);
+        assert_eq!(
+            s.rejections(),
+            [
+                ("extension", 3),
+                ("ignore-pattern", 1),
+                ("include-pattern", 1)
+            ]
+        );
+    }
 
     #[test]
-    fn looks_like_glob_detects_wildcards() {
-        assert!(looks_like_glob("src/**/*.rs"));
-        assert!(looks_like_glob("a?b"));
-        assert!(looks_like_glob("a[bc]"));
-        assert!(looks_like_glob("a/{b,c}.txt"));
-        assert!(!looks_like_glob("plain/path"));
+    fn shallow_filters_accept_only_direct_children_of_a_root() {
+        let tmp = TempDir::new().unwrap();
+        let settings = Settings {
+            watch: vec![tmp.path().display().to_string()],
+            recursive: false,
+            ignore: vec!["**/*.tmp".into()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = filters.roots.clone();
+        let root = &roots[0];
+
+        assert!(filters.accepts(&roots, &root.join("a.txt")));
+        assert!(!filters.accepts(&roots, &root.join("sub/a.txt")));
+        assert!(
+            !filters.accepts(&roots, &root.join("a.tmp")),
+            "regular filters still apply"
+        );
     }
 
     #[test]
-    fn glob_root_takes_fixed_prefix() {
-        assert_eq!(glob_root("src/**/*.rs"), PathBuf::from("src"));
-        assert_eq!(glob_root("a/b/c/*.x"), PathBuf::from("a/b/c"));
-        assert_eq!(glob_root("**/*.rs"), PathBuf::from("."));
-        assert_eq!(glob_root("*.rs"), PathBuf::from("."));
+    fn shallow_filters_explain_a_nested_rejection() {
+        let tmp = TempDir::new().unwrap();
+        let settings = Settings {
+            watch: vec![tmp.path().display().to_string()],
+            recursive: false,
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = filters.roots.clone();
+        let decision = filters.explain(&roots, &roots[0].join("sub/a.txt"));
+        assert_eq!(
+            decision,
+            PathDecision::Rejected {
+                reason: RejectReason::NotDirectChild(None)
+            }
+        );
+        assert!(decision.to_string().contains("--no-recursive"));
     }
 
     #[test]
     fn resolve_watch_roots_errors_on_missing_path() {
-        let err = resolve_watch_roots(&["definitely-does-not-exist-12345".into()])
+        let err = roots_for(&["definitely-does-not-exist-12345".into()], true, false)
             .expect_err("should reject missing path");
         let msg = err.to_string();
         assert!(
@@ -261,7 +3756,8 @@ mod tests {
     #[test]
     fn resolve_watch_roots_accepts_existing_dir() {
         let tmp = TempDir::new().unwrap();
-        let resolved = resolve_watch_roots(&[tmp.path().to_string_lossy().into_owned()]).unwrap();
+        let resolved =
+            roots_for(&[tmp.path().to_string_lossy().into_owned()], true, false).unwrap();
         assert_eq!(resolved.len(), 1);
     }
 
@@ -269,7 +3765,7 @@ mod tests {
     fn resolve_watch_roots_dedupes() {
         let tmp = TempDir::new().unwrap();
         let s = tmp.path().to_string_lossy().into_owned();
-        let resolved = resolve_watch_roots(&[s.clone(), s]).unwrap();
+        let resolved = roots_for(&[s.clone(), s], true, false).unwrap();
         assert_eq!(resolved.len(), 1);
     }
 
@@ -277,14 +3773,187 @@ mod tests {
     fn resolve_watch_roots_accepts_glob_with_existing_prefix() {
         let tmp = TempDir::new().unwrap();
         let pattern = format!("{}/**/*.rs", tmp.path().display());
-        let resolved = resolve_watch_roots(&[pattern]).unwrap();
+        let resolved = roots_for(&[pattern], true, false).unwrap();
         assert_eq!(resolved.len(), 1);
     }
 
+    #[test]
+    fn file_glob_watches_the_parents_of_its_matches() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().canonicalize().unwrap().join("src");
+        for (file, body) in [
+            ("a/x.rs", ""),
+            ("b/y.rs", ""),
+            ("b/notes.txt", ""),
+            ("c/README", ""),
+        ] {
+            let path = src.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, body).unwrap();
+        }
+        let spec = format!("{}/*/*.rs", src.display());
+
+        let roots = roots_for(std::slice::from_ref(&spec), true, false).unwrap();
+        assert_eq!(roots, vec![src.join("a"), src.join("b")]);
+
+        let settings = Settings {
+            watch: vec![spec.clone()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        assert!(filters.accepts(&roots, &src.join("b").join("y.rs")));
+        assert!(filters.accepts(&roots, &src.join("a").join("new.rs")));
+        assert!(!filters.accepts(&roots, &src.join("b").join("notes.txt")));
+    }
+
+    #[test]
+    fn recursive_file_glob_keeps_only_the_outermost_parent() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().canonicalize().unwrap().join("src");
+        std::fs::create_dir_all(src.join("net")).unwrap();
+        std::fs::write(src.join("lib.rs"), "").unwrap();
+        std::fs::write(src.join("net").join("mod.rs"), "").unwrap();
+        let spec = format!("{}/**/*.rs", src.display());
+
+        assert_eq!(
+            expand_watch_glob(&spec, true, false, &no_filter())
+                .unwrap()
+                .roots,
+            vec![src.clone()]
+        );
+        assert_eq!(
+            expand_watch_glob(&spec, false, false, &no_filter())
+                .unwrap()
+                .roots,
+            vec![src.clone(), src.join("net")]
+        );
+    }
+
+    #[test]
+    fn single_star_glob_watches_one_level_without_recursing() {
+        let tmp = TempDir::new().unwrap();
+        let src = tmp.path().canonicalize().unwrap().join("src");
+        for file in ["top.rs", "a/x.rs", "a/deep/y.rs", "b/z.rs"] {
+            let path = src.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let spec = format!("{}/*", src.display());
+
+        let glob = expand_watch_glob(&spec, true, false, &no_filter()).unwrap();
+        assert!(glob.shallow);
+        assert_eq!(glob.roots, vec![src.clone(), src.join("a"), src.join("b")]);
+
+        let settings = Settings {
+            watch: vec![spec.clone()],
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = glob.roots;
+        for root in &roots {
+            assert_eq!(filters.recursive_mode(root), RecursiveMode::NonRecursive);
+        }
+        assert!(filters.accepts(&roots, &src.join("a").join("x.rs")));
+        assert!(filters.accepts(&roots, &src.join("top.rs")));
+        let decision = filters.explain(&roots, &src.join("a").join("deep").join("y.rs"));
+        assert_eq!(
+            decision,
+            PathDecision::Rejected {
+                reason: RejectReason::NotDirectChild(Some(spec))
+            }
+        );
+        assert!(
+            !decision.to_string().contains("--no-recursive"),
+            "the flag was never passed: {decision}"
+        );
+    }
+
+    #[test]
+    fn watch_globs_can_ignore_case() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().canonicalize().unwrap();
+        std::fs::create_dir_all(base.join("Assets")).unwrap();
+        std::fs::write(base.join("Assets").join("Logo.PNG"), "").unwrap();
+        let spec = format!("{}/*/logo.png", base.display());
+
+        assert_eq!(
+            expand_watch_glob(&spec, true, false, &no_filter())
+                .unwrap()
+                .roots,
+            vec![base.clone()]
+        );
+        assert_eq!(
+            expand_watch_glob(&spec, true, true, &no_filter())
+                .unwrap()
+                .roots,
+            vec![base.join("Assets")]
+        );
+
+        let settings = Settings {
+            watch: vec![spec],
+            ignore_case_paths: true,
+            ..Settings::default()
+        };
+        let filters = Filters::build(&settings).unwrap();
+        let roots = filters.roots.clone();
+        assert!(filters.accepts(&roots, &base.join("Assets").join("Logo.PNG")));
+        assert!(!filters.accepts(&roots, &base.join("Assets").join("Icon.PNG")));
+    }
+
+    #[test]
+    fn watch_globs_skip_ignored_directories() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().canonicalize().unwrap();
+        for file in ["src/lib.rs", "target/debug/build.rs", "vendor/x/y.rs"] {
+            let path = base.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let settings = Settings {
+            watch: vec![format!("{}/**/*.rs", base.display())],
+            recursive: false,
+            ignore: vec!["target/**".into(), "**/vendor/**".into()],
+            ..Settings::default()
+        };
+        assert_eq!(
+            Filters::build(&settings).unwrap().roots,
+            vec![base.join("src")]
+        );
+    }
+
+    #[test]
+    fn glob_with_no_matches_watches_its_fixed_prefix() {
+        let tmp = TempDir::new().unwrap();
+        let base = tmp.path().canonicalize().unwrap();
+        let spec = format!("{}/**/*.rs", base.display());
+        assert_eq!(
+            expand_watch_glob(&spec, true, false, &no_filter())
+                .unwrap()
+                .roots,
+            vec![base]
+        );
+    }
+
     #[test]
     fn resolve_watch_roots_rejects_glob_with_missing_prefix() {
-        let err = resolve_watch_roots(&["nope/**/*.rs".into()])
+        let err = roots_for(&["nope/**/*.rs".into()], true, false)
             .expect_err("missing glob prefix should error");
         assert!(err.to_string().contains("no existing root"));
     }
+
+    #[test]
+    fn watch_list_trims_skips_blanks_and_dedupes() {
+        let input = "src\n\n  tests \nsrc\ndocs\n";
+        let read = read_watch_list(input.as_bytes(), &["docs".into()]).unwrap();
+        assert_eq!(read, vec!["src", "tests"]);
+    }
+
+    #[test]
+    fn empty_watch_list_is_an_error_without_other_watches() {
+        let err = read_watch_list("\n \n".as_bytes(), &[]).unwrap_err();
+        assert!(err.to_string().contains("no paths"), "{err}");
+        assert!(read_watch_list("".as_bytes(), &["src".into()])
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -6,6 +6,8 @@
 
 mod common;
 
+use std::io::Read;
+use std::process::Stdio;
 use std::time::Duration;
 
 use common::*;
@@ -17,18 +19,74 @@ fn help_lists_every_documented_flag() {
     assert!(output.status.success(), "--help should exit zero");
     let stdout = String::from_utf8_lossy(&output.stdout);
     for flag in [
+        "--root",
         "--watch",
+        "--watch-stdin",
         "--ext",
         "--pattern",
         "--ignore",
+        "--ignore-file",
+        "--pattern-file",
+        "--exclude-output",
+        "--ignore-editor-temp",
+        "--include-hidden",
+        "--exclude-hidden",
+        "--no-debounce-pattern",
+        "--ignore-case-paths",
+        "--files-only",
+        "--dirs-only",
+        "--no-recursive",
+        "--rewatch",
+        "--canonicalize",
+        "--no-canonicalize",
+        "--content-only",
+        "--git-tracked-only",
+        "--watch-extensions-only",
         "--debounce",
+        "--throttle",
+        "--await-stable",
+        "--output",
         "--initial",
+        "--require-initial",
+        "--changed-since",
+        "--max-runs",
+        "--count-initial",
+        "--fail-fast",
+        "--idle-timeout",
+        "--path-display",
         "--clear",
+        "--clear-mode",
         "--restart",
+        "--restart-signal",
+        "--restart-grace",
+        "--raw-command",
+        "--command-from-matched-file",
+        "--parallel",
+        "--capture",
+        "--before",
+        "--after",
+        "--max-memory",
+        "--buffer-size",
+        "--watch-retries",
         "--config",
+        "--reload-config",
+        "--print-config",
+        "--check-config",
+        "--config-schema",
+        "--explain",
+        "--init",
+        "--ready-fd",
+        "--label",
+        "--timestamps",
+        "--log-file",
+        "--verbose",
         "--fast",
+        "--count-only",
         "--stats",
+        "--summary-interval",
+        "--print-events-summary-on-exit",
         "--bench",
+        "--list-watchers",
     ] {
         assert!(stdout.contains(flag), "--help missing {flag}:\n{stdout}");
     }
@@ -47,6 +105,72 @@ fn version_prints_a_semver_string() {
     );
 }
 
+#[test]
+fn version_json_reports_the_crate_version_and_build_metadata() {
+    let output = flash()
+        .args(["--version", "--format", "json"])
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.trim();
+    assert!(line.starts_with('{') && line.ends_with('}'), "{stdout:?}");
+    assert!(
+        line.contains(&format!("\"version\":\"{}\"", env!("CARGO_PKG_VERSION"))),
+        "missing version field: {stdout:?}"
+    );
+    for key in ["commit", "rustc", "target"] {
+        assert!(
+            line.contains(&format!("\"{key}\":\"")),
+            "missing {key}: {stdout:?}"
+        );
+    }
+}
+
+#[test]
+fn format_without_version_is_rejected() {
+    let output = flash()
+        .args(["--format", "json", "echo", "hi"])
+        .output()
+        .expect("spawn");
+    assert!(!output.status.success());
+}
+
+#[test]
+fn zero_stats_interval_is_rejected() {
+    let output = flash()
+        .args(["--stats", "--stats-interval", "0", "echo", "hi"])
+        .output()
+        .expect("spawn");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--stats-interval"), "{stderr}");
+}
+
+#[test]
+fn watch_stdin_with_empty_input_is_an_error() {
+    let output = flash()
+        .args(["--watch-stdin", "echo", "hi"])
+        .stdin(Stdio::null())
+        .output()
+        .expect("spawn");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--watch-stdin"), "{stderr}");
+}
+
+#[test]
+fn list_watchers_reports_the_backend_without_a_command() {
+    let output = flash().arg("--list-watchers").output().expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let backend = stdout
+        .lines()
+        .find_map(|l| l.strip_prefix("backend: "))
+        .unwrap_or_else(|| panic!("no backend line in {stdout:?}"));
+    assert!(!backend.trim().is_empty(), "{stdout:?}");
+}
+
 #[test]
 fn unknown_flag_is_rejected_with_clap_styled_error() {
     let output = flash()
@@ -160,6 +284,224 @@ fn glob_with_missing_root_is_rejected() {
     );
 }
 
+#[test]
+fn max_dirs_limit_fails_before_running_the_command() {
+    let workspace = Workspace::new();
+    for dir in ["a", "b", "c/d"] {
+        std::fs::create_dir_all(workspace.watch_dir().join(dir)).unwrap();
+    }
+    let marker = workspace.marker("should-never-appear");
+
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["--max-dirs", "2", "--initial"])
+        .arg(format!("printf x > {}", marker.display()))
+        .output()
+        .expect("spawn");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("more than 2 directories"),
+        "stderr should explain the directory limit; got:\n{stderr}"
+    );
+    assert!(!marker.exists(), "command must not run past a failed limit");
+}
+
+#[test]
+fn max_memory_stops_flash_once_its_own_memory_is_over_the_limit() {
+    let workspace = Workspace::new();
+
+    // No real session fits in a megabyte, so the first sample is over.
+    let mut cmd = flash();
+    cmd.args(["-w", &workspace.watch_str()])
+        .args(["--max-memory", "1"])
+        .arg("true");
+    let mut child = spawn_capturing(cmd);
+    let status = wait_for_exit(&mut child, Duration::from_secs(5))
+        .expect("flash-watcher should exit instead of watching");
+    assert!(!status.success(), "going over the limit is an error");
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert!(
+        stderr.contains("over the --max-memory limit of 1 MB"),
+        "stderr should say why it stopped; got:\n{stderr}"
+    );
+}
+
+#[test]
+fn failing_before_command_aborts_startup_with_its_exit_code() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("should-never-appear");
+
+    let mut cmd = flash();
+    cmd.args(["-w", &workspace.watch_str()])
+        .args(["--before", "exit 3", "--initial"])
+        .arg(format!("printf x > {}", marker.display()));
+    let mut child = spawn_capturing(cmd);
+    let status = wait_for_exit(&mut child, Duration::from_secs(5))
+        .expect("flash-watcher should exit instead of watching");
+    assert_eq!(status.code(), Some(3), "exit code mirrors --before");
+    let mut stderr = String::new();
+    child
+        .stderr
+        .take()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+    assert!(
+        stderr.contains("--before command `exit 3` exited"),
+        "stderr should name the failed --before command; got:\n{stderr}"
+    );
+    assert!(!marker.exists(), "the watched command must not run");
+}
+
+#[test]
+fn after_command_runs_when_max_runs_ends_with_a_failure() {
+    let workspace = Workspace::new();
+    let cleanup = workspace.marker("cleaned-up");
+
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["--after", &format!("printf x > {}", cleanup.display())])
+        .args(["--initial", "--max-runs", "1", "--count-initial"])
+        .arg("exit 4")
+        .output()
+        .expect("spawn");
+    assert_eq!(output.status.code(), Some(4), "exit code mirrors the run");
+    assert!(cleanup.exists(), "--after runs even though the run failed");
+}
+
+#[test]
+fn require_initial_exits_with_the_failing_initial_runs_status() {
+    let workspace = Workspace::new();
+    let cleanup = workspace.marker("cleaned-up");
+
+    // Should it start watching anyway, --idle-timeout ends it with status 0.
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["--after", &format!("printf x > {}", cleanup.display())])
+        .args(["--require-initial", "--idle-timeout", "5"])
+        .arg("exit 5")
+        .output()
+        .expect("spawn");
+    assert_eq!(output.status.code(), Some(5), "exit code mirrors the run");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("not watching (--require-initial)"),
+        "stderr should say why it stopped; got:\n{stderr}"
+    );
+    assert!(cleanup.exists(), "--after still runs on the way out");
+}
+
+#[cfg(unix)]
+#[test]
+fn command_after_double_dash_runs_without_the_shell() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = Workspace::new();
+    let args = workspace.marker("args");
+    let tool = workspace.marker("print args");
+    std::fs::write(
+        &tool,
+        format!(
+            "#!/bin/sh\nprintf '%s\\n' \"$#\" \"$@\" > '{}'\n",
+            args.display()
+        ),
+    )
+    .expect("write script");
+    std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).expect("chmod");
+    let tool = tool.to_string_lossy().into_owned();
+
+    let run = |command: &[&str]| {
+        let output = flash()
+            .args(["-w", &workspace.watch_str()])
+            .args(["--initial", "--max-runs", "1", "--count-initial", "--"])
+            .args(command)
+            .output()
+            .expect("spawn");
+        assert!(
+            output.status.success(),
+            "{command:?} should run; got {output:?}"
+        );
+        std::fs::read_to_string(&args).expect("the script ran")
+    };
+    assert_eq!(run(&[&tool, "two words"]), "1\ntwo words\n");
+    // A lone argument with a space would otherwise go to `sh -c`, which
+    // splits it.
+    assert_eq!(run(&[&tool]), "0\n");
+}
+
+#[test]
+fn verbose_reports_how_long_each_startup_phase_took() {
+    let workspace = Workspace::new();
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["--verbose", "--initial", "--idle-timeout", "1", "true"])
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout
+        .lines()
+        .find(|line| line.starts_with("startup"))
+        .unwrap_or_else(|| panic!("no startup timings in:\n{stdout}"));
+
+    let millis = |took: &str| match took.strip_suffix("ms") {
+        Some(ms) => ms.parse::<f64>().expect(line),
+        None => {
+            took.strip_suffix('s')
+                .expect(line)
+                .parse::<f64>()
+                .expect(line)
+                * 1000.0
+        }
+    };
+    // "startup · config 0.2ms, filters 0.1ms, ..., 2.3ms in all"
+    let (_, timings) = line.split_once(' ').unwrap();
+    let (phases, total) = timings
+        .trim_start_matches(['·', '-', ' '])
+        .rsplit_once(", ")
+        .expect(line);
+    let total = millis(total.strip_suffix(" in all").expect(line));
+    let phases: Vec<(&str, f64)> = phases
+        .split(", ")
+        .map(|timing| {
+            let (phase, took) = timing.rsplit_once(' ').expect(line);
+            (phase, millis(took))
+        })
+        .collect();
+    let names: Vec<&str> = phases.iter().map(|(phase, _)| *phase).collect();
+    assert_eq!(
+        names,
+        ["config", "filters", "initial run", "watches"],
+        "{line}"
+    );
+    let sum: f64 = phases.iter().map(|(_, ms)| ms).sum();
+    assert!(sum <= total + 0.5 && total < 10_000.0, "{line}");
+}
+
+#[test]
+fn before_command_runs_ahead_of_the_initial_run() {
+    let workspace = Workspace::new();
+    let setup = workspace.marker("generated");
+    let marker = workspace.marker("initial-ran");
+
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["--before", &format!("printf x > {}", setup.display())])
+        .args(["--initial", "--max-runs", "1", "--count-initial"])
+        .arg(format!("cat {} > {}", setup.display(), marker.display()))
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(std::fs::read_to_string(&marker).unwrap(), "x");
+}
+
 #[test]
 fn invalid_glob_pattern_is_rejected() {
     let workspace = Workspace::new();
@@ -190,3 +532,108 @@ fn invalid_config_path_is_rejected() {
         "stderr should mention reading the config file; got:\n{stderr}"
     );
 }
+
+#[test]
+fn explain_reports_why_a_path_is_rejected_and_exits() {
+    let workspace = Workspace::new();
+    workspace.write("build/out.rs", "");
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .args(["-e", "rs", "-i", "**/build/**"])
+        .arg("--explain")
+        .arg(workspace.watch_dir().join("build").join("out.rs"))
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("rejected: matches ignore pattern '**/build/**'"),
+        "unexpected explanation:\n{stdout}"
+    );
+}
+
+#[test]
+fn pattern_file_adds_to_the_include_patterns() {
+    let workspace = Workspace::new();
+    let includes = workspace.marker("includes");
+    std::fs::write(&includes, "# generated code\ngen/**\n").unwrap();
+
+    let explain = |path: &str| {
+        let output = flash()
+            .args(["-w", &workspace.watch_str(), "-p", "src/**"])
+            .arg("--pattern-file")
+            .arg(&includes)
+            .arg("--explain")
+            .arg(workspace.watch_dir().join(path))
+            .output()
+            .expect("spawn");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert!(explain("src/lib.rs").contains("matches include pattern 'src/**'"));
+    assert!(explain("gen/api.rs").contains("matches include pattern 'gen/**'"));
+    assert!(explain("docs/notes.md").contains("matches no include pattern"));
+}
+
+#[test]
+fn include_patterns_still_apply_under_a_watch_glob() {
+    let workspace = Workspace::new();
+    let includes = workspace.marker("includes");
+    std::fs::write(&includes, "**/gen/**\n").unwrap();
+    let glob = format!("{}/src/**/*.rs", workspace.watch_str());
+    std::fs::create_dir_all(workspace.watch_dir().join("src")).unwrap();
+
+    let explain = |path: &str| {
+        let output = flash()
+            .args(["-w", &glob, "-p", "**/lib.rs"])
+            .arg("--pattern-file")
+            .arg(&includes)
+            .arg("--explain")
+            .arg(workspace.watch_dir().join(path))
+            .output()
+            .expect("spawn");
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    assert!(explain("src/lib.rs").contains("matches include pattern '**/lib.rs'"));
+    assert!(explain("src/gen/api.rs").contains("matches include pattern '**/gen/**'"));
+    assert!(explain("src/main.rs").contains("matches no include pattern"));
+    assert!(explain("src/notes.md").contains("doesn't match the --watch glob"));
+}
+
+#[test]
+fn explain_names_the_watch_glob_that_keeps_a_path_too_deep() {
+    let workspace = Workspace::new();
+    let src = workspace.watch_dir().join("src");
+    std::fs::create_dir_all(src.join("a/deep")).unwrap();
+    let glob = format!("{}/*", src.display());
+
+    let output = flash()
+        .args(["-w", &glob])
+        .arg("--explain")
+        .arg(src.join("a/deep/y.rs"))
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("deeper than the --watch glob"), "{stdout}");
+    assert!(!stdout.contains("--no-recursive"), "{stdout}");
+}
+
+#[test]
+fn pattern_from_is_an_alias_for_pattern_file() {
+    let workspace = Workspace::new();
+    let includes = workspace.marker("includes");
+    std::fs::write(&includes, "gen/**\n").unwrap();
+
+    let output = flash()
+        .args(["-w", &workspace.watch_str()])
+        .arg("--pattern-from")
+        .arg(&includes)
+        .arg("--explain")
+        .arg(workspace.watch_dir().join("gen/api.rs"))
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("matches include pattern 'gen/**'"));
+}
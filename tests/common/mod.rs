@@ -5,6 +5,7 @@
 //! silences that without weakening warnings in production code.
 #![allow(dead_code)]
 
+use std::io::Read;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
 use std::thread;
@@ -68,6 +69,19 @@ pub fn spawn_capturing(mut cmd: Command) -> Child {
         .expect("spawn flash-watcher")
 }
 
+/// Block until the ready byte arrives on the child's stdout. The child must
+/// have been spawned with `--ready-fd 1` via [`spawn_capturing`]; stdout is
+/// otherwise unused, so pair this with `--fast`.
+pub fn wait_until_ready(child: &mut Child) {
+    let mut byte = [0u8; 1];
+    child
+        .stdout
+        .as_mut()
+        .expect("piped stdout")
+        .read_exact(&mut byte)
+        .expect("read ready signal");
+}
+
 pub fn wait_for_path(path: &Path, timeout: Duration) -> bool {
     let deadline = Instant::now() + timeout;
     while Instant::now() < deadline {
@@ -37,6 +37,80 @@ fn config_supplies_command_and_watch_paths() {
     assert!(fired, "config-driven invocation should fire the command");
 }
 
+#[test]
+fn config_brace_pattern_matches_every_alternative() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "command:\n  - 'printf x >> {marker}'\nwatch:\n  - {watch}\npattern:\n  - '*.{{js,ts}}'\ndebounce: 10\n",
+            marker = marker.display(),
+            watch = workspace.watch_dir().display(),
+        ),
+    )
+    .unwrap();
+
+    let mut c = flash();
+    c.arg("--fast").args(["-f", config.to_str().unwrap()]);
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+    workspace.write("skip.py", "no");
+    let quiet = wait_for_path_absent(&marker, Duration::from_millis(700));
+    workspace.write("app.js", "js");
+    let js = wait_for_path(&marker, Duration::from_secs(5));
+    thread::sleep(Duration::from_millis(300));
+    let _ = fs::remove_file(&marker);
+    workspace.write("app.ts", "ts");
+    let ts = wait_for_path(&marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(quiet, "a .py change should not match *.{{js,ts}}");
+    assert!(js, "*.{{js,ts}} should match .js");
+    assert!(ts, "*.{{js,ts}} should match .ts");
+}
+
+#[test]
+fn per_root_filters_diverge_between_watch_roots() {
+    let workspace = Workspace::new();
+    let assets = workspace.root.path().join("assets");
+    fs::create_dir(&assets).unwrap();
+    let marker = workspace.marker("marker");
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "command:\n  - 'printf x >> {marker}'\nwatch:\n  - path: {src}\n    ext: rs\n  - path: {assets}\n    ext: png\ndebounce: 10\n",
+            marker = marker.display(),
+            src = workspace.watch_dir().display(),
+            assets = assets.display(),
+        ),
+    )
+    .unwrap();
+
+    let mut c = flash();
+    c.arg("--fast").args(["-f", config.to_str().unwrap()]);
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+    workspace.write("logo.png", "not under assets");
+    fs::write(assets.join("lib.rs"), "not under src").unwrap();
+    let quiet = wait_for_path_absent(&marker, Duration::from_millis(700));
+
+    fs::write(assets.join("logo.png"), "png").unwrap();
+    let fired = wait_for_path(&marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(quiet, "each root should only admit its own extension");
+    assert!(fired, "a .png under assets should fire");
+}
+
 #[test]
 fn cli_overrides_command_from_config() {
     let workspace = Workspace::new();
@@ -80,6 +154,175 @@ fn cli_overrides_command_from_config() {
     );
 }
 
+#[test]
+fn print_config_dumps_the_merged_settings_and_exits() {
+    let workspace = Workspace::new();
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        "command:\n  - cargo\n  - test\ndebounce: 999\nrestart: true\n",
+    )
+    .unwrap();
+
+    let output = flash()
+        .args(["-f", config.to_str().unwrap()])
+        .args(["--debounce", "25", "--print-config"])
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in [
+        "- cargo",
+        "debounce: 25",
+        "restart: true",
+        "stats_interval: 10",
+    ] {
+        assert!(
+            stdout.lines().any(|l| l == line),
+            "expected `{line}` in the dump; got:\n{stdout}"
+        );
+    }
+}
+
+#[test]
+fn check_config_passes_a_valid_config_without_running_it() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "command:\n  - 'printf x > {marker}'\nwatch:\n  - {watch}\npattern:\n  - 'src/**/*.rs'\ninitial: true\n",
+            marker = marker.display(),
+            watch = workspace.watch_dir().display(),
+        ),
+    )
+    .unwrap();
+
+    let output = flash()
+        .args(["-f", config.to_str().unwrap(), "--check-config"])
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "config ok\n");
+    assert!(!marker.exists(), "checking must not run the command");
+}
+
+#[test]
+fn check_config_reports_every_problem_and_fails() {
+    let workspace = Workspace::new();
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "watch:\n  - {watch}\npattern:\n  - 'src/[ab'\n",
+            watch = workspace.watch_dir().display(),
+        ),
+    )
+    .unwrap();
+
+    let output = flash()
+        .args(["-f", config.to_str().unwrap(), "--check-config"])
+        .output()
+        .expect("spawn");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for expected in [
+        "no command specified",
+        "invalid glob 'src/[ab' at column 5",
+        "the config has 2 problems",
+    ] {
+        assert!(
+            stderr.contains(expected),
+            "expected `{expected}` in the report; got:\n{stderr}"
+        );
+    }
+}
+
+#[test]
+fn config_schema_is_json_describing_the_config_keys() {
+    let output = flash().arg("--config-schema").output().expect("spawn");
+    assert!(output.status.success());
+    let schema: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("the schema should be valid JSON");
+    let properties = schema["properties"]
+        .as_object()
+        .expect("the schema should list properties");
+    for key in ["command", "debounce", "watch"] {
+        assert!(
+            properties.contains_key(key),
+            "expected `{key}` in the schema; got:\n{schema:#}"
+        );
+    }
+}
+
+#[test]
+fn repeated_config_files_layer_in_order() {
+    let workspace = Workspace::new();
+    let team = workspace.root.path().join("team.yaml");
+    let personal = workspace.root.path().join("personal.yaml");
+    fs::write(
+        &team,
+        "command:\n  - cargo\n  - check\ndebounce: 500\nrestart: true\n",
+    )
+    .unwrap();
+    fs::write(&personal, "debounce: 100\n").unwrap();
+
+    let output = flash()
+        .args(["-f", team.to_str().unwrap()])
+        .args(["-f", personal.to_str().unwrap()])
+        .arg("--print-config")
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in ["- check", "debounce: 100", "restart: true"] {
+        assert!(
+            stdout.lines().any(|l| l == line),
+            "expected `{line}` in the dump; got:\n{stdout}"
+        );
+    }
+}
+
+#[test]
+fn init_writes_a_starter_config_and_refuses_to_clobber_it() {
+    let workspace = Workspace::new();
+    let config = workspace.root.path().join("flash.yaml");
+
+    let output = flash()
+        .arg("--init")
+        .current_dir(workspace.root.path())
+        .output()
+        .expect("spawn");
+    assert!(output.status.success(), "--init should succeed");
+    assert!(config.exists(), "--init should write flash.yaml in the cwd");
+
+    fs::write(&config, "command: [mine]\n").unwrap();
+    let output = flash()
+        .arg("--init")
+        .current_dir(workspace.root.path())
+        .output()
+        .expect("spawn");
+    assert!(
+        !output.status.success(),
+        "--init must not overwrite silently"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--force"),
+        "stderr should suggest --force; got:\n{stderr}"
+    );
+    assert_eq!(fs::read_to_string(&config).unwrap(), "command: [mine]\n");
+
+    let output = flash()
+        .args(["--init", "--force"])
+        .current_dir(workspace.root.path())
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    assert_ne!(fs::read_to_string(&config).unwrap(), "command: [mine]\n");
+}
+
 #[test]
 fn invalid_config_yaml_is_rejected_with_context() {
     let workspace = Workspace::new();
@@ -113,3 +356,157 @@ fn unknown_config_keys_are_rejected() {
         "unknown keys in config should fail the run"
     );
 }
+
+#[cfg(unix)]
+fn config_in_watch_dir(workspace: &Workspace, marker: &std::path::Path, ext: &str) -> String {
+    let config = workspace.watch_dir().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "command:\n  - 'printf x >> {marker}'\nwatch:\n  - {watch}\next: {ext}\ndebounce: 10\n",
+            marker = marker.display(),
+            watch = workspace.watch_dir().display(),
+        ),
+    )
+    .unwrap();
+    config.to_string_lossy().into_owned()
+}
+
+#[cfg(unix)]
+#[test]
+fn editing_the_active_config_does_not_run_the_command() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let config = config_in_watch_dir(&workspace, &marker, "yaml");
+
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--ready-fd", "1"])
+        .args(["-f", &config]);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    fs::write(&config, fs::read_to_string(&config).unwrap() + "# edited\n").unwrap();
+    let absent = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("other.yaml", "x");
+    let fired = wait_for_path(&marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        absent,
+        "the config file itself should be ignored even though `ext: yaml` matches it"
+    );
+    assert!(fired, "other matching files still trigger the command");
+}
+
+#[cfg(unix)]
+#[test]
+fn reload_config_applies_edited_filters_live() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let config = config_in_watch_dir(&workspace, &marker, "rs");
+
+    let mut c = flash();
+    c.arg("--fast")
+        .arg("--reload-config")
+        .args(["--ready-fd", "1"])
+        .args(["-f", &config]);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    let edited = fs::read_to_string(&config)
+        .unwrap()
+        .replace("ext: rs", "ext: txt");
+    fs::write(&config, edited).unwrap();
+    let absent = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("notes.txt", "x");
+    let fired = wait_for_path(&marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(absent, "reloading the config must not run the command");
+    assert!(
+        fired,
+        "the reloaded `ext: txt` filter should let .txt changes through"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn reload_config_follows_edits_to_an_extended_base() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let base = config_in_watch_dir(&workspace, &marker, "rs");
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(&config, format!("extends: {base}\n")).unwrap();
+
+    let mut c = flash();
+    c.arg("--fast")
+        .arg("--reload-config")
+        .args(["--ready-fd", "1"])
+        .args(["-f", config.to_str().unwrap()]);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    let edited = fs::read_to_string(&base)
+        .unwrap()
+        .replace("ext: rs", "ext: yaml,txt");
+    fs::write(&base, edited).unwrap();
+    let absent = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("notes.txt", "x");
+    let fired = wait_for_path(&marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(absent, "editing the base config must not run the command");
+    assert!(fired, "the base's new `ext` should apply after a reload");
+}
+
+#[cfg(unix)]
+#[test]
+fn projects_route_each_change_to_its_own_command() {
+    let workspace = Workspace::new();
+    let api_marker = workspace.marker("api-ran");
+    let web_marker = workspace.marker("web-ran");
+    workspace.write("api/main.rs", "");
+    workspace.write("web/index.js", "");
+    let config = workspace.root.path().join("flash.yaml");
+    fs::write(
+        &config,
+        format!(
+            "debounce: 10\nprojects:\n  \
+             - watch: [{api}]\n    command: ['printf x >> {api_marker}']\n  \
+             - watch: [{web}]\n    command: ['printf x >> {web_marker}']\n",
+            api = workspace.watch_dir().join("api").display(),
+            web = workspace.watch_dir().join("web").display(),
+            api_marker = api_marker.display(),
+            web_marker = web_marker.display(),
+        ),
+    )
+    .unwrap();
+
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--ready-fd", "1"])
+        .args(["-f", config.to_str().unwrap()]);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    workspace.write("api/main.rs", "fn main() {}");
+    let api_ran = wait_for_path(&api_marker, Duration::from_secs(5));
+    let web_idle = wait_for_path_absent(&web_marker, STEADY_STATE);
+    workspace.write("web/index.js", "main()");
+    let web_ran = wait_for_path(&web_marker, Duration::from_secs(5));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(api_ran, "a change under api/ should run the api command");
+    assert!(web_idle, "a change under api/ must not run the web command");
+    assert!(web_ran, "a change under web/ should run the web command");
+    assert_eq!(
+        fs::read_to_string(&api_marker).unwrap(),
+        "x",
+        "a change under web/ must not run the api command"
+    );
+}
@@ -6,7 +6,8 @@
 mod common;
 
 use std::fs;
-use std::process::Command;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -47,6 +48,29 @@ fn file_modification_triggers_the_command() {
     assert!(fired, "modifying a file should run the command");
 }
 
+#[cfg(unix)]
+#[test]
+fn ready_fd_signals_once_watching_so_no_settle_sleep_is_needed() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_capturing(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--ready-fd", "1"],
+    ));
+    wait_until_ready(&mut child);
+
+    workspace.write("right-after-ready.txt", "v1");
+
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        fired,
+        "a change made right after the ready signal should be seen"
+    );
+}
+
 #[test]
 fn file_creation_triggers_the_command() {
     let workspace = Workspace::new();
@@ -89,6 +113,51 @@ fn extension_filter_keeps_unrelated_files_quiet() {
     assert!(fired, "extension filter should still admit .rs changes");
 }
 
+/// Start Flash with `flag`, make the change it should ignore, then the one
+/// it should act on. Reports (stayed quiet, then fired).
+fn entry_kind_filter(
+    flag: &str,
+    quiet_change: fn(&Workspace),
+    firing_change: fn(&Workspace),
+) -> (bool, bool) {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_silent(watcher_command(&workspace.watch_str(), &marker, &[flag]));
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+    quiet_change(&workspace);
+    let quiet = wait_for_path_absent(&marker, Duration::from_millis(700));
+    firing_change(&workspace);
+    let fired = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    (quiet, fired)
+}
+
+fn create_dir(workspace: &Workspace) {
+    fs::create_dir(workspace.watch_dir().join("module")).unwrap();
+}
+
+fn create_file(workspace: &Workspace) {
+    workspace.write("lib.rs", "fn main() {}");
+}
+
+#[test]
+fn dirs_only_fires_for_a_new_directory_but_not_a_file() {
+    let (quiet, fired) = entry_kind_filter("--dirs-only", create_file, create_dir);
+    assert!(quiet, "a new file should not fire with --dirs-only");
+    assert!(fired, "a new directory should fire with --dirs-only");
+}
+
+#[test]
+fn files_only_fires_for_a_new_file_but_not_a_directory() {
+    let (quiet, fired) = entry_kind_filter("--files-only", create_dir, create_file);
+    assert!(quiet, "a new directory should not fire with --files-only");
+    assert!(fired, "a new file should fire with --files-only");
+}
+
 #[test]
 fn ignore_pattern_keeps_matching_paths_quiet() {
     let workspace = Workspace::new();
@@ -123,6 +192,44 @@ fn ignore_pattern_keeps_matching_paths_quiet() {
     assert!(fired, "non-ignored change should still fire");
 }
 
+/// Run Flash from `cwd` with the given `-w` spec and a `-p` pattern that is
+/// relative to the watch root, then report whether a matching write fired.
+fn relative_pattern_fires(workspace: &Workspace, cwd: &std::path::Path, watch: &str) -> bool {
+    let marker = workspace.marker("marker");
+    let mut c = watcher_command(watch, &marker, &["-p", "sub/*.rs"]);
+    c.current_dir(cwd);
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+    workspace.write("sub/lib.rs", "fn x() {}");
+
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    fired
+}
+
+#[test]
+fn include_pattern_is_relative_to_an_absolute_watch_root() {
+    let workspace = Workspace::new();
+    fs::create_dir(workspace.watch_dir().join("sub")).unwrap();
+    assert!(
+        relative_pattern_fires(&workspace, workspace.root.path(), &workspace.watch_str()),
+        "root-relative pattern should match under an absolute -w path"
+    );
+}
+
+#[test]
+fn include_pattern_is_relative_to_a_relative_watch_root() {
+    let workspace = Workspace::new();
+    fs::create_dir(workspace.watch_dir().join("sub")).unwrap();
+    assert!(
+        relative_pattern_fires(&workspace, workspace.root.path(), "watch"),
+        "root-relative pattern should match under a relative -w path"
+    );
+}
+
 #[test]
 fn initial_flag_runs_the_command_before_any_change() {
     let workspace = Workspace::new();
@@ -185,114 +292,1336 @@ fn debounce_window_coalesces_bursty_writes() {
 }
 
 #[test]
-fn multiple_watch_roots_are_all_observed() {
+fn no_debounce_pattern_skips_the_debounce_window() {
     let workspace = Workspace::new();
-    let extra_dir = workspace.root.path().join("extra");
-    fs::create_dir(&extra_dir).unwrap();
-    fs::write(extra_dir.join("seed.txt"), "seed").unwrap();
     let marker = workspace.marker("marker");
 
     let mut c = flash();
     c.arg("--fast")
-        .args(["--debounce", "10"])
+        .args(["--debounce", "3s"])
+        .args(["--no-debounce-pattern", "**/reload.trigger"])
         .args(["-w", &workspace.watch_str()])
-        .args(["-w", extra_dir.to_str().unwrap()])
         .arg(touch_marker_cmd(&marker));
     let mut child = spawn_silent(c);
 
     thread::sleep(STEADY_STATE);
     let _ = fs::remove_file(&marker);
 
-    // Change only the *second* watch root and verify the command still fires.
-    fs::write(extra_dir.join("trigger.txt"), "v1").unwrap();
+    let started = Instant::now();
+    workspace.write("reload.trigger", "now");
+    let urgent = wait_for_path(&marker, MAX_E2E);
+    let urgent_after = started.elapsed();
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("other.txt", "later");
+    let held_back = wait_for_path_absent(&marker, Duration::from_millis(1500));
+    let debounced = wait_for_path(&marker, MAX_E2E);
 
-    let fired = wait_for_path(&marker, MAX_E2E);
     let _ = child.kill();
     let _ = child.wait();
-    assert!(fired, "second watch root should also be observed");
+    assert!(
+        urgent,
+        "a change matching the pattern should run the command"
+    );
+    assert!(
+        urgent_after < Duration::from_secs(2),
+        "the matching change should not wait out the 3s window; took {urgent_after:?}"
+    );
+    assert!(held_back, "other changes should still wait for the window");
+    assert!(debounced, "other changes should run once the window closes");
 }
 
+#[cfg(unix)]
 #[test]
-fn restart_mode_kills_the_previous_long_running_child() {
+fn command_from_matched_file_runs_a_sibling_script_or_the_command() {
+    use std::os::unix::fs::PermissionsExt;
+
     let workspace = Workspace::new();
-    let stamp = workspace.marker("stamp");
+    let marker = workspace.marker("marker");
+    let scripted = workspace.marker("scripted");
+    let script = workspace.watch_dir().join("app/build.sh");
+    workspace.write(
+        "app/build.sh",
+        &format!("#!/bin/sh\n{}\n", touch_marker_cmd(&scripted)),
+    );
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).expect("chmod");
+    fs::create_dir(workspace.watch_dir().join("docs")).expect("create docs dir");
+
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--command-from-matched-file", "build.sh"],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("app/main.c", "int main;");
+    let ran_script = wait_for_path(&scripted, MAX_E2E);
+    let ran_command_too = marker.exists();
+
+    workspace.write("docs/notes.txt", "no script here");
+    let ran_command = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(ran_script, "a change next to build.sh should run it");
+    assert!(
+        !ran_command_too,
+        "the script replaces the command for its changes"
+    );
+    assert!(
+        ran_command,
+        "a change with no script nearby should run the command"
+    );
+}
+
+#[test]
+fn zero_debounce_runs_for_every_event() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
 
-    // The command sleeps long enough that, without restart-mode, a second
-    // change would be running while the first is still asleep. Restart mode
-    // must kill the first process before launching the second.
-    let cmd = format!("echo run >> {stamp} && sleep 2", stamp = stamp.display());
     let mut c = flash();
     c.arg("--fast")
-        .args(["--debounce", "10"])
-        .arg("--restart")
+        .args(["--debounce", "0"])
         .args(["-w", &workspace.watch_str()])
-        .arg(&cmd);
+        .arg(touch_marker_cmd(&marker));
     let mut child = spawn_silent(c);
 
     thread::sleep(STEADY_STATE);
-    let _ = fs::remove_file(&stamp);
+    let _ = fs::remove_file(&marker);
 
-    workspace.write("a.txt", "1");
-    thread::sleep(Duration::from_millis(400));
-    workspace.write("a.txt", "2");
-    thread::sleep(Duration::from_millis(400));
+    workspace.write("same.txt", "1");
+    workspace.write("same.txt", "2");
+    thread::sleep(Duration::from_millis(800));
 
     let _ = child.kill();
     let _ = child.wait();
 
-    // Two changes ⇒ at least two `run` lines, but each previous run is killed
-    // before completing the `sleep`. We just want to see that the command ran
-    // multiple times without piling up children indefinitely.
-    let log = fs::read_to_string(&stamp).unwrap_or_default();
-    let runs = log.lines().filter(|l| l.trim() == "run").count();
+    let bytes = fs::read(&marker).unwrap_or_default();
     assert!(
-        runs >= 2,
-        "restart mode should have launched the command at least twice; saw:\n{log}"
+        bytes.len() >= 2,
+        "two rapid writes to one file should each run the command with no debounce; saw {} runs",
+        bytes.len()
     );
 }
 
 #[test]
-fn fast_mode_does_not_print_the_banner() {
+fn max_runs_exits_after_exactly_n_runs_with_the_last_status() {
     let workspace = Workspace::new();
     let marker = workspace.marker("marker");
     let mut c = flash();
     c.arg("--fast")
-        .args(["--debounce", "10"])
+        .args(["--debounce", "10", "--max-runs", "3"])
         .args(["-w", &workspace.watch_str()])
-        .arg(touch_marker_cmd(&marker));
-    let mut child = spawn_capturing(c);
+        .arg(format!("printf x >> {}; exit 3", marker.display()));
+    let mut child = spawn_silent(c);
 
-    thread::sleep(Duration::from_millis(800));
-    let _ = child.kill();
+    thread::sleep(STEADY_STATE);
+    let deadline = Instant::now() + MAX_E2E;
+    let mut i = 0;
+    while Instant::now() < deadline && child.try_wait().unwrap().is_none() {
+        workspace.write("stream.txt", &format!("v{i}"));
+        i += 1;
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(2))
+        .expect("flash should exit once --max-runs is used up");
+    assert_eq!(
+        status.code(),
+        Some(3),
+        "exit status should mirror the command"
+    );
+    let runs = fs::read(&marker).unwrap_or_default().len();
+    assert_eq!(runs, 3, "expected exactly 3 runs, saw {runs}");
+}
+
+#[test]
+fn fail_fast_exits_with_the_status_of_the_first_failed_run() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10", "--fail-fast"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(format!(
+            "printf x >> {m}; [ $(wc -c < {m}) -lt 2 ] || exit 4",
+            m = marker.display()
+        ));
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    workspace.write("first.txt", "one");
+    assert!(wait_for_path(&marker, MAX_E2E), "first change should run");
+    assert!(
+        child.try_wait().unwrap().is_none(),
+        "a passing run keeps watching"
+    );
+
+    let deadline = Instant::now() + MAX_E2E;
+    let mut i = 0;
+    while Instant::now() < deadline && child.try_wait().unwrap().is_none() {
+        workspace.write("second.txt", &format!("v{i}"));
+        i += 1;
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    let status = wait_for_exit(&mut child, Duration::from_secs(2))
+        .expect("flash should stop after the failing run");
+    assert_eq!(
+        status.code(),
+        Some(4),
+        "exit status should mirror the command"
+    );
+    assert_eq!(fs::read(&marker).unwrap_or_default().len(), 2);
+}
+
+#[test]
+fn idle_timeout_exits_cleanly_when_nothing_changes() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let started = Instant::now();
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--idle-timeout", "1"],
+    ));
+    let status = wait_for_exit(&mut child, MAX_E2E).expect("flash should exit once idle");
+    assert!(status.success(), "idle exit is a clean exit: {status:?}");
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert!(!marker.exists(), "nothing changed, so nothing ran");
+}
+
+#[test]
+fn exit_summary_reports_changes_runs_and_failures() {
+    let workspace = Workspace::new();
+    let mut cmd = flash();
+    cmd.args(["--fast", "--debounce", "10", "--ready-fd", "1"])
+        .args(["--initial", "--count-initial", "--max-runs", "2"])
+        .arg("--print-events-summary-on-exit")
+        .args(["-w", &workspace.watch_str()])
+        .arg("false");
+    let mut child = spawn_capturing(cmd);
+    wait_until_ready(&mut child);
+
+    workspace.write("a.txt", "a");
     let output = child.wait_with_output().expect("collect output");
+    assert!(!output.status.success(), "the last run failed: {output:?}");
 
     let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout
+        .lines()
+        .find(|l| l.contains("session"))
+        .unwrap_or_else(|| panic!("no session line in:\n{stdout}"));
     assert!(
-        !stdout.contains("watching for changes"),
-        "--fast should suppress the banner; got:\n{stdout}"
+        summary.contains("1 change · 2 runs · 2 failed · up "),
+        "{summary}"
     );
 }
 
 #[test]
-fn default_mode_prints_a_banner_with_watch_roots() {
+fn exit_summary_counts_changes_dropped_by_a_full_buffer() {
     let workspace = Workspace::new();
-    let marker = workspace.marker("marker");
-    let mut c = flash();
-    c.args(["--debounce", "10"])
+    let mut cmd = flash();
+    cmd.args(["--fast", "--debounce", "0", "--ready-fd", "1"])
+        .args(["--buffer-size", "1", "--max-runs", "2"])
+        .arg("--print-events-summary-on-exit")
         .args(["-w", &workspace.watch_str()])
-        .arg(touch_marker_cmd(&marker));
-    let mut child = spawn_capturing(c);
+        .args(["sleep", "1"]);
+    let mut child = spawn_capturing(cmd);
+    wait_until_ready(&mut child);
 
-    // Give the banner a moment to print.
-    let banner_deadline = Instant::now() + Duration::from_millis(800);
-    while Instant::now() < banner_deadline {
-        thread::sleep(Duration::from_millis(20));
+    workspace.write("first.txt", "a");
+    // While the first run sleeps, the one-slot buffer fills at once.
+    thread::sleep(Duration::from_millis(300));
+    for i in 0..20 {
+        workspace.write(&format!("burst-{i}.txt"), "b");
     }
-    let _ = child.kill();
     let output = child.wait_with_output().expect("collect output");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout
+        .lines()
+        .find(|l| l.contains("session"))
+        .unwrap_or_else(|| panic!("no session line in:\n{stdout}"));
+    let dropped: u64 = summary
+        .split(" · ")
+        .find_map(|part| part.strip_suffix(" dropped"))
+        .unwrap_or_else(|| panic!("no dropped count in: {summary}"))
+        .parse()
+        .unwrap();
+    assert!(dropped > 0, "{summary}");
+}
+
+#[test]
+fn count_only_tallies_accepted_and_rejected_changes_without_a_command() {
+    let workspace = Workspace::new();
+    let mut cmd = flash();
+    cmd.args(["--fast", "--count-only", "--debounce", "10", "-e", "rs"])
+        .args(["--idle-timeout", "2"])
+        .args(["-w", &workspace.watch_str()]);
+    let child = spawn_capturing(cmd);
+
+    thread::sleep(STEADY_STATE);
+    workspace.write("a.rs", "a");
+    workspace.write("b.rs", "b");
+    workspace.write("notes.txt", "n");
+    let output = child.wait_with_output().expect("collect output");
+    assert!(output.status.success(), "{output:?}");
+
     let stdout = String::from_utf8_lossy(&output.stdout);
+    let summary = stdout
+        .lines()
+        .find_map(|line| line.split_once("counted "))
+        .map(|(_, counts)| counts.split_whitespace().collect::<Vec<_>>())
+        .unwrap_or_else(|| panic!("no closing summary:\n{stdout}"));
+    let count = |at: usize| summary[at].parse::<u64>().unwrap();
+    assert_eq!(
+        (summary[1], summary[4]),
+        ("accepted", "rejected"),
+        "{stdout}"
+    );
+    assert!(count(0) >= 2, "both .rs files are accepted:\n{stdout}");
+    assert!(count(3) >= 1, "notes.txt is rejected:\n{stdout}");
+    let tallies = stdout.lines().filter(|l| l.contains("accepted")).count();
     assert!(
-        stdout.contains("watching for changes"),
-        "default banner missing; got:\n{stdout}"
+        tallies > 1,
+        "a running tally precedes the summary:\n{stdout}"
+    );
+}
+
+#[test]
+fn command_output_reaches_the_terminal_by_default() {
+    let workspace = Workspace::new();
+    let output = flash()
+        .args(["--initial", "--max-runs", "1", "--count-initial"])
+        .args(["-w", &workspace.watch_str()])
+        .arg("echo hello-from-the-command")
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("hello-from-the-command"), "{stdout}");
+    assert!(
+        !stdout.contains("output of"),
+        "no delimiters without --capture"
+    );
+}
+
+#[test]
+fn capture_prints_the_output_between_delimiters() {
+    let workspace = Workspace::new();
+    let output = flash()
+        .args([
+            "--initial",
+            "--max-runs",
+            "1",
+            "--count-initial",
+            "--capture",
+        ])
+        .args(["-w", &workspace.watch_str()])
+        .arg("echo hello-from-the-command")
+        .output()
+        .expect("spawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let at = lines
+        .iter()
+        .position(|l| *l == "hello-from-the-command")
+        .unwrap_or_else(|| panic!("output missing:\n{stdout}"));
+    assert!(
+        lines[at - 1].contains("output of `echo hello-from-the-command`"),
+        "{stdout}"
+    );
+    assert!(lines[at + 1].contains("end of output"), "{stdout}");
+}
+
+/// Poll `path` until it holds `needle`, for up to `timeout`.
+fn wait_for_contents(path: &std::path::Path, needle: &str, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if fs::read_to_string(path).is_ok_and(|s| s.contains(needle)) {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[cfg(unix)]
+#[test]
+fn event_placeholder_names_what_happened_to_the_file() {
+    let workspace = Workspace::new();
+    let log = workspace.marker("events");
+    let mut cmd = flash();
+    cmd.args(["--fast", "--debounce", "10", "-e", "rs"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(format!("echo {{event}} >> {}", log.display()));
+    let mut child = spawn_silent(cmd);
+
+    thread::sleep(STEADY_STATE);
+    workspace.write("a.rs", "v1");
+    let created = wait_for_contents(&log, "create", MAX_E2E);
+    thread::sleep(Duration::from_millis(300));
+    workspace.write("a.rs", "v2");
+    let modified = wait_for_contents(&log, "modify", MAX_E2E);
+    fs::remove_file(workspace.watch_dir().join("a.rs")).unwrap();
+    let removed = wait_for_contents(&log, "remove", MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    let seen = fs::read_to_string(&log).unwrap_or_default();
+    assert!(created && modified && removed, "saw:\n{seen}");
+}
+
+#[test]
+fn max_runs_can_count_the_initial_run() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--initial", "--max-runs", "1", "--count-initial"],
+    ));
+
+    let status =
+        wait_for_exit(&mut child, MAX_E2E).expect("flash should exit after the initial run");
+    assert!(status.success());
+    assert_eq!(fs::read(&marker).unwrap_or_default().len(), 1);
+}
+
+#[test]
+fn multiple_watch_roots_are_all_observed() {
+    let workspace = Workspace::new();
+    let extra_dir = workspace.root.path().join("extra");
+    fs::create_dir(&extra_dir).unwrap();
+    fs::write(extra_dir.join("seed.txt"), "seed").unwrap();
+    let marker = workspace.marker("marker");
+
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10"])
+        .args(["-w", &workspace.watch_str()])
+        .args(["-w", extra_dir.to_str().unwrap()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    // Change only the *second* watch root and verify the command still fires.
+    fs::write(extra_dir.join("trigger.txt"), "v1").unwrap();
+
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(fired, "second watch root should also be observed");
+}
+
+#[test]
+fn watch_stdin_watches_every_piped_directory() {
+    let workspace = Workspace::new();
+    let extra_dir = workspace.root.path().join("extra");
+    fs::create_dir(&extra_dir).unwrap();
+    let marker = workspace.marker("marker");
+
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10", "--watch-stdin"])
+        .arg(touch_marker_cmd(&marker))
+        .stdin(Stdio::piped());
+    let mut child = spawn_silent(c);
+    let list = format!(
+        "{}\n{}\n{}\n",
+        workspace.watch_str(),
+        extra_dir.display(),
+        workspace.watch_str()
+    );
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(list.as_bytes())
+        .unwrap();
+
+    thread::sleep(STEADY_STATE);
+    let mut fired = Vec::new();
+    for dir in [workspace.watch_dir(), extra_dir] {
+        let _ = fs::remove_file(&marker);
+        fs::write(dir.join("trigger.txt"), "v1").unwrap();
+        fired.push(wait_for_path(&marker, MAX_E2E));
+        thread::sleep(Duration::from_millis(100));
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    assert_eq!(
+        fired,
+        [true, true],
+        "both piped directories should be watched"
+    );
+}
+
+#[test]
+fn rewatch_picks_up_a_deleted_and_recreated_root() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--rewatch"],
+    ));
+
+    thread::sleep(STEADY_STATE);
+    fs::remove_dir_all(workspace.watch_dir()).unwrap();
+    thread::sleep(Duration::from_millis(500));
+    fs::create_dir(workspace.watch_dir()).unwrap();
+    // Give the poll time to notice the root is back and watch it again.
+    thread::sleep(Duration::from_secs(1));
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("after.txt", "back");
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        fired,
+        "a change in the recreated root should run the command"
+    );
+}
+
+#[test]
+fn rewatch_picks_up_a_root_replaced_between_polls() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--rewatch"],
+    ));
+
+    thread::sleep(STEADY_STATE);
+    // Gone and back before any poll could see the root missing.
+    fs::remove_dir_all(workspace.watch_dir()).unwrap();
+    fs::create_dir(workspace.watch_dir()).unwrap();
+    thread::sleep(Duration::from_secs(1));
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("after.txt", "back");
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        fired,
+        "a change in the replaced root should run the command"
+    );
+}
+
+#[test]
+fn removing_a_root_without_rewatch_warns() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut child = spawn_capturing(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--ready-fd", "1"],
+    ));
+    wait_until_ready(&mut child);
+
+    fs::remove_dir_all(workspace.watch_dir()).unwrap();
+    thread::sleep(Duration::from_secs(1));
+    let _ = child.kill();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("was removed"), "{stderr}");
+    assert!(stderr.contains("--rewatch"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn changes_behind_a_symlinked_dir_are_reported_under_the_real_path() {
+    let workspace = Workspace::new();
+    let real = workspace.watch_dir().join("real");
+    fs::create_dir(&real).unwrap();
+    std::os::unix::fs::symlink(&real, workspace.watch_dir().join("link")).unwrap();
+    let marker = workspace.marker("marker");
+    // notify may report the change under either spelling; only the resolved
+    // one matches this include pattern.
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["-p", "real/**"],
+    ));
+
+    thread::sleep(STEADY_STATE);
+    fs::write(real.join("lib.rs"), "fn main() {}").unwrap();
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(fired, "the change should match real/** once resolved");
+}
+
+#[test]
+fn restart_mode_kills_the_previous_long_running_child() {
+    let workspace = Workspace::new();
+    let stamp = workspace.marker("stamp");
+
+    // The command sleeps long enough that, without restart-mode, a second
+    // change would be running while the first is still asleep. Restart mode
+    // must kill the first process before launching the second.
+    let cmd = format!("echo run >> {stamp} && sleep 2", stamp = stamp.display());
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10"])
+        .arg("--restart")
+        .args(["-w", &workspace.watch_str()])
+        .arg(&cmd);
+    let mut child = spawn_silent(c);
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&stamp);
+
+    workspace.write("a.txt", "1");
+    thread::sleep(Duration::from_millis(400));
+    workspace.write("a.txt", "2");
+    thread::sleep(Duration::from_millis(400));
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    // Two changes ⇒ at least two `run` lines, but each previous run is killed
+    // before completing the `sleep`. We just want to see that the command ran
+    // multiple times without piling up children indefinitely.
+    let log = fs::read_to_string(&stamp).unwrap_or_default();
+    let runs = log.lines().filter(|l| l.trim() == "run").count();
+    assert!(
+        runs >= 2,
+        "restart mode should have launched the command at least twice; saw:\n{log}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn max_runs_stops_a_restart_mode_server_instead_of_waiting_on_it() {
+    let workspace = Workspace::new();
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10", "--max-runs", "1", "--ready-fd", "1"])
+        .arg("--restart")
+        .args(["-w", &workspace.watch_str()])
+        .arg("sleep 30");
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    workspace.write("a.txt", "1");
+    let exited = wait_for_exit(&mut child, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        exited.is_some(),
+        "--max-runs should stop the server it started, not wait for it"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn changes_during_a_blocking_run_coalesce_into_one_follow_up_run() {
+    let workspace = Workspace::new();
+    let stamp = workspace.marker("stamp");
+
+    // Without --restart the command blocks the event loop. Changes made while
+    // it sleeps must produce one more run, not one run per change.
+    let cmd = format!("echo run >> {stamp} && sleep 1", stamp = stamp.display());
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10"])
+        .args(["--ready-fd", "1"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(&cmd);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    workspace.write("first.txt", "1");
+    assert!(wait_for_path(&stamp, MAX_E2E), "first change should run");
+    for i in 0..5 {
+        workspace.write(&format!("during-{i}.txt"), "x");
+        thread::sleep(Duration::from_millis(100));
+    }
+    // First run ends ~1s in, the follow-up ~1s after that; leave headroom.
+    thread::sleep(Duration::from_millis(3000));
+
+    let _ = child.kill();
+    let _ = child.wait();
+
+    let log = fs::read_to_string(&stamp).unwrap_or_default();
+    let runs = log.lines().filter(|l| l.trim() == "run").count();
+    assert_eq!(
+        runs, 2,
+        "five changes during a run should coalesce into one follow-up; saw:\n{log}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn parallel_runs_overlap_and_tag_their_output() {
+    let workspace = Workspace::new();
+    let stamp = workspace.marker("stamp");
+
+    // Run one after another, three of these take at least 3s.
+    let cmd = format!("echo start; sleep 1; echo done >> {}", stamp.display());
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10"])
+        .args(["--ready-fd", "1"])
+        .args(["--parallel", "3"])
+        .args(["--max-runs", "3"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(&cmd);
+    let mut child = spawn_capturing(c);
+    wait_until_ready(&mut child);
+
+    let started = Instant::now();
+    for i in 0..3 {
+        workspace.write(&format!("job-{i}.txt"), "x");
+        thread::sleep(Duration::from_millis(150));
+    }
+    let status = wait_for_exit(&mut child, Duration::from_secs(10));
+    let took = started.elapsed();
+    let mut stdout = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+
+    assert!(status.is_some_and(|s| s.success()), "{status:?}");
+    let log = fs::read_to_string(&stamp).unwrap_or_default();
+    assert_eq!(log.lines().count(), 3, "every job should finish:\n{log}");
+    assert!(
+        took < Duration::from_millis(2_500),
+        "jobs did not overlap: {took:?}"
+    );
+    for job in 1..=3 {
+        assert!(stdout.contains(&format!("[job {job}] start")), "{stdout}");
+    }
+}
+
+#[test]
+fn changed_since_runs_for_files_edited_before_startup() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("edited-while-down.rs", "fn main() {}");
+
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--changed-since", "60", "-e", "rs"],
+    ));
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        fired,
+        "a recently edited file should trigger a run at startup"
+    );
+}
+
+#[test]
+fn changed_since_respects_the_filters() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+
+    // Only the seed .txt file is recent, and the extension filter drops it.
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--changed-since", "60", "-e", "rs"],
+    ));
+    let absent = wait_for_path_absent(&marker, STEADY_STATE);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        absent,
+        "filtered-out files must not trigger the startup run"
+    );
+}
+
+#[test]
+fn throttle_spaces_runs_under_continuous_changes() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let throttle = Duration::from_millis(600);
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--throttle", "600"],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    // Change a file every 50ms for 2.5s and note when each run lands.
+    let mut runs = Vec::new();
+    let mut seen = 0;
+    let start = Instant::now();
+    let mut i = 0;
+    while start.elapsed() < Duration::from_millis(2500) {
+        workspace.write("busy.txt", &i.to_string());
+        i += 1;
+        let until = Instant::now() + Duration::from_millis(50);
+        while Instant::now() < until {
+            let len = fs::metadata(&marker).map(|m| m.len()).unwrap_or(0);
+            if len > seen {
+                seen = len;
+                runs.push(Instant::now());
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(
+        runs.len() >= 2,
+        "continuous changes should still run; saw {runs:?}"
+    );
+    // Allow for the polling granularity when timing each run.
+    let slack = Duration::from_millis(60);
+    for pair in runs.windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(gap + slack >= throttle, "runs only {gap:?} apart");
+    }
+}
+
+#[test]
+fn no_recursive_ignores_subdirectories_but_sees_direct_children() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("nested/existing.txt", "seed");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--no-recursive", "-e", "txt"],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("nested/existing.txt", "changed");
+    let nested_ignored = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("direct.txt", "changed");
+    let direct_seen = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        nested_ignored,
+        "a change in a subdirectory must not trigger"
+    );
+    assert!(direct_seen, "a change to a direct child should trigger");
+}
+
+#[cfg(unix)]
+#[test]
+fn content_only_ignores_a_chmod_but_not_a_write() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("script.sh", "echo hi");
+    let script = workspace.watch_dir().join("script.sh");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--content-only", "-e", "sh"],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+    let chmod_ignored = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("script.sh", "echo changed");
+    let write_seen = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        chmod_ignored,
+        "a chmod must not trigger with --content-only"
+    );
+    assert!(write_seen, "a content change should trigger");
+}
+
+#[test]
+fn git_tracked_only_ignores_untracked_files() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("tracked.txt", "v0");
+    workspace.write("build.log", "v0");
+    for args in [&["init", "-q"][..], &["add", "tracked.txt"]] {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(workspace.watch_dir())
+            .args(args)
+            .status()
+            .expect("git should be installed");
+        assert!(status.success(), "git {args:?} failed");
+    }
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--git-tracked-only"],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("build.log", "v1");
+    let untracked_ignored = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("tracked.txt", "v1");
+    let tracked_seen = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        untracked_ignored,
+        "an untracked file must not trigger with --git-tracked-only"
+    );
+    assert!(tracked_seen, "a tracked file should trigger");
+}
+
+#[test]
+fn exclude_output_ignores_the_output_dir_but_not_its_siblings() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("dist/bundle.js", "v0");
+    let dist = workspace.watch_dir().join("dist");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--exclude-output", &dist.to_string_lossy()],
+    ));
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("dist/bundle.js", "v1");
+    workspace.write("dist/assets/logo.svg", "v1");
+    let output_ignored = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("main.js", "v1");
+    let sibling_seen = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        output_ignored,
+        "changes under --exclude-output must not trigger"
+    );
+    assert!(
+        sibling_seen,
+        "a change beside the output dir should trigger"
+    );
+}
+
+#[test]
+fn root_makes_patterns_relative_to_it_from_a_subdirectory() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    workspace.write("app/main.rs", "v0");
+    workspace.write("src/lib.rs", "v0");
+    let mut c = flash();
+    c.current_dir(workspace.watch_dir().join("app"))
+        .args(["--fast", "--debounce", "10", "--root", ".."])
+        .args(["-p", "src/**"])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_silent(c);
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("app/main.rs", "v1");
+    let outside_ignored = wait_for_path_absent(&marker, Duration::from_millis(800));
+    workspace.write("src/lib.rs", "v1");
+    let pattern_matched = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        outside_ignored,
+        "app/main.rs doesn't match src/** under the root"
+    );
+    assert!(
+        pattern_matched,
+        "src/** should match relative to --root, not the working directory"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn await_stable_runs_once_after_a_chunked_write_settles() {
+    let workspace = Workspace::new();
+    let big = workspace.watch_dir().join("big.bin");
+    let log = workspace.marker("runs.log");
+    let mut c = flash();
+    c.args(["--fast", "--debounce", "10", "--await-stable", "400ms"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(format!(
+            "wc -c < '{}' >> '{}'",
+            big.display(),
+            log.display()
+        ));
+    let mut child = spawn_silent(c);
+    thread::sleep(STEADY_STATE);
+
+    let mut file = fs::File::create(&big).unwrap();
+    for _ in 0..8 {
+        file.write_all(&[b'x'; 4096]).unwrap();
+        file.flush().unwrap();
+        thread::sleep(Duration::from_millis(100));
+    }
+    drop(file);
+    let ran = wait_for_path(&log, MAX_E2E);
+    // Long enough for a second run to show up if the queued events caused one.
+    thread::sleep(Duration::from_millis(1_000));
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(ran, "the command should run once the file settles");
+    let runs = fs::read_to_string(&log).unwrap();
+    let sizes: Vec<&str> = runs.lines().map(str::trim).collect();
+    assert_eq!(sizes, ["32768"], "one run, which saw the whole file");
+}
+
+#[test]
+fn watch_extensions_only_picks_up_a_match_in_a_skipped_directory() {
+    let workspace = Workspace::new();
+    workspace.write("src/lib.rs", "");
+    workspace.write("fixtures/input.json", "{}");
+    let marker = workspace.marker("marker");
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--watch-extensions-only", "-e", "rs"],
+    ));
+
+    thread::sleep(STEADY_STATE);
+    let _ = fs::remove_file(&marker);
+
+    workspace.write("fixtures/case.rs", "fn main() {}");
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        fired,
+        "a .rs file added to the skipped fixtures directory should run the command"
+    );
+}
+
+#[test]
+fn fast_mode_does_not_print_the_banner() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut c = flash();
+    c.arg("--fast")
+        .args(["--debounce", "10"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_capturing(c);
+
+    thread::sleep(Duration::from_millis(800));
+    let _ = child.kill();
+    let output = child.wait_with_output().expect("collect output");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        !stdout.contains("watching for changes"),
+        "--fast should suppress the banner; got:\n{stdout}"
+    );
+}
+
+#[test]
+fn summary_interval_replaces_per_change_lines_with_an_aggregate() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut c = flash();
+    c.args(["--debounce", "10", "--summary-interval", "1"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_capturing(c);
+
+    thread::sleep(STEADY_STATE);
+    for i in 0..5 {
+        workspace.write(&format!("burst-{}.txt", i % 2), &format!("v{i}"));
+        thread::sleep(Duration::from_millis(50));
+    }
+    thread::sleep(Duration::from_millis(1500));
+
+    let _ = child.kill();
+    let output = child.wait_with_output().expect("collect output");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout
+            .lines()
+            .any(|l| l.contains(" across ") && l.contains("in the last 1s, ran command")),
+        "expected an aggregate summary line; got:\n{stdout}"
+    );
+    assert!(
+        !stdout.contains('↻') && !stdout.contains("[CHANGE]"),
+        "per-change lines should be suppressed in summary mode; got:\n{stdout}"
+    );
+    assert!(
+        marker.exists(),
+        "the command must still run in summary mode"
+    );
+}
+
+#[test]
+fn default_mode_prints_a_banner_with_watch_roots() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let mut c = flash();
+    c.args(["--debounce", "10"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_capturing(c);
+
+    // Give the banner a moment to print.
+    let banner_deadline = Instant::now() + Duration::from_millis(800);
+    while Instant::now() < banner_deadline {
+        thread::sleep(Duration::from_millis(20));
+    }
+    let _ = child.kill();
+    let output = child.wait_with_output().expect("collect output");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("watching for changes"),
+        "default banner missing; got:\n{stdout}"
+    );
+}
+
+#[test]
+fn console_output_is_valid_utf8_without_mojibake() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    // --changed-since picks up the seed file, so one run covers the banner,
+    // a change line, and the ready line without touching anything.
+    let mut c = flash();
+    c.args(["--debounce", "10"])
+        .args(["--changed-since", "60", "--max-runs", "1"])
+        .args(["-w", &workspace.watch_str()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_capturing(c);
+    let status = wait_for_exit(&mut child, MAX_E2E);
+    assert!(status.is_some(), "--max-runs 1 should exit on its own");
+    let output = child.wait_with_output().expect("collect output");
+
+    let stdout = String::from_utf8(output.stdout).expect("stdout is valid UTF-8");
+    assert!(
+        stdout.contains("watching for changes") && stdout.contains("seed.txt"),
+        "expected the banner and a change line; got:\n{stdout}"
+    );
+    // Glyphs whose UTF-8 bytes were decoded as Latin-1/CP-1252 and encoded
+    // again start with these ("â–¶ï¸", "ðŸ”¥", ...).
+    for garbled in ["\u{FFFD}", "â", "ð", "ï¸", "Ã"] {
+        assert!(
+            !stdout.contains(garbled),
+            "output contains mojibake {garbled:?}:\n{stdout}"
+        );
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn label_and_timestamps_prefix_change_and_exit_lines() {
+    let workspace = Workspace::new();
+    let mut c = flash();
+    c.args(["--label", "api", "--timestamps"])
+        .args(["--changed-since", "60", "--max-runs", "1"])
+        .args(["-w", &workspace.watch_str()])
+        .arg("false");
+    let mut child = spawn_capturing(c);
+    assert!(
+        wait_for_exit(&mut child, MAX_E2E).is_some(),
+        "--max-runs 1 should exit on its own"
+    );
+    let output = child.wait_with_output().expect("collect output");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    // "[api] HH:MM:SS <text>"
+    let prefixed = |line: &str| {
+        line.strip_prefix("[api] ").is_some_and(|rest| {
+            let bytes = rest.as_bytes();
+            bytes.len() > 9
+                && bytes[2] == b':'
+                && bytes[5] == b':'
+                && bytes[8] == b' '
+                && [0, 1, 3, 4, 6, 7]
+                    .iter()
+                    .all(|&i| bytes[i].is_ascii_digit())
+        })
+    };
+    let change = stdout.lines().find(|l| l.contains("seed.txt"));
+    assert!(
+        change.is_some_and(prefixed),
+        "change line should carry the label and time; got:\n{stdout}"
+    );
+    let exit = stderr.lines().find(|l| l.contains("command exited with"));
+    assert!(
+        exit.is_some_and(prefixed),
+        "exit notice should carry the label and time; got:\n{stderr}"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_runs_the_after_command_before_exiting() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let cleanup = workspace.marker("cleaned-up");
+    let after = format!("printf x > {}", cleanup.display());
+    let mut child = spawn_capturing(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--ready-fd", "1", "--after", &after],
+    ));
+    wait_until_ready(&mut child);
+
+    let sent = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .expect("run kill");
+    assert!(sent.success());
+
+    let status = wait_for_exit(&mut child, MAX_E2E);
+    if status.is_none() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+    assert_eq!(
+        status.and_then(|s| s.code()),
+        Some(130),
+        "Ctrl-C should exit 130"
+    );
+    assert!(cleanup.exists(), "--after should run on Ctrl-C");
+}
+
+#[cfg(unix)]
+#[test]
+fn file_glob_watch_fires_only_for_matching_files() {
+    let workspace = Workspace::new();
+    let src = workspace.watch_dir().join("src");
+    fs::create_dir(&src).unwrap();
+    fs::write(src.join("lib.rs"), "v0").unwrap();
+    let marker = workspace.marker("marker");
+    let glob = format!("{}/*/*.rs", workspace.watch_str());
+    let mut child = spawn_capturing(watcher_command(&glob, &marker, &["--ready-fd", "1"]));
+    wait_until_ready(&mut child);
+
+    fs::write(src.join("notes.txt"), "sibling").unwrap();
+    let quiet = wait_for_path_absent(&marker, Duration::from_millis(500));
+    fs::write(src.join("lib.rs"), "v1").unwrap();
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(quiet, "a non-matching sibling must not run the command");
+    assert!(
+        fired,
+        "a file matching the --watch glob should run the command"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn single_star_glob_watches_direct_children_but_not_below() {
+    let workspace = Workspace::new();
+    let deep = workspace.watch_dir().join("src").join("a").join("deep");
+    fs::create_dir_all(&deep).unwrap();
+    workspace.write("src/a/x.txt", "v0");
+    workspace.write("src/a/deep/y.txt", "v0");
+    let marker = workspace.marker("marker");
+    let glob = format!("{}/src/*", workspace.watch_str());
+    let mut child = spawn_capturing(watcher_command(&glob, &marker, &["--ready-fd", "1"]));
+    wait_until_ready(&mut child);
+
+    workspace.write("src/a/deep/y.txt", "v1");
+    let quiet = wait_for_path_absent(&marker, Duration::from_millis(500));
+    workspace.write("src/a/x.txt", "v1");
+    let fired = wait_for_path(&marker, MAX_E2E);
+    let _ = child.kill();
+    let _ = child.wait();
+
+    assert!(quiet, "a change two levels below the glob must not run");
+    assert!(fired, "a change in a directory the glob matched should run");
+}
+
+#[test]
+fn log_file_gets_flash_output_without_colors() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    // Inside the watch root, so logging a change must not count as one.
+    let log = workspace.watch_dir().join("flash.log");
+    let mut c = flash();
+    c.env("CLICOLOR_FORCE", "1")
+        .args(["--debounce", "10"])
+        .args(["--log-file", &log.to_string_lossy()])
+        .args(["-w", &workspace.watch_str()])
+        .arg(touch_marker_cmd(&marker));
+    let mut child = spawn_silent(c);
+    thread::sleep(STEADY_STATE);
+
+    workspace.write("changed.txt", "v1");
+    let ran = wait_for_path(&marker, MAX_E2E);
+    thread::sleep(STEADY_STATE);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(ran, "the change should run the command");
+    assert_eq!(
+        fs::read_to_string(&marker).unwrap(),
+        "x",
+        "writes to the log file must not trigger runs"
+    );
+    let logged = fs::read_to_string(&log).unwrap();
+    assert!(
+        logged.lines().any(|l| l.ends_with("changed.txt")),
+        "the change line should be logged; got:\n{logged}"
+    );
+    assert!(
+        logged.lines().any(|l| l.starts_with("ready")),
+        "startup lines should be logged; got:\n{logged}"
+    );
+    assert!(
+        !logged.contains('\x1b'),
+        "colors must be stripped:\n{logged}"
+    );
+}
+
+#[test]
+fn output_newer_than_the_change_skips_the_run() {
+    let workspace = Workspace::new();
+    let marker = workspace.marker("marker");
+    let output = workspace.marker("app");
+    let built = fs::File::create(&output).unwrap();
+    let mut child = spawn_silent(watcher_command(
+        &workspace.watch_str(),
+        &marker,
+        &["--output", &output.to_string_lossy()],
+    ));
+    thread::sleep(STEADY_STATE);
+
+    built
+        .set_modified(std::time::SystemTime::now() + Duration::from_secs(3600))
+        .unwrap();
+    workspace.write("main.rs", "v1");
+    let skipped = wait_for_path_absent(&marker, Duration::from_millis(800));
+    built
+        .set_modified(std::time::SystemTime::now() - Duration::from_secs(3600))
+        .unwrap();
+    workspace.write("main.rs", "v2");
+    let ran = wait_for_path(&marker, MAX_E2E);
+
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(
+        skipped,
+        "an output newer than the change should skip the run"
     );
+    assert!(ran, "an output older than the change should let it run");
 }